@@ -1,12 +1,24 @@
-use angel::solana_rpc::get_tokens_with_account;
+use angel::{
+    config::{DustConfig, RpcConfig},
+    solana_rpc::SolanaRpc,
+};
 
 #[tokio::main]
 async fn main() {
     let account = "JDLGDgY7jSGkmmRPzQcYtwLQpkrqMgYqY7cFkNb81NTq"; // 当前要有持仓才行
     let solana_rpc_url = "https://api.mainnet-beta.solana.com";
-    match get_tokens_with_account(account, solana_rpc_url).await {
-        Ok(tokens) => {
-            for token in tokens {
+    let rpc = SolanaRpc::new(
+        RpcConfig::default(),
+        vec![(solana_rpc_url.to_string(), 1)],
+        10,
+    )
+    .unwrap();
+    match rpc
+        .get_tokens_with_account(account, &DustConfig::default())
+        .await
+    {
+        Ok(holdings) => {
+            for token in holdings.accounts {
                 println!("111 {:?}", token);
             }
         }
@@ -0,0 +1,292 @@
+use serde::Serialize;
+
+use crate::{
+    config::{ConcentrationConfig, ConcentrationMetric},
+    holders::HolderKind,
+};
+
+// one of the largest token accounts, with what is known about who owns it
+#[derive(Debug, Clone, Default)]
+pub struct LargestHolder {
+    pub address: String, // the token account
+    pub amount: u64,
+    pub owner: Option<String>,
+    pub kind: HolderKind,
+    pub label: Option<String>,           // name from the holder registry
+    pub tracked_account: Option<String>, // the tracked wallet owning the token account
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HolderShare {
+    pub address: String,
+    pub owner: Option<String>,
+    pub kind: HolderKind,
+    pub label: Option<String>,
+    pub amount: String, // raw u64 amount
+    pub ui_amount: f64,
+    pub share: f64, // of the total supply, 0..=1
+    pub tracked_account: Option<String>,
+    pub excluded: bool, // left out of the metrics, see exclude in [concentration]
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Reason {
+    pub metric: ConcentrationMetric,
+    pub value: f64,
+    pub max: f64,
+    pub score: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConcentrationReport {
+    pub token: String,
+    pub supply: String, // raw u64 supply
+    pub decimals: u8,
+    pub holders: Vec<HolderShare>, // largest first, excluded ones included
+    pub excluded_share: f64,       // held by pools, bonding curves and burn addresses
+    pub large_holders: usize,
+    pub top_holder_share: f64,
+    pub top10_share: f64,
+    pub hhi: f64,
+    pub gini: f64,
+    pub tracked_holders: usize,
+    pub tracked_share: f64,
+    pub score: u32, // 0..=100
+    pub is_suspicion: bool,
+    pub reasons: Vec<Reason>, // the rules that fired
+}
+
+impl ConcentrationReport {
+    // holders come from getTokenLargestAccounts, large_threshold is a ui amount. the metrics
+    // only look at holders whose kind is not in config.exclude
+    pub fn new(
+        token: &str,
+        supply: u64,
+        decimals: u8,
+        holders: Vec<LargestHolder>,
+        large_threshold: f64,
+        config: &ConcentrationConfig,
+    ) -> Self {
+        let scale = 10f64.powi(decimals as i32);
+        let share_of = |amount: u64| {
+            if supply == 0 {
+                0.0
+            } else {
+                amount as f64 / supply as f64
+            }
+        };
+
+        let mut holders = holders;
+        holders.sort_by_key(|h| std::cmp::Reverse(h.amount));
+        let amounts: Vec<u64> = holders
+            .iter()
+            .filter(|h| !config.exclude.contains(&h.kind))
+            .map(|h| h.amount)
+            .collect();
+        let holders: Vec<HolderShare> = holders
+            .into_iter()
+            .map(|h| HolderShare {
+                excluded: config.exclude.contains(&h.kind),
+                ui_amount: h.amount as f64 / scale,
+                share: share_of(h.amount),
+                amount: h.amount.to_string(),
+                address: h.address,
+                owner: h.owner,
+                kind: h.kind,
+                label: h.label,
+                tracked_account: h.tracked_account,
+            })
+            .collect();
+
+        let counted: Vec<&HolderShare> = holders.iter().filter(|h| !h.excluded).collect();
+        let large_holders = counted
+            .iter()
+            .filter(|h| h.ui_amount > large_threshold)
+            .count();
+        let top_holder_share = counted.first().map_or(0.0, |h| h.share);
+        let top10_share = counted.iter().take(10).map(|h| h.share).sum();
+        let hhi = counted.iter().map(|h| h.share * h.share).sum();
+        let tracked_holders = counted
+            .iter()
+            .filter(|h| h.tracked_account.is_some())
+            .count();
+        let tracked_share = counted
+            .iter()
+            .filter(|h| h.tracked_account.is_some())
+            .map(|h| h.share)
+            .sum();
+        let excluded_share = holders.iter().filter(|h| h.excluded).map(|h| h.share).sum();
+
+        let mut report = Self {
+            token: token.to_string(),
+            supply: supply.to_string(),
+            decimals,
+            holders,
+            excluded_share,
+            large_holders,
+            top_holder_share,
+            top10_share,
+            hhi,
+            gini: gini(&amounts),
+            tracked_holders,
+            tracked_share,
+            score: 0,
+            is_suspicion: false,
+            reasons: vec![],
+        };
+        for rule in &config.rules {
+            let value = report.metric(rule.metric);
+            if value > rule.max {
+                report.reasons.push(Reason {
+                    metric: rule.metric,
+                    value,
+                    max: rule.max,
+                    score: rule.score,
+                });
+            }
+        }
+        report.score = report.reasons.iter().map(|r| r.score).sum::<u32>().min(100);
+        report.is_suspicion = !report.reasons.is_empty() && report.score >= config.suspicion_score;
+        report
+    }
+
+    pub fn metric(&self, metric: ConcentrationMetric) -> f64 {
+        match metric {
+            ConcentrationMetric::LargeHolders => self.large_holders as f64,
+            ConcentrationMetric::TopHolderShare => self.top_holder_share,
+            ConcentrationMetric::Top10Share => self.top10_share,
+            ConcentrationMetric::Hhi => self.hhi,
+            ConcentrationMetric::Gini => self.gini,
+            ConcentrationMetric::TrackedHolders => self.tracked_holders as f64,
+            ConcentrationMetric::TrackedShare => self.tracked_share,
+        }
+    }
+}
+
+// gini coefficient of the amounts, 0 when they are all equal
+fn gini(amounts: &[u64]) -> f64 {
+    let total: f64 = amounts.iter().map(|&a| a as f64).sum();
+    if amounts.is_empty() || total == 0.0 {
+        return 0.0;
+    }
+    let mut sorted = amounts.to_vec();
+    sorted.sort();
+    let n = sorted.len() as f64;
+    let weighted: f64 = sorted
+        .iter()
+        .enumerate()
+        .map(|(i, &a)| (i + 1) as f64 * a as f64)
+        .sum();
+    2.0 * weighted / (n * total) - (n + 1.0) / n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ConcentrationRule;
+
+    fn holders(amounts: &[u64]) -> Vec<LargestHolder> {
+        amounts
+            .iter()
+            .enumerate()
+            .map(|(i, &amount)| LargestHolder {
+                address: format!("ata{}", i),
+                amount,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn test_gini() {
+        assert_eq!(gini(&[]), 0.0);
+        assert!(close(gini(&[5, 5, 5, 5]), 0.0));
+        assert!(close(gini(&[0, 0, 0, 10]), 0.75));
+    }
+
+    #[test]
+    fn test_metrics() {
+        // a 40% pool, two 10% wallets and nine 1% wallets, decimals 2
+        let mut amounts = vec![40_000, 10_000, 10_000];
+        amounts.extend([1_000; 9]);
+        let mut holders = holders(&amounts);
+        holders[0].kind = HolderKind::RaydiumAmm;
+        holders[0].label = Some("Raydium AMM v4 authority".to_string());
+        holders[2].tracked_account = Some("evil".to_string());
+        let report = ConcentrationReport::new(
+            "mint",
+            100_000,
+            2,
+            holders,
+            50.0,
+            &ConcentrationConfig::default(),
+        );
+
+        assert_eq!(report.holders[0].ui_amount, 400.0);
+        assert!(report.holders[0].excluded);
+        assert_eq!(report.holders[0].kind, HolderKind::RaydiumAmm);
+        assert!(close(report.excluded_share, 0.4));
+        // the pool does not count, the largest holder is a 10% wallet
+        assert!(close(report.top_holder_share, 0.1));
+        assert!(close(report.top10_share, 0.28));
+        assert!(close(report.hhi, 0.01 + 0.01 + 9.0 * 0.0001));
+        assert_eq!(report.large_holders, 2);
+        assert_eq!(report.tracked_holders, 1);
+        assert!(close(report.tracked_share, 0.1));
+        assert_eq!(report.holders[2].tracked_account.as_deref(), Some("evil"));
+
+        let fired: Vec<ConcentrationMetric> = report.reasons.iter().map(|r| r.metric).collect();
+        assert_eq!(
+            fired,
+            vec![
+                ConcentrationMetric::LargeHolders,
+                ConcentrationMetric::TrackedHolders,
+            ]
+        );
+        assert_eq!(report.score, 100);
+        assert!(report.is_suspicion);
+    }
+
+    #[test]
+    fn test_rules() {
+        let config = ConcentrationConfig {
+            rules: vec![
+                ConcentrationRule {
+                    metric: ConcentrationMetric::TopHolderShare,
+                    max: 0.3,
+                    score: 30,
+                },
+                ConcentrationRule {
+                    metric: ConcentrationMetric::Hhi,
+                    max: 0.5,
+                    score: 40,
+                },
+            ],
+            suspicion_score: 50,
+            exclude: vec![],
+            labels: vec![],
+        };
+        let mut pool = holders(&[100, 400]);
+        pool[1].kind = HolderKind::PumpFunBondingCurve;
+        let report = ConcentrationReport::new("mint", 1_000, 0, pool, 1_000_000.0, &config);
+        // nothing is excluded, so the bonding curve counts
+        assert_eq!(report.holders[0].address, "ata1");
+        assert!(!report.holders[0].excluded);
+        assert_eq!(report.reasons.len(), 1);
+        assert_eq!(
+            report.reasons[0].metric,
+            ConcentrationMetric::TopHolderShare
+        );
+        assert_eq!(report.score, 30);
+        assert!(!report.is_suspicion);
+
+        // an empty supply scores nothing instead of dividing by zero
+        let report = ConcentrationReport::new("mint", 0, 0, vec![], 1.0, &config);
+        assert_eq!(report.score, 0);
+        assert!(!report.is_suspicion);
+    }
+}
@@ -1,9 +1,15 @@
+use crate::{
+    holders::{HolderKind, HolderLabel},
+    source::HoldingsSourceKind,
+};
 use anyhow::Result;
-use std::{env, str::FromStr, sync::Arc};
+use solana_sdk::commitment_config::CommitmentLevel;
+use std::{collections::HashMap, env, str::FromStr, sync::Arc};
 use tokio::{fs, sync::OnceCell};
-use validator::Validate;
+use validator::{Validate, ValidationError};
 
 #[derive(Clone, Debug, Validate, serde::Deserialize)]
+#[validate(schema(function = "validate_rpc_endpoints"))]
 pub struct Config {
     #[validate(length(min = 1))]
     pub database_url: String, // database url
@@ -13,9 +19,434 @@ pub struct Config {
     pub solana_rpc_url: String, // solana rpc url, split by , eg https://a.com, https://b.com
     #[validate(range(min = 1))]
     pub solana_rpc_curl_interval: u64, // solana rpc curl interval, eg 60 -> 60s
+    #[serde(default = "default_daemon_workers")]
+    #[validate(range(min = 1))]
+    pub daemon_workers: usize, // 同时同步的账户数
+    #[serde(default = "default_solana_rpc_rps")]
+    #[validate(range(min = 1))]
+    pub solana_rpc_rps: u32, // 每个rpc地址每秒最多请求数
+    #[serde(default = "default_owner_scan_interval")]
+    pub owner_scan_interval: u64, // 多久按owner重新扫描一次账户的token账户, 其余轮询只刷新已知token账户的余额, 单位秒, 0每次都扫描
 
     #[validate(range(min = 100000.0))]
     pub check_largest_account_hold_coin: f64, // 要检查的最大账户持有币种数量,不能大于这个数量
+
+    #[serde(default)]
+    #[validate(nested)]
+    pub rpc: RpcConfig, // rpc请求的commitment/超时/重试
+
+    #[serde(default)]
+    #[validate(nested)]
+    pub dust: DustConfig, // 持仓低于阈值的币不跟踪
+
+    #[serde(default)]
+    #[validate(nested)]
+    pub concentration: ConcentrationConfig, // 最大持有者集中度的打分规则
+
+    #[serde(default)]
+    #[validate(nested)]
+    pub history: HistoryConfig, // 按交易记录同步关注账户的每笔余额变化
+
+    #[serde(default)]
+    #[validate(nested)]
+    pub stream: StreamConfig, // 持仓变化的来源: 轮询/websocket/grpc
+
+    #[serde(default)]
+    #[validate(nested)]
+    pub webhooks: Vec<WebhookConfig>, // 建仓/清仓时推送的webhook, 可以配置多个
+
+    #[validate(nested)]
+    pub telegram: Option<TelegramConfig>, // 买入/可疑币种推送到telegram, 不配置就不推送
+}
+
+#[derive(Clone, Debug, Validate, serde::Deserialize)]
+pub struct RpcConfig {
+    #[serde(default = "default_rpc_commitment")]
+    pub commitment: CommitmentLevel, // processed, confirmed or finalized, for reads and subscriptions
+    #[serde(default = "default_rpc_timeout")]
+    #[validate(range(min = 1))]
+    pub timeout: u64, // request timeout, eg 30 -> 30s
+    #[serde(default = "default_rpc_max_retries")]
+    pub max_retries: u32, // retries on 429/5xx and network errors
+    #[serde(default = "default_rpc_backoff_ms")]
+    #[validate(range(min = 1))]
+    pub backoff_ms: u64, // delay before the first retry, doubled on every retry, with jitter
+    #[serde(default)]
+    #[validate(nested)]
+    pub endpoints: Vec<RpcEndpoint>, // added to solana_rpc_url, or sets the weight of a url in it
+    #[serde(default)]
+    pub sticky: bool, // stay on one endpoint until it fails instead of spreading requests
+    #[serde(default = "default_rpc_eject_after")]
+    #[validate(range(min = 1))]
+    pub eject_after: u32, // failures in a row before an endpoint is ejected
+    #[serde(default = "default_rpc_eject_secs")]
+    pub eject_secs: u64, // how long an ejected endpoint is left alone, eg 30 -> 30s
+}
+
+#[derive(Clone, Debug, Validate, serde::Deserialize)]
+pub struct RpcEndpoint {
+    #[validate(url)]
+    pub url: String,
+    #[serde(default = "default_rpc_weight")]
+    pub weight: u32, // share of the requests, 0 means only when every other endpoint is ejected
+}
+
+impl Default for RpcConfig {
+    fn default() -> Self {
+        Self {
+            commitment: default_rpc_commitment(),
+            timeout: default_rpc_timeout(),
+            max_retries: default_rpc_max_retries(),
+            backoff_ms: default_rpc_backoff_ms(),
+            endpoints: vec![],
+            sticky: false,
+            eject_after: default_rpc_eject_after(),
+            eject_secs: default_rpc_eject_secs(),
+        }
+    }
+}
+
+// slower than confirmed but never rolled back, a position seen once stays
+fn default_rpc_commitment() -> CommitmentLevel {
+    CommitmentLevel::Finalized
+}
+
+fn default_rpc_timeout() -> u64 {
+    30
+}
+
+fn default_rpc_max_retries() -> u32 {
+    3
+}
+
+fn default_rpc_backoff_ms() -> u64 {
+    500
+}
+
+fn default_rpc_eject_after() -> u32 {
+    3
+}
+
+fn default_rpc_eject_secs() -> u64 {
+    30
+}
+
+fn default_rpc_weight() -> u32 {
+    1
+}
+
+#[derive(Clone, Debug, Validate, serde::Deserialize)]
+pub struct DustConfig {
+    #[serde(default = "default_dust_min_ui_amount")]
+    #[validate(range(min = 0.0))]
+    pub min_ui_amount: f64, // holdings below this ui amount are dust
+    #[serde(default)]
+    pub accounts: HashMap<String, f64>, // account -> min ui amount
+    #[serde(default)]
+    pub mints: HashMap<String, f64>, // mint -> min ui amount, wins over the account
+}
+
+impl Default for DustConfig {
+    fn default() -> Self {
+        Self {
+            min_ui_amount: default_dust_min_ui_amount(),
+            accounts: HashMap::new(),
+            mints: HashMap::new(),
+        }
+    }
+}
+
+fn default_dust_min_ui_amount() -> f64 {
+    100000.0
+}
+
+impl DustConfig {
+    // the mint override first, then the account override, then the global threshold
+    pub fn min_ui_amount(&self, account: &str, mint: &str) -> f64 {
+        self.mints
+            .get(mint)
+            .or_else(|| self.accounts.get(account))
+            .copied()
+            .unwrap_or(self.min_ui_amount)
+    }
+
+    pub fn is_dust(&self, account: &str, mint: &str, ui_amount: f64) -> bool {
+        ui_amount < self.min_ui_amount(account, mint)
+    }
+}
+
+#[derive(Clone, Debug, Validate, serde::Deserialize)]
+pub struct HistoryConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_history_interval")]
+    #[validate(range(min = 1))]
+    pub interval: u64, // seconds between two ingestion passes
+    #[serde(default = "default_history_page_size")]
+    #[validate(range(min = 1, max = 1000))]
+    pub page_size: usize, // signatures per getSignaturesForAddress call
+    #[serde(default = "default_history_backfill_pages")]
+    #[validate(range(min = 1))]
+    pub backfill_pages: usize, // pages read for an account seen for the first time
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval: default_history_interval(),
+            page_size: default_history_page_size(),
+            backfill_pages: default_history_backfill_pages(),
+        }
+    }
+}
+
+fn default_history_interval() -> u64 {
+    60
+}
+
+fn default_history_page_size() -> usize {
+    100
+}
+
+fn default_history_backfill_pages() -> usize {
+    1
+}
+
+#[derive(Clone, Debug, Validate, serde::Deserialize)]
+pub struct StreamConfig {
+    #[serde(default)]
+    pub source: HoldingsSourceKind, // polling, pubsub or grpc
+    #[serde(default)]
+    pub ws_url: Option<String>, // none: the first solana_rpc_url with http(s) replaced by ws(s)
+    #[serde(default)]
+    pub grpc_url: Option<String>, // yellowstone endpoint, required for the grpc source
+    #[serde(default)]
+    pub grpc_x_token: Option<String>, // sent as the x-token header
+    #[serde(default = "default_stream_poll_interval")]
+    #[validate(range(min = 1))]
+    pub poll_interval: u64, // seconds between polling passes while the stream is connected
+    #[serde(default = "default_stream_account_refresh")]
+    #[validate(range(min = 1))]
+    pub account_refresh: u64, // seconds between looking for accounts to (un)subscribe
+    #[serde(default = "default_stream_max_backoff")]
+    #[validate(range(min = 1))]
+    pub max_backoff: u64, // longest wait before a reconnect, seconds
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self {
+            source: HoldingsSourceKind::default(),
+            ws_url: None,
+            grpc_url: None,
+            grpc_x_token: None,
+            poll_interval: default_stream_poll_interval(),
+            account_refresh: default_stream_account_refresh(),
+            max_backoff: default_stream_max_backoff(),
+        }
+    }
+}
+
+fn default_stream_poll_interval() -> u64 {
+    300
+}
+
+fn default_stream_account_refresh() -> u64 {
+    30
+}
+
+fn default_stream_max_backoff() -> u64 {
+    60
+}
+
+// what a concentration rule looks at, shares are of the total supply in 0..=1
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConcentrationMetric {
+    LargeHolders,   // holders above check_largest_account_hold_coin
+    TopHolderShare, // share of the largest holder
+    Top10Share,
+    Hhi,  // sum of squared holder shares
+    Gini, // inequality among the largest holders, 0 even .. 1 one holder has it all
+    TrackedHolders,
+    TrackedShare,
+}
+
+impl ConcentrationMetric {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConcentrationMetric::LargeHolders => "large_holders",
+            ConcentrationMetric::TopHolderShare => "top_holder_share",
+            ConcentrationMetric::Top10Share => "top10_share",
+            ConcentrationMetric::Hhi => "hhi",
+            ConcentrationMetric::Gini => "gini",
+            ConcentrationMetric::TrackedHolders => "tracked_holders",
+            ConcentrationMetric::TrackedShare => "tracked_share",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Validate, serde::Deserialize)]
+pub struct ConcentrationRule {
+    pub metric: ConcentrationMetric,
+    pub max: f64,   // the rule fires when the metric is above this
+    pub score: u32, // added to the risk score when it fires
+}
+
+#[derive(Clone, Debug, Validate, serde::Deserialize)]
+pub struct ConcentrationConfig {
+    #[serde(default = "default_concentration_rules")]
+    #[validate(nested)]
+    pub rules: Vec<ConcentrationRule>,
+    #[serde(default = "default_concentration_suspicion_score")]
+    pub suspicion_score: u32, // a token scoring this or more is suspicious
+    #[serde(default = "default_concentration_exclude")]
+    pub exclude: Vec<HolderKind>, // holders of these kinds are labelled but left out of the metrics
+    #[serde(default)]
+    #[validate(nested)]
+    pub labels: Vec<HolderLabel>, // extra pools, exchanges and programs for the holder registry
+}
+
+impl Default for ConcentrationConfig {
+    fn default() -> Self {
+        Self {
+            rules: default_concentration_rules(),
+            suspicion_score: default_concentration_suspicion_score(),
+            exclude: default_concentration_exclude(),
+            labels: vec![],
+        }
+    }
+}
+
+// the first rule is the old check: a holder above check_largest_account_hold_coin that is
+// not a pool
+fn default_concentration_rules() -> Vec<ConcentrationRule> {
+    [
+        (ConcentrationMetric::LargeHolders, 0.0, 50),
+        (ConcentrationMetric::TrackedHolders, 0.0, 50),
+        (ConcentrationMetric::Top10Share, 0.5, 25),
+        (ConcentrationMetric::Gini, 0.9, 10),
+    ]
+    .into_iter()
+    .map(|(metric, max, score)| ConcentrationRule { metric, max, score })
+    .collect()
+}
+
+fn default_concentration_exclude() -> Vec<HolderKind> {
+    vec![
+        HolderKind::RaydiumAmm,
+        HolderKind::RaydiumCpmm,
+        HolderKind::RaydiumClmm,
+        HolderKind::OrcaWhirlpool,
+        HolderKind::Meteora,
+        HolderKind::PumpFunBondingCurve,
+        HolderKind::Burn,
+    ]
+}
+
+fn default_concentration_suspicion_score() -> u32 {
+    50
+}
+
+#[derive(Clone, Debug, Validate, serde::Deserialize)]
+pub struct WebhookConfig {
+    #[validate(url)]
+    pub url: String,
+    #[validate(length(min = 1))]
+    pub secret: String, // hmac-sha256 key, the signature is sent in X-Angel-Signature
+    #[serde(default = "default_webhook_max_retries")]
+    pub max_retries: u32, // retries after the first attempt
+    #[serde(default = "default_webhook_backoff_ms")]
+    #[validate(range(min = 1))]
+    pub backoff_ms: u64, // delay before the first retry, doubled on every retry
+    #[serde(default = "default_webhook_timeout")]
+    #[validate(range(min = 1))]
+    pub timeout: u64, // request timeout, eg 10 -> 10s
+}
+
+fn default_daemon_workers() -> usize {
+    8
+}
+
+fn default_solana_rpc_rps() -> u32 {
+    10
+}
+
+fn default_owner_scan_interval() -> u64 {
+    600
+}
+
+fn default_webhook_max_retries() -> u32 {
+    5
+}
+
+fn default_webhook_backoff_ms() -> u64 {
+    1000
+}
+
+fn default_webhook_timeout() -> u64 {
+    10
+}
+
+#[derive(Clone, Debug, Validate, serde::Deserialize)]
+pub struct TelegramConfig {
+    #[validate(length(min = 1))]
+    pub bot_token: String,
+    #[serde(default = "default_telegram_api_url")]
+    #[validate(url)]
+    pub api_url: String, // bot api base url, point it at a mock in tests
+    #[serde(default)]
+    pub default_chat_ids: Vec<String>, // accounts that are in no group go here
+    #[serde(default)]
+    pub suspicious_chat_ids: Vec<String>, // suspicious tokens, empty means default_chat_ids
+    #[serde(default = "default_telegram_buy_template")]
+    pub buy_template: String, // {account} {token} {event_type} {amount} {delta} {slot} {group}, amounts are ui amounts
+    #[serde(default = "default_telegram_suspicious_template")]
+    pub suspicious_template: String, // {token} {score} {count} {threshold} {reasons}
+    #[serde(default = "default_telegram_suspicious_cooldown")]
+    pub suspicious_cooldown: i64, // do not repeat the same suspicious token within, eg 3600 -> 3600s
+    #[serde(default)]
+    #[validate(nested)]
+    pub groups: Vec<TelegramGroup>,
+}
+
+#[derive(Clone, Debug, Validate, serde::Deserialize)]
+pub struct TelegramGroup {
+    #[validate(length(min = 1))]
+    pub name: String,
+    #[validate(length(min = 1))]
+    pub accounts: Vec<String>, // tracked account addresses routed to this group
+    #[validate(length(min = 1))]
+    pub chat_ids: Vec<String>,
+    pub buy_template: Option<String>, // overrides TelegramConfig::buy_template
+}
+
+fn default_telegram_api_url() -> String {
+    "https://api.telegram.org".to_string()
+}
+
+fn default_telegram_buy_template() -> String {
+    "[{group}] {account} {event_type} {token}\namount: {amount}, delta: {delta}, slot: {slot}"
+        .to_string()
+}
+
+fn default_telegram_suspicious_template() -> String {
+    "suspicious token {token}: score {score}, {count} holders above {threshold}\n{reasons}"
+        .to_string()
+}
+
+fn default_telegram_suspicious_cooldown() -> i64 {
+    3600
+}
+
+// solana_rpc_url="," passes the length check but leaves no endpoint to ask
+fn validate_rpc_endpoints(config: &Config) -> Result<(), ValidationError> {
+    if config.rpc_endpoints().is_empty() {
+        return Err(ValidationError::new("rpc_endpoints")
+            .with_message("solana_rpc_url or rpc.endpoints needs at least one url".into()));
+    }
+    Ok(())
 }
 
 impl FromStr for Config {
@@ -50,6 +481,41 @@ impl Config {
         let index = rand::random::<usize>() % urls.len();
         urls[index].to_string()
     }
+
+    // the pubsub endpoint, rpc nodes serve it on the same host
+    pub fn stream_ws_url(&self) -> String {
+        if let Some(url) = &self.stream.ws_url {
+            return url.clone();
+        }
+        let url = self.solana_rpc_url.split(',').next().unwrap_or("").trim();
+        if let Some(rest) = url.strip_prefix("https://") {
+            format!("wss://{}", rest)
+        } else if let Some(rest) = url.strip_prefix("http://") {
+            format!("ws://{}", rest)
+        } else {
+            url.to_string()
+        }
+    }
+
+    // solana_rpc_url with weight 1 each, then rpc.endpoints, a repeated url keeps the later weight
+    pub fn rpc_endpoints(&self) -> Vec<(String, u32)> {
+        let mut endpoints: Vec<(String, u32)> = vec![];
+        let urls = self
+            .solana_rpc_url
+            .split(',')
+            .map(|url| (url.trim().to_string(), 1))
+            .chain(self.rpc.endpoints.iter().map(|e| (e.url.clone(), e.weight)));
+        for (url, weight) in urls {
+            if url.is_empty() {
+                continue;
+            }
+            match endpoints.iter_mut().find(|(known, _)| *known == url) {
+                Some(endpoint) => endpoint.1 = weight,
+                None => endpoints.push((url, weight)),
+            }
+        }
+        endpoints
+    }
 }
 
 #[cfg(test)]
@@ -63,14 +529,24 @@ mod tests {
             host_uri: "localhost:8080".to_string(),
             solana_rpc_url: "https://a.com,https://b.com,https://c.com".to_string(),
             solana_rpc_curl_interval: 60,
+            daemon_workers: 8,
+            solana_rpc_rps: 10,
+            owner_scan_interval: 600,
+            rpc: RpcConfig::default(),
+            dust: DustConfig::default(),
+            concentration: ConcentrationConfig::default(),
+            history: HistoryConfig::default(),
+            stream: StreamConfig::default(),
             check_largest_account_hold_coin: 100000.0,
+            webhooks: vec![],
+            telegram: None,
         };
 
         let url1 = config.get_random_solana_rpc_url();
-        assert!(vec!["https://a.com", "https://b.com", "https://c.com"].contains(&url1.as_str()));
+        assert!(["https://a.com", "https://b.com", "https://c.com"].contains(&url1.as_str()));
 
         let url2 = config.get_random_solana_rpc_url();
-        assert!(vec!["https://a.com", "https://b.com", "https://c.com"].contains(&url2.as_str()));
+        assert!(["https://a.com", "https://b.com", "https://c.com"].contains(&url2.as_str()));
 
         // Test single URL case
         let config_single = Config {
@@ -78,10 +554,150 @@ mod tests {
             host_uri: "localhost:8080".to_string(),
             solana_rpc_url: "https://single.com".to_string(),
             solana_rpc_curl_interval: 60,
+            daemon_workers: 8,
+            solana_rpc_rps: 10,
+            owner_scan_interval: 600,
+            rpc: RpcConfig::default(),
+            dust: DustConfig::default(),
+            concentration: ConcentrationConfig::default(),
+            history: HistoryConfig::default(),
+            stream: StreamConfig::default(),
             check_largest_account_hold_coin: 100000.0,
+            webhooks: vec![],
+            telegram: None,
         };
 
         let single_url = config_single.get_random_solana_rpc_url();
         assert_eq!(single_url, "https://single.com");
     }
+
+    #[test]
+    fn test_parse_webhooks() {
+        let config: Config = r#"
+            database_url="sqlite://./data/db.sqlite3"
+            host_uri="127.0.0.1:2211"
+            solana_rpc_url="https://api.mainnet-beta.solana.com"
+            solana_rpc_curl_interval=10
+            check_largest_account_hold_coin=100000000.0
+
+            [[webhooks]]
+            url="https://example.com/hook"
+            secret="s"
+
+            [[webhooks]]
+            url="not a url"
+            secret="s"
+            max_retries=1
+        "#
+        .parse()
+        .unwrap();
+
+        assert_eq!(config.daemon_workers, 8);
+        assert_eq!(config.rpc.commitment, CommitmentLevel::Finalized);
+        assert_eq!(config.rpc.max_retries, 3);
+        assert_eq!(config.solana_rpc_rps, 10);
+        assert_eq!(config.owner_scan_interval, 600);
+        assert_eq!(config.webhooks.len(), 2);
+        assert_eq!(config.webhooks[0].max_retries, 5);
+        assert_eq!(config.webhooks[0].backoff_ms, 1000);
+        assert_eq!(config.webhooks[1].max_retries, 1);
+        assert!(config.webhooks[0].validate().is_ok());
+        assert_eq!(
+            config.rpc_endpoints(),
+            vec![("https://api.mainnet-beta.solana.com".to_string(), 1)]
+        );
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_rpc_endpoints() {
+        let config: Config = r#"
+            database_url="sqlite://./data/db.sqlite3"
+            host_uri="127.0.0.1:2211"
+            solana_rpc_url="https://a.com, https://b.com,"
+            solana_rpc_curl_interval=10
+            check_largest_account_hold_coin=100000000.0
+
+            [rpc]
+            sticky=true
+            commitment="confirmed"
+
+            [[rpc.endpoints]]
+            url="https://b.com"
+            weight=5
+
+            [[rpc.endpoints]]
+            url="https://c.com"
+        "#
+        .parse()
+        .unwrap();
+
+        assert!(config.validate().is_ok());
+        // only separators is no endpoint at all
+        let empty = Config {
+            solana_rpc_url: ",".to_string(),
+            rpc: RpcConfig::default(),
+            ..config.clone()
+        };
+        assert!(empty.rpc_endpoints().is_empty());
+        assert!(empty.validate().is_err());
+        assert!(config.rpc.sticky);
+        assert_eq!(config.rpc.commitment, CommitmentLevel::Confirmed);
+        assert_eq!(config.rpc.eject_after, 3);
+        assert_eq!(
+            config.rpc_endpoints(),
+            vec![
+                ("https://a.com".to_string(), 1),
+                ("https://b.com".to_string(), 5),
+                ("https://c.com".to_string(), 1),
+            ]
+        );
+        // the stream follows the first rpc url unless it has its own
+        assert_eq!(config.stream.source, HoldingsSourceKind::Polling);
+        assert_eq!(config.stream_ws_url(), "wss://a.com");
+        let config = Config {
+            stream: StreamConfig {
+                ws_url: Some("ws://127.0.0.1:8900".to_string()),
+                ..Default::default()
+            },
+            ..config
+        };
+        assert_eq!(config.stream_ws_url(), "ws://127.0.0.1:8900");
+    }
+
+    #[test]
+    fn test_dust_threshold() {
+        let config: Config = r#"
+            database_url="sqlite://./data/db.sqlite3"
+            host_uri="127.0.0.1:2211"
+            solana_rpc_url="https://api.mainnet-beta.solana.com"
+            solana_rpc_curl_interval=10
+            check_largest_account_hold_coin=100000000.0
+
+            [dust]
+            min_ui_amount=1000.0
+
+            [dust.accounts]
+            whale=10.0
+
+            [dust.mints]
+            sol=0.5
+        "#
+        .parse()
+        .unwrap();
+        let dust = &config.dust;
+
+        assert_eq!(dust.min_ui_amount("evil", "meme"), 1000.0);
+        assert_eq!(dust.min_ui_amount("whale", "meme"), 10.0);
+        assert_eq!(dust.min_ui_amount("whale", "sol"), 0.5);
+
+        assert!(dust.is_dust("evil", "meme", 999.0));
+        assert!(!dust.is_dust("whale", "meme", 999.0));
+        assert!(!dust.is_dust("evil", "sol", 1.0));
+
+        assert_eq!(
+            DustConfig::default().min_ui_amount("evil", "meme"),
+            100000.0
+        );
+    }
 }
@@ -1,60 +1,848 @@
-use std::time::Duration;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+    str::FromStr,
+    sync::{Arc, LazyLock, Mutex},
+    time::Duration,
+};
 
-use crate::solana_rpc::get_tokens_with_account;
-use tokio::time::sleep;
-use tracing::{error, info};
+use crate::{
+    config::{Config, DustConfig},
+    history, metadata,
+    models::{HoldingSnapshot, NewCoin, PositionEvent, PositionEventType},
+    repository::SharedRepository,
+    solana_rpc::{get_global_solana_rpc, Holdings, MintExtensions, TokenAccounts},
+    source::{holdings_source, wait_next_pass, PollingSource, SharedHoldingsSource},
+    telegram::{get_global_telegram, TelegramNotifier},
+    webhook::WebhookNotifier,
+};
+use anyhow::Result;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use tokio::{
+    sync::{
+        mpsc::{unbounded_channel, UnboundedReceiver},
+        watch, OwnedMutexGuard, Semaphore,
+    },
+    task::JoinSet,
+    time::{sleep, Instant},
+};
+use tracing::{error, info, warn};
 
 use crate::{config::get_global_config, models::get_global_manager};
 
+// notifications arriving this close together are one sync
+const NOTIFICATION_BURST: Duration = Duration::from_millis(500);
+
 pub async fn daemon() {
     // loop and interval
     let c = get_global_config().await;
-    let manager = get_global_manager().await;
-    info!("daemon start");
+    let manager: SharedRepository = get_global_manager().await.clone();
+    let notifier = WebhookNotifier::new(c.webhooks.clone());
+    let telegram = get_global_telegram().await;
+    let interval = Duration::from_secs(c.solana_rpc_curl_interval);
+    info!(
+        "daemon start, workers: {}, rps per endpoint: {}",
+        c.daemon_workers, c.solana_rpc_rps
+    );
+    if c.history.enabled {
+        tokio::spawn(history::history(c, manager.clone()));
+    }
+    let known = Arc::new(KnownTokenAccounts::default());
+    let source = holdings_source(c, manager.clone()).unwrap_or_else(|e| {
+        error!("holdings source error: {:?}, polling instead", e);
+        Arc::new(PollingSource)
+    });
+    info!("holdings source: {}", source.name());
+    let (connected, mut source_status) = watch::channel(false);
+    let (sender, wallets) = unbounded_channel();
+    let watching = source.clone();
+    tokio::spawn(async move { watching.watch(sender, connected).await });
+    tokio::spawn(sync_notified(
+        c,
+        manager.clone(),
+        notifier.clone(),
+        telegram,
+        source.clone(),
+        known.clone(),
+        wallets,
+    ));
     loop {
-        info!("daemon loop, sleep {}s", c.solana_rpc_curl_interval);
-        sleep(Duration::from_secs(c.solana_rpc_curl_interval)).await;
-        // get evil accounts
-        match manager.get_all_accounts().await {
-            Ok(accounts) => {
-                for account in accounts {
-                    match get_tokens_with_account(&account.account, &c.get_random_solana_rpc_url())
-                        .await
-                    {
-                        Ok(tokens) => {
-                            if tokens.len() > 0 {
-                                // del old coins
-                                if let Err(e) =
-                                    manager.del_coin_with_account(&account.account).await
-                                {
-                                    error!(
-                                        "del old coins error: {:?}, account: {}",
-                                        e, &account.account
-                                    );
-                                    continue;
-                                }
-                            }
-                            for token in &tokens {
-                                if let Err(e) =
-                                    manager.add_new_coin(&account.account, &token.mint).await
-                                {
-                                    error!(
-                                        "add new coin error: {:?}, account: {}, token: {}",
-                                        e, &account.account, &token.mint
-                                    );
-                                    continue;
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            error!("get tokens with account error: {:?}", e);
-                        }
-                    }
+        // the next pass is scheduled from the start of this one, not from its end
+        let start = Instant::now();
+        let stats = run_pass(c, &manager, &notifier, telegram, &source, &known).await;
+        let elapsed = start.elapsed();
+        if elapsed > interval {
+            warn!(
+                "daemon pass took {:?} for {} accounts, longer than the {}s interval",
+                elapsed, stats.accounts, c.solana_rpc_curl_interval
+            );
+        } else {
+            info!(
+                "daemon pass took {:?} for {} accounts, {} owner scans, {} dust token accounts skipped",
+                elapsed, stats.accounts, stats.scans, stats.dust
+            );
+        }
+        wait_next_pass(start, c, &mut source_status).await;
+    }
+}
+
+#[derive(Debug, Default)]
+struct PassStats {
+    accounts: usize,
+    scans: usize, // accounts read with getTokenAccountsByOwner, the rest were refreshed
+    dust: usize,  // token accounts below the dust threshold
+}
+
+// the token accounts of each wallet as of its last owner scan, dust included. the passes in
+// between read their balances with getMultipleAccounts, 100 token accounts of any wallets per
+// request, a new token account shows up with the next scan
+#[derive(Default)]
+struct KnownTokenAccounts {
+    wallets: Mutex<HashMap<String, KnownWallet>>,
+}
+
+struct KnownWallet {
+    scanned_at: Instant,
+    accounts: TokenAccounts,
+    extensions: BTreeMap<String, MintExtensions>,
+}
+
+impl KnownTokenAccounts {
+    fn scanned(&self, wallet: &str, holdings: &Holdings) {
+        let mut accounts = holdings.accounts.clone();
+        accounts.extend(holdings.skipped.iter().cloned());
+        self.wallets.lock().unwrap().insert(
+            wallet.to_string(),
+            KnownWallet {
+                scanned_at: Instant::now(),
+                accounts,
+                extensions: holdings.extensions.clone(),
+            },
+        );
+    }
+
+    // never scanned or scanned longer than interval ago
+    fn scan_due(&self, wallet: &str, interval: Duration) -> bool {
+        self.wallets
+            .lock()
+            .unwrap()
+            .get(wallet)
+            .is_none_or(|known| known.scanned_at.elapsed() >= interval)
+    }
+
+    // wallets no longer tracked are forgotten
+    fn retain(&self, wallets: &HashSet<&str>) {
+        self.wallets
+            .lock()
+            .unwrap()
+            .retain(|wallet, _| wallets.contains(wallet.as_str()));
+    }
+
+    // the token account pubkeys of the wallets, in the order refreshed_holdings reads them
+    fn keys(&self, wallets: &[String]) -> Result<Vec<Pubkey>> {
+        let known = self.wallets.lock().unwrap();
+        let mut keys = vec![];
+        for wallet in wallets {
+            for account in known.get(wallet).map_or(&[][..], |k| &k.accounts) {
+                keys.push(Pubkey::from_str(&account.pubkey)?);
+            }
+        }
+        Ok(keys)
+    }
+
+    // the holdings of the wallets from the token accounts fetched for keys, dust skipped again
+    // because a balance may have crossed the threshold. wallets without token accounts are
+    // left out, nothing of theirs can have changed before the next scan
+    fn refreshed_holdings(
+        &self,
+        wallets: &[String],
+        slot: u64,
+        fetched: &[Option<Account>],
+        dust: &DustConfig,
+    ) -> Result<Vec<(String, Holdings)>> {
+        let known = self.wallets.lock().unwrap();
+        let mut fetched = fetched.iter();
+        let mut refreshed = vec![];
+        for wallet in wallets {
+            let Some(known) = known.get(wallet) else {
+                continue;
+            };
+            if known.accounts.is_empty() {
+                continue;
+            }
+            let owner = Pubkey::from_str(wallet)?;
+            let mut holdings = Holdings {
+                slot,
+                extensions: known.extensions.clone(),
+                ..Default::default()
+            };
+            for account in &known.accounts {
+                let found = fetched.next().and_then(|found| found.as_ref());
+                holdings.accounts.extend(account.refreshed(&owner, found));
+            }
+            holdings.skip_dust(wallet, dust);
+            refreshed.push((wallet.clone(), holdings));
+        }
+        Ok(refreshed)
+    }
+}
+
+// balances of the known token accounts of the wallets
+async fn refresh_known(
+    c: &Config,
+    known: &KnownTokenAccounts,
+    wallets: &[String],
+) -> Result<Vec<(String, Holdings)>> {
+    let keys = known.keys(wallets)?;
+    if keys.is_empty() {
+        return Ok(vec![]);
+    }
+    let rpc = get_global_solana_rpc().await;
+    let (slot, fetched) = rpc.get_multiple_accounts_with_slot(&keys).await?;
+    known.refreshed_holdings(wallets, slot, &fetched, &c.dust)
+}
+
+// sync every tracked account, at most daemon_workers at the same time
+async fn run_pass(
+    c: &'static Arc<Config>,
+    manager: &SharedRepository,
+    notifier: &WebhookNotifier,
+    telegram: &'static TelegramNotifier,
+    source: &SharedHoldingsSource,
+    known: &Arc<KnownTokenAccounts>,
+) -> PassStats {
+    // get evil accounts
+    let accounts = match manager.get_all_accounts().await {
+        Ok(accounts) => accounts,
+        Err(e) => {
+            error!("get all accounts error: {:?}", e);
+            return PassStats::default();
+        }
+    };
+
+    let mut stats = PassStats {
+        accounts: accounts.len(),
+        ..Default::default()
+    };
+    known.retain(&accounts.iter().map(|a| a.account.as_str()).collect());
+    let scan_interval = Duration::from_secs(c.owner_scan_interval);
+    let (mut scans, refresh): (Vec<String>, Vec<String>) = accounts
+        .into_iter()
+        .map(|account| account.account)
+        .partition(|account| known.scan_due(account, scan_interval));
+    let refreshed = match refresh_known(c, known, &refresh).await {
+        Ok(refreshed) => refreshed,
+        Err(e) => {
+            error!(
+                "refresh known token accounts error: {:?}, scanning instead",
+                e
+            );
+            scans.extend(refresh);
+            vec![]
+        }
+    };
+    stats.scans = scans.len();
+
+    // none is an owner scan
+    let jobs = scans.into_iter().map(|account| (account, None)).chain(
+        refreshed
+            .into_iter()
+            .map(|(account, holdings)| (account, Some(holdings))),
+    );
+    let workers = Arc::new(Semaphore::new(c.daemon_workers));
+    let mut tasks = JoinSet::new();
+    for (account, holdings) in jobs {
+        let permit = workers
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("semaphore closed");
+        let manager = manager.clone();
+        let notifier = notifier.clone();
+        let source = source.clone();
+        let known = known.clone();
+        tasks.spawn(async move {
+            let _permit = permit;
+            match holdings {
+                Some(holdings) => {
+                    track_holdings(&manager, &notifier, telegram, &account, &holdings).await
+                }
+                None => {
+                    sync_account(c, &manager, &notifier, telegram, &source, &known, &account).await
                 }
             }
-            Err(e) => {
-                error!("get all accounts error: {:?}", e);
+        });
+    }
+    while let Some(result) = tasks.join_next().await {
+        match result {
+            Ok(dust) => stats.dust += dust,
+            Err(e) => error!("daemon worker error: {:?}", e),
+        }
+    }
+    stats
+}
+
+// scan the token accounts of one account and track them, returns the dust token accounts
+// skipped
+async fn sync_account(
+    c: &'static Arc<Config>,
+    manager: &SharedRepository,
+    notifier: &WebhookNotifier,
+    telegram: &'static TelegramNotifier,
+    source: &SharedHoldingsSource,
+    known: &KnownTokenAccounts,
+    account: &str,
+) -> usize {
+    match source.holdings(account, &c.dust).await {
+        Ok(holdings) => {
+            known.scanned(account, &holdings);
+            let dust = track_holdings(manager, notifier, telegram, account, &holdings).await;
+            // names for the responses, only mints seen for the first time hit the rpc
+            let mints: Vec<String> = holdings.accounts.iter().map(|a| a.mint.clone()).collect();
+            let rpc = get_global_solana_rpc().await;
+            if let Err(e) = metadata::resolve_missing(manager, rpc, &mints).await {
+                warn!("resolve metadata error: {:?}, account: {}", e, account);
             }
+            dust
+        }
+        Err(e) => {
+            error!("get tokens with account error: {:?}", e);
+            0
         }
     }
 }
+
+async fn track_holdings(
+    manager: &SharedRepository,
+    notifier: &WebhookNotifier,
+    telegram: &TelegramNotifier,
+    account: &str,
+    holdings: &Holdings,
+) -> usize {
+    if let Err(e) = track_account(manager, notifier, telegram, account, holdings).await {
+        error!("track account error: {:?}, account: {}", e, account);
+    }
+    holdings.skipped.len()
+}
+
+// the accounts the holdings source names, one transaction changes several accounts of a
+// wallet so a burst is read once
+async fn sync_notified(
+    c: &'static Arc<Config>,
+    manager: SharedRepository,
+    notifier: WebhookNotifier,
+    telegram: &'static TelegramNotifier,
+    source: SharedHoldingsSource,
+    known: Arc<KnownTokenAccounts>,
+    mut wallets: UnboundedReceiver<String>,
+) {
+    while let Some(wallet) = wallets.recv().await {
+        sleep(NOTIFICATION_BURST).await;
+        let mut burst = HashSet::from([wallet]);
+        while let Ok(wallet) = wallets.try_recv() {
+            burst.insert(wallet);
+        }
+        for wallet in burst {
+            sync_account(c, &manager, &notifier, telegram, &source, &known, &wallet).await;
+        }
+    }
+}
+
+// a notified sync and a polling pass may track the same account at once and would both diff
+// against the same previous snapshot, or check the slot before the other one writes, so
+// tracking is one at a time per account
+#[derive(Default)]
+struct AccountLocks {
+    accounts: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl AccountLocks {
+    async fn lock(&self, account: &str) -> AccountGuard<'_> {
+        let lock = self
+            .accounts
+            .lock()
+            .unwrap()
+            .entry(account.to_string())
+            .or_default()
+            .clone();
+        // created before waiting, so a caller that gives up while waiting cleans up too
+        let mut guard = AccountGuard {
+            locks: self,
+            account: account.to_string(),
+            guard: None,
+        };
+        guard.guard = Some(lock.lock_owned().await);
+        guard
+    }
+}
+
+// the lock of an account is forgotten once nobody holds or waits for it, deleted accounts
+// do not pile up
+struct AccountGuard<'a> {
+    locks: &'a AccountLocks,
+    account: String,
+    guard: Option<OwnedMutexGuard<()>>,
+}
+
+impl Drop for AccountGuard<'_> {
+    fn drop(&mut self) {
+        self.guard.take();
+        let mut accounts = self.locks.accounts.lock().unwrap();
+        if accounts
+            .get(&self.account)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            accounts.remove(&self.account);
+        }
+    }
+}
+
+static ACCOUNT_LOCKS: LazyLock<AccountLocks> = LazyLock::new(AccountLocks::default);
+
+// diff the holdings against the previous pass, record the snapshot, the events and the coins,
+// then push the events to the webhooks and telegram
+async fn track_account(
+    manager: &SharedRepository,
+    notifier: &WebhookNotifier,
+    telegram: &TelegramNotifier,
+    account: &str,
+    holdings: &Holdings,
+) -> Result<()> {
+    let _tracking = ACCOUNT_LOCKS.lock(account).await;
+    // the first pass only sets the baseline, what the account already holds is not a new position.
+    // an account holding nothing is observed as well, so its first buy is
+    let observed = manager
+        .get_account_with_mint(account)
+        .await?
+        .and_then(|a| a.last_observed_slot)
+        .map(|slot| slot as u64);
+    let previous = match observed {
+        None => None,
+        // an endpoint lagging behind the one that answered before, its holdings are older
+        // than what is stored
+        Some(slot) if holdings.slot < slot => {
+            warn!(
+                "skip holdings from slot {}, slot {} is stored, account: {}",
+                holdings.slot, slot, account
+            );
+            return Ok(());
+        }
+        Some(_) => Some(manager.get_current_holdings(account).await?),
+    };
+
+    let events = match previous {
+        Some(previous) => diff_positions(
+            account,
+            &previous,
+            holdings,
+            chrono::Local::now().timestamp(),
+        )?,
+        None => vec![],
+    };
+    let events = manager.record_holdings(account, holdings, &events).await?;
+    for event in &events {
+        info!(
+            "position event: {:?}, account: {}, token: {}, delta: {}",
+            event.event_type, event.account, event.token, event.delta
+        );
+    }
+    if !events.is_empty() {
+        notifier.notify(manager.clone(), &events);
+        telegram.notify_buys(&events);
+    }
+
+    let coins: Vec<NewCoin> = holdings
+        .positions()?
+        .into_iter()
+        .map(|(token, position)| NewCoin {
+            extensions: holdings.extensions.get(&token).cloned().unwrap_or_default(),
+            amount: position.amount,
+            decimals: position.decimals,
+            pubkey: position.pubkey,
+            program: position.program,
+            token,
+        })
+        .collect();
+    manager.sync_coins_with_account(account, &coins).await?;
+
+    Ok(())
+}
+
+fn diff_positions(
+    account: &str,
+    previous: &[HoldingSnapshot],
+    holdings: &Holdings,
+    created_at: i64,
+) -> Result<Vec<PositionEvent>> {
+    let event = |token: &str,
+                 event_type: PositionEventType,
+                 previous_amount: u64,
+                 amount: u64,
+                 decimals: i64,
+                 ui_delta: f64| PositionEvent {
+        id: 0,
+        account: account.to_string(),
+        token: token.to_string(),
+        event_type,
+        previous_amount: previous_amount.to_string(),
+        amount: amount.to_string(),
+        delta: (amount as i128 - previous_amount as i128).to_string(),
+        decimals,
+        ui_delta,
+        slot: holdings.slot as i64,
+        created_at,
+    };
+
+    let positions = holdings.positions()?;
+    let mut events = vec![];
+    for (token, position) in &positions {
+        let decimals = position.decimals as i64;
+        match previous.iter().find(|last| &last.mint == token) {
+            None => events.push(event(
+                token,
+                PositionEventType::NewPosition,
+                0,
+                position.amount,
+                decimals,
+                position.ui_amount,
+            )),
+            Some(last) => {
+                let last_amount = last.amount.parse::<u64>()?;
+                let event_type = match position.amount.cmp(&last_amount) {
+                    Ordering::Greater => PositionEventType::Increased,
+                    Ordering::Less => PositionEventType::Decreased,
+                    Ordering::Equal => continue,
+                };
+                events.push(event(
+                    token,
+                    event_type,
+                    last_amount,
+                    position.amount,
+                    decimals,
+                    position.ui_amount - last.ui_amount,
+                ));
+            }
+        }
+    }
+
+    for last in previous {
+        if positions.contains_key(&last.mint) {
+            continue;
+        }
+        events.push(event(
+            &last.mint,
+            PositionEventType::Exited,
+            last.amount.parse::<u64>()?,
+            0,
+            last.decimals,
+            -last.ui_amount,
+        ));
+    }
+
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{repository::MemoryRepository, solana_rpc::TokenAccount};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_account_locks() {
+        let locks = AccountLocks::default();
+        let evil = locks.lock("evil").await;
+        // another account is not held up
+        let other = locks.lock("other").await;
+        let again = tokio::time::timeout(Duration::from_millis(50), locks.lock("evil")).await;
+        assert!(again.is_err());
+        drop(evil);
+        let evil = tokio::time::timeout(Duration::from_secs(1), locks.lock("evil"))
+            .await
+            .unwrap();
+
+        // nobody holds or waits for them anymore
+        drop(evil);
+        drop(other);
+        assert!(locks.accounts.lock().unwrap().is_empty());
+    }
+
+    fn snapshot(mint: &str, amount: u64) -> HoldingSnapshot {
+        HoldingSnapshot {
+            id: 0,
+            account: "evil".to_string(),
+            mint: mint.to_string(),
+            amount: amount.to_string(),
+            decimals: 0,
+            ui_amount: amount as f64,
+            slot: 1,
+            observed_at: 0,
+        }
+    }
+
+    fn token(mint: &str, amount: u64) -> TokenAccount {
+        TokenAccount {
+            pubkey: format!("{}-ata", mint),
+            mint: mint.to_string(),
+            amount: amount.to_string(),
+            decimals: 0,
+            ui_amount: amount as f64,
+            program: spl_token::id().to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_track_account() {
+        let manager: SharedRepository = Arc::new(MemoryRepository::new());
+        manager.add_new_account("evil").await.unwrap();
+        let notifier = WebhookNotifier::new(vec![]);
+        let telegram = TelegramNotifier::new(None);
+        let pass = |slot, accounts| Holdings {
+            slot,
+            accounts,
+            ..Default::default()
+        };
+
+        // the first pass is the baseline, nothing is reported as new
+        track_account(
+            &manager,
+            &notifier,
+            &telegram,
+            "evil",
+            &pass(1, vec![token("a", 100), token("b", 100)]),
+        )
+        .await
+        .unwrap();
+        assert!(manager
+            .get_position_events(Some("evil"), None, None, 100)
+            .await
+            .unwrap()
+            .is_empty());
+        let b = manager.get_coin_with_token("b").await.unwrap().unwrap();
+        assert_eq!(b.amount, "100");
+        assert_eq!(b.pubkey.as_deref(), Some("b-ata"));
+
+        track_account(
+            &manager,
+            &notifier,
+            &telegram,
+            "evil",
+            &pass(2, vec![token("a", 100), token("c", 7)]),
+        )
+        .await
+        .unwrap();
+        let events = manager
+            .get_position_events(Some("evil"), None, None, 100)
+            .await
+            .unwrap();
+        let mut summary: Vec<(&str, PositionEventType)> = events
+            .iter()
+            .map(|e| (e.token.as_str(), e.event_type))
+            .collect();
+        summary.sort_by_key(|(token, _)| *token);
+        assert_eq!(
+            summary,
+            vec![
+                ("b", PositionEventType::Exited),
+                ("c", PositionEventType::NewPosition)
+            ]
+        );
+        assert!(manager.get_coin_with_token("b").await.unwrap().is_none());
+        assert!(manager.get_coin_with_token("c").await.unwrap().is_some());
+
+        // an endpoint still at slot 1 does not undo what slot 2 stored
+        track_account(
+            &manager,
+            &notifier,
+            &telegram,
+            "evil",
+            &pass(1, vec![token("a", 100), token("b", 100)]),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            manager
+                .get_position_events(Some("evil"), None, None, 100)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(manager.get_coin_with_token("b").await.unwrap().is_none());
+        assert_eq!(manager.get_current_holdings("evil").await.unwrap().len(), 2);
+        let account = manager
+            .get_account_with_mint("evil")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(account.last_observed_slot, Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_track_empty_account() {
+        let manager: SharedRepository = Arc::new(MemoryRepository::new());
+        manager.add_new_account("fresh").await.unwrap();
+        let notifier = WebhookNotifier::new(vec![]);
+        let telegram = TelegramNotifier::new(None);
+
+        // a freshly funded wallet holds nothing yet, or only dust
+        let dust = Holdings {
+            slot: 1,
+            skipped: vec![token("dust", 1)],
+            ..Default::default()
+        };
+        track_account(&manager, &notifier, &telegram, "fresh", &dust)
+            .await
+            .unwrap();
+
+        // so its first buy is a new position, not the baseline
+        let buy = Holdings {
+            slot: 2,
+            accounts: vec![token("a", 100)],
+            ..Default::default()
+        };
+        track_account(&manager, &notifier, &telegram, "fresh", &buy)
+            .await
+            .unwrap();
+        let events = manager
+            .get_position_events(Some("fresh"), None, None, 100)
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, PositionEventType::NewPosition);
+        assert_eq!(events[0].token, "a");
+    }
+
+    #[test]
+    fn test_known_token_accounts() {
+        use solana_sdk::program_pack::Pack;
+        use spl_token_2022::state::{Account as TokenAccountState, AccountState};
+
+        let known = KnownTokenAccounts::default();
+        let wallets: Vec<String> = (0..3).map(|_| Pubkey::new_unique().to_string()).collect();
+        let mints: Vec<String> = (0..2).map(|_| Pubkey::new_unique().to_string()).collect();
+        let token_account = |mint: &str, amount: u64| TokenAccount {
+            pubkey: Pubkey::new_unique().to_string(),
+            ..token(mint, amount)
+        };
+        let interval = Duration::from_secs(60);
+        assert!(known.scan_due(&wallets[0], interval));
+
+        // the first wallet has a position and a dust account, the second nothing
+        known.scanned(
+            &wallets[0],
+            &Holdings {
+                slot: 1,
+                accounts: vec![token_account(&mints[0], 200000)],
+                skipped: vec![token_account(&mints[1], 5)],
+                ..Default::default()
+            },
+        );
+        known.scanned(&wallets[1], &Holdings::default());
+        assert!(!known.scan_due(&wallets[0], interval));
+        assert!(known.scan_due(&wallets[0], Duration::ZERO));
+        assert!(known.scan_due(&wallets[2], interval));
+
+        let keys = known.keys(&wallets).unwrap();
+        assert_eq!(keys.len(), 2);
+        let data = |owner: &str, mint: &str, amount: u64| {
+            let mut data = vec![0; TokenAccountState::LEN];
+            TokenAccountState::pack(
+                TokenAccountState {
+                    mint: Pubkey::from_str(mint).unwrap(),
+                    owner: Pubkey::from_str(owner).unwrap(),
+                    amount,
+                    state: AccountState::Initialized,
+                    ..Default::default()
+                },
+                &mut data,
+            )
+            .unwrap();
+            Some(Account {
+                lamports: 2039280,
+                data,
+                owner: spl_token::id(),
+                executable: false,
+                rent_epoch: 0,
+            })
+        };
+
+        // the position was sold down to dust, the dust account grew above the threshold
+        let refreshed = known
+            .refreshed_holdings(
+                &wallets,
+                9,
+                &[
+                    data(&wallets[0], &mints[0], 10),
+                    data(&wallets[0], &mints[1], 300000),
+                ],
+                &DustConfig::default(),
+            )
+            .unwrap();
+        assert_eq!(refreshed.len(), 1);
+        let (wallet, holdings) = &refreshed[0];
+        assert_eq!(wallet, &wallets[0]);
+        assert_eq!(holdings.slot, 9);
+        let amounts: Vec<&str> = holdings
+            .accounts
+            .iter()
+            .map(|a| a.amount.as_str())
+            .collect();
+        assert_eq!(amounts, vec!["300000"]);
+        assert_eq!(holdings.skipped.len(), 1);
+
+        // a closed token account is gone until the next scan says otherwise
+        let refreshed = known
+            .refreshed_holdings(
+                &wallets,
+                10,
+                &[None, data(&wallets[0], &mints[1], 300000)],
+                &DustConfig::default(),
+            )
+            .unwrap();
+        assert_eq!(refreshed[0].1.accounts.len(), 1);
+        assert!(refreshed[0].1.skipped.is_empty());
+
+        known.retain(&HashSet::from([wallets[1].as_str()]));
+        assert!(known.scan_due(&wallets[0], interval));
+        assert!(known.keys(&wallets).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_diff_positions() {
+        let previous = vec![
+            snapshot("up", 100),
+            snapshot("down", 100),
+            snapshot("same", 100),
+            snapshot("gone", 100),
+        ];
+        let holdings = Holdings {
+            slot: 2,
+            accounts: vec![
+                token("up", 150),
+                token("down", 40),
+                token("same", 100),
+                token("new", 10),
+                token("new", 5),
+            ],
+            ..Default::default()
+        };
+
+        let events = diff_positions("evil", &previous, &holdings, 1000).unwrap();
+        let summary: Vec<(&str, PositionEventType, &str)> = events
+            .iter()
+            .map(|e| (e.token.as_str(), e.event_type, e.delta.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("down", PositionEventType::Decreased, "-60"),
+                ("new", PositionEventType::NewPosition, "15"),
+                ("up", PositionEventType::Increased, "50"),
+                ("gone", PositionEventType::Exited, "-100"),
+            ]
+        );
+        assert!(events.iter().all(|e| e.slot == 2 && e.created_at == 1000));
+        assert_eq!(events[3].ui_delta, -100.0);
+    }
+}
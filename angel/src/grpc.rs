@@ -0,0 +1,827 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Mutex,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use solana_sdk::{commitment_config::CommitmentLevel, pubkey::Pubkey};
+use tokio::{
+    sync::{mpsc, mpsc::UnboundedSender, watch},
+    time::{interval, sleep, Instant},
+};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{
+    codec::ProstCodec,
+    codegen::http::uri::PathAndQuery,
+    transport::{ClientTlsConfig, Endpoint},
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::{Config, DustConfig},
+    repository::SharedRepository,
+    solana_rpc::{get_global_solana_rpc, Holdings, TokenAccount},
+    source::{Backoff, HoldingsSource},
+};
+
+// the part of yellowstone's geyser.proto the daemon uses, the tags follow the upstream file.
+// fields that are not listed here are skipped when decoding
+pub mod proto {
+    use std::collections::HashMap;
+
+    pub const SUBSCRIBE_PATH: &str = "/geyser.Geyser/Subscribe";
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum CommitmentLevel {
+        Processed = 0,
+        Confirmed = 1,
+        Finalized = 2,
+    }
+
+    // a new request on the same stream replaces the filters, one with only a ping keeps them
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubscribeRequest {
+        #[prost(map = "string, message", tag = "1")]
+        pub accounts: HashMap<String, SubscribeRequestFilterAccounts>,
+        #[prost(enumeration = "CommitmentLevel", optional, tag = "6")]
+        pub commitment: Option<i32>,
+        #[prost(message, optional, tag = "9")]
+        pub ping: Option<SubscribeRequestPing>,
+    }
+
+    // accounts and owners are ors, filters are ands on top
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubscribeRequestFilterAccounts {
+        #[prost(string, repeated, tag = "2")]
+        pub account: Vec<String>,
+        #[prost(string, repeated, tag = "3")]
+        pub owner: Vec<String>,
+        #[prost(message, repeated, tag = "4")]
+        pub filters: Vec<SubscribeRequestFilterAccountsFilter>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubscribeRequestFilterAccountsFilter {
+        #[prost(oneof = "Filter", tags = "1")]
+        pub filter: Option<Filter>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Filter {
+        #[prost(message, tag = "1")]
+        Memcmp(SubscribeRequestFilterAccountsFilterMemcmp),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubscribeRequestFilterAccountsFilterMemcmp {
+        #[prost(uint64, tag = "1")]
+        pub offset: u64,
+        #[prost(oneof = "MemcmpData", tags = "3")]
+        pub data: Option<MemcmpData>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum MemcmpData {
+        #[prost(string, tag = "3")]
+        Base58(String),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubscribeRequestPing {
+        #[prost(int32, tag = "1")]
+        pub id: i32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubscribeUpdate {
+        #[prost(string, repeated, tag = "1")]
+        pub filters: Vec<String>,
+        #[prost(oneof = "UpdateOneof", tags = "2, 6, 9")]
+        pub update_oneof: Option<UpdateOneof>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum UpdateOneof {
+        #[prost(message, tag = "2")]
+        Account(SubscribeUpdateAccount),
+        #[prost(message, tag = "6")]
+        Ping(SubscribeUpdatePing),
+        #[prost(message, tag = "9")]
+        Pong(SubscribeUpdatePong),
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubscribeUpdateAccount {
+        #[prost(message, optional, tag = "1")]
+        pub account: Option<SubscribeUpdateAccountInfo>,
+        #[prost(uint64, tag = "2")]
+        pub slot: u64,
+        #[prost(bool, tag = "3")]
+        pub is_startup: bool,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubscribeUpdateAccountInfo {
+        #[prost(bytes = "vec", tag = "1")]
+        pub pubkey: Vec<u8>,
+        #[prost(uint64, tag = "2")]
+        pub lamports: u64,
+        #[prost(bytes = "vec", tag = "3")]
+        pub owner: Vec<u8>,
+        #[prost(bytes = "vec", tag = "6")]
+        pub data: Vec<u8>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubscribeUpdatePing {}
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct SubscribeUpdatePong {
+        #[prost(int32, tag = "1")]
+        pub id: i32,
+    }
+}
+
+use proto::{
+    Filter, MemcmpData, SubscribeRequest, SubscribeRequestFilterAccounts,
+    SubscribeRequestFilterAccountsFilter, SubscribeRequestFilterAccountsFilterMemcmp,
+    SubscribeRequestPing, SubscribeUpdate, SubscribeUpdateAccountInfo, UpdateOneof,
+};
+
+// a yellowstone geyser account stream. the tracked wallets themselves and the token accounts
+// they own, by the owner field at offset 32, like the pubsub stream
+pub struct GrpcStream {
+    url: String,
+    x_token: Option<String>,
+    commitment: CommitmentLevel,
+    manager: SharedRepository,
+    account_refresh: Duration,
+    max_backoff: Duration,
+    cache: HoldingsCache,
+    rescan: Duration, // how long a scan seeds the cache
+}
+
+impl GrpcStream {
+    pub fn new(c: &Config, manager: SharedRepository) -> Result<Self> {
+        let url = c
+            .stream
+            .grpc_url
+            .clone()
+            .ok_or_else(|| anyhow!("stream.grpc_url is required for the grpc source"))?;
+        Ok(Self {
+            url,
+            x_token: c.stream.grpc_x_token.clone(),
+            commitment: c.rpc.commitment,
+            manager,
+            account_refresh: Duration::from_secs(c.stream.account_refresh),
+            max_backoff: Duration::from_secs(c.stream.max_backoff),
+            cache: HoldingsCache::default(),
+            rescan: Duration::from_secs(c.stream.poll_interval),
+        })
+    }
+
+    async fn accounts(&self) -> Result<HashSet<String>> {
+        Ok(self
+            .manager
+            .get_all_accounts()
+            .await?
+            .into_iter()
+            .map(|account| account.account)
+            .collect())
+    }
+
+    fn request(&self, wallets: &HashSet<String>) -> SubscribeRequest {
+        let mut request = SubscribeRequest {
+            commitment: Some(match self.commitment {
+                CommitmentLevel::Processed => proto::CommitmentLevel::Processed,
+                CommitmentLevel::Confirmed => proto::CommitmentLevel::Confirmed,
+                CommitmentLevel::Finalized => proto::CommitmentLevel::Finalized,
+            } as i32),
+            ..Default::default()
+        };
+        if wallets.is_empty() {
+            return request;
+        }
+        let mut sorted: Vec<String> = wallets.iter().cloned().collect();
+        sorted.sort();
+        for wallet in &sorted {
+            let memcmp = SubscribeRequestFilterAccountsFilterMemcmp {
+                offset: 32,
+                data: Some(MemcmpData::Base58(wallet.clone())),
+            };
+            request.accounts.insert(
+                format!("token_accounts:{}", wallet),
+                SubscribeRequestFilterAccounts {
+                    owner: vec![
+                        spl_token::id().to_string(),
+                        spl_token_2022::id().to_string(),
+                    ],
+                    filters: vec![SubscribeRequestFilterAccountsFilter {
+                        filter: Some(Filter::Memcmp(memcmp)),
+                    }],
+                    ..Default::default()
+                },
+            );
+        }
+        request.accounts.insert(
+            "wallets".to_string(),
+            SubscribeRequestFilterAccounts {
+                account: sorted,
+                ..Default::default()
+            },
+        );
+        request
+    }
+
+    async fn connection(
+        &self,
+        wallets: &UnboundedSender<String>,
+        connected: &watch::Sender<bool>,
+    ) -> Result<()> {
+        let mut endpoint = Endpoint::from_shared(self.url.clone())?;
+        if self.url.starts_with("https://") {
+            endpoint = endpoint.tls_config(ClientTlsConfig::new().with_webpki_roots())?;
+        }
+        let mut client = tonic::client::Grpc::new(endpoint.connect().await?);
+        client.ready().await.map_err(|e| anyhow!(e))?;
+
+        let mut subscribed = self.accounts().await?;
+        let (requests, receiver) = mpsc::channel(16);
+        requests.send(self.request(&subscribed)).await?;
+        let mut request = tonic::Request::new(ReceiverStream::new(receiver));
+        if let Some(token) = &self.x_token {
+            request.metadata_mut().insert("x-token", token.parse()?);
+        }
+        let mut updates = client
+            .streaming(
+                request,
+                PathAndQuery::from_static(proto::SUBSCRIBE_PATH),
+                ProstCodec::<SubscribeRequest, SubscribeUpdate>::default(),
+            )
+            .await?
+            .into_inner();
+        info!("grpc stream connected: {}", self.url);
+        self.cache.reset(true);
+        self.cache.subscribe(&subscribed);
+        connected.send_replace(true);
+
+        let mut refresh = interval(self.account_refresh);
+        refresh.tick().await;
+        let mut ping = 0;
+        loop {
+            tokio::select! {
+                _ = refresh.tick() => {
+                    let accounts = self.accounts().await?;
+                    if accounts != subscribed {
+                        self.cache.subscribe(&accounts);
+                        requests.send(self.request(&accounts)).await?;
+                        subscribed = accounts;
+                    } else {
+                        // load balancers close streams that look idle
+                        ping += 1;
+                        let request = SubscribeRequest {
+                            ping: Some(SubscribeRequestPing { id: ping }),
+                            ..Default::default()
+                        };
+                        requests.send(request).await?;
+                    }
+                }
+                update = updates.message() => {
+                    let Some(update) = update? else {
+                        return Ok(());
+                    };
+                    if let Some(UpdateOneof::Account(account)) = update.update_oneof {
+                        let Some(info) = account.account else {
+                            continue;
+                        };
+                        if let Some(wallet) = wallet_of(&info, &subscribed) {
+                            self.cache.update(&wallet, &info, account.slot);
+                            wallets.send(wallet)?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// the tracked wallet an updated account is or belongs to
+fn wallet_of(info: &SubscribeUpdateAccountInfo, wallets: &HashSet<String>) -> Option<String> {
+    let pubkey = Pubkey::try_from(info.pubkey.as_slice()).ok()?.to_string();
+    if wallets.contains(&pubkey) {
+        return Some(pubkey);
+    }
+    let owner = Pubkey::try_from(info.data.get(32..64)?).ok()?.to_string();
+    wallets.contains(&owner).then_some(owner)
+}
+
+// a token account as the stream sent it, decimals come from the mint and are not in there
+#[derive(Clone, Debug, PartialEq)]
+struct StreamedTokenAccount {
+    mint: String,
+    amount: u64,
+    program: String,
+    slot: u64,
+}
+
+impl StreamedTokenAccount {
+    // the spl token layout, token-2022 accounts start the same: mint, owner, amount
+    fn decode(info: &SubscribeUpdateAccountInfo, slot: u64) -> Option<Self> {
+        let mint = Pubkey::try_from(info.data.get(0..32)?).ok()?;
+        let amount = u64::from_le_bytes(info.data.get(64..72)?.try_into().ok()?);
+        let program = Pubkey::try_from(info.owner.as_slice()).ok()?;
+        Some(Self {
+            mint: mint.to_string(),
+            amount,
+            program: program.to_string(),
+            slot,
+        })
+    }
+}
+
+#[derive(Default)]
+struct CachedWallet {
+    scan: Option<(Instant, Holdings)>, // the rpc scan the updates apply to, dust included
+    updates: HashMap<String, StreamedTokenAccount>, // token account pubkey -> newest update
+    slot: u64,                         // newest update of the wallet or its accounts
+}
+
+#[derive(Default)]
+struct CacheState {
+    connected: bool,
+    generation: u64, // bumped on every (dis)connect, a scan from before is not seeded
+    subscribed: HashSet<String>, // updates of other wallets do not arrive yet
+    wallets: HashMap<String, CachedWallet>,
+}
+
+// what the tracked wallets hold, an rpc scan with the stream updates since on top. an update
+// missed while the stream was down would leave it stale, so it is only used while connected
+#[derive(Default)]
+struct HoldingsCache {
+    state: Mutex<CacheState>,
+}
+
+impl HoldingsCache {
+    fn reset(&self, connected: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.connected = connected;
+        state.generation += 1;
+        state.wallets.clear();
+        state.subscribed.clear();
+    }
+
+    fn subscribe(&self, wallets: &HashSet<String>) {
+        let mut state = self.state.lock().unwrap();
+        state.wallets.retain(|wallet, _| wallets.contains(wallet));
+        state.subscribed = wallets.clone();
+    }
+
+    // the generation a scan started now seeds, none while disconnected
+    fn generation(&self) -> Option<u64> {
+        let state = self.state.lock().unwrap();
+        state.connected.then_some(state.generation)
+    }
+
+    // kept for wallets that were not scanned yet too, they may change while the scan runs
+    fn update(&self, wallet: &str, info: &SubscribeUpdateAccountInfo, slot: u64) {
+        let mut state = self.state.lock().unwrap();
+        let cached = state.wallets.entry(wallet.to_string()).or_default();
+        cached.slot = cached.slot.max(slot);
+        let Ok(pubkey) = Pubkey::try_from(info.pubkey.as_slice()) else {
+            return;
+        };
+        if pubkey.to_string() == wallet {
+            return;
+        }
+        let Some(account) = StreamedTokenAccount::decode(info, slot) else {
+            return;
+        };
+        let newest = cached
+            .updates
+            .get(&pubkey.to_string())
+            .is_none_or(|cached| cached.slot <= slot);
+        if newest {
+            cached.updates.insert(pubkey.to_string(), account);
+        }
+    }
+
+    fn seed(&self, wallet: &str, generation: Option<u64>, holdings: &Holdings) {
+        let mut state = self.state.lock().unwrap();
+        if !state.connected
+            || Some(state.generation) != generation
+            || !state.subscribed.contains(wallet)
+        {
+            return;
+        }
+        let cached = state.wallets.entry(wallet.to_string()).or_default();
+        cached
+            .updates
+            .retain(|_, update| update.slot > holdings.slot);
+        cached.scan = Some((Instant::now(), holdings.clone()));
+    }
+
+    // none when the wallet has to be scanned: not yet, too long ago, or a token account of a
+    // mint the scan did not see, its decimals and extensions are unknown
+    fn holdings(&self, wallet: &str, dust: &DustConfig, rescan: Duration) -> Option<Holdings> {
+        let state = self.state.lock().unwrap();
+        if !state.connected {
+            return None;
+        }
+        let cached = state.wallets.get(wallet)?;
+        let (scanned_at, scan) = cached.scan.as_ref()?;
+        if scanned_at.elapsed() >= rescan {
+            return None;
+        }
+        let mut accounts: BTreeMap<String, TokenAccount> = scan
+            .accounts
+            .iter()
+            .chain(&scan.skipped)
+            .map(|account| (account.pubkey.clone(), account.clone()))
+            .collect();
+        let decimals: HashMap<String, u8> = accounts
+            .values()
+            .map(|account| (account.mint.clone(), account.decimals))
+            .collect();
+        for (pubkey, update) in &cached.updates {
+            let decimals = *decimals.get(&update.mint)?;
+            accounts.insert(
+                pubkey.clone(),
+                TokenAccount {
+                    pubkey: pubkey.clone(),
+                    mint: update.mint.clone(),
+                    amount: update.amount.to_string(),
+                    decimals,
+                    ui_amount: spl_token_2022::amount_to_ui_amount(update.amount, decimals),
+                    program: update.program.clone(),
+                },
+            );
+        }
+        let mut holdings = Holdings {
+            slot: scan.slot.max(cached.slot),
+            accounts: accounts.into_values().collect(),
+            extensions: scan.extensions.clone(),
+            skipped: vec![],
+        };
+        holdings.skip_dust(wallet, dust);
+        Some(holdings)
+    }
+}
+
+#[async_trait]
+impl HoldingsSource for GrpcStream {
+    fn name(&self) -> &'static str {
+        "grpc"
+    }
+
+    // reconnects forever, the backoff starts over once a stream was opened
+    async fn watch(&self, wallets: UnboundedSender<String>, connected: watch::Sender<bool>) {
+        info!("grpc stream start, url: {}", self.url);
+        let mut backoff = Backoff::new(self.max_backoff);
+        loop {
+            match self.connection(&wallets, &connected).await {
+                Ok(()) => warn!("grpc stream closed by the server"),
+                Err(e) => error!("grpc stream error: {:?}", e),
+            }
+            if *connected.borrow() {
+                backoff.reset();
+            }
+            self.cache.reset(false);
+            connected.send_replace(false);
+            let delay = backoff.next_delay();
+            warn!("grpc stream reconnect in {:?}, polling meanwhile", delay);
+            sleep(delay).await;
+        }
+    }
+
+    // served from the stream while it is up, a scan only seeds it
+    async fn holdings(&self, account: &str, dust: &DustConfig) -> Result<Holdings> {
+        if let Some(holdings) = self.cache.holdings(account, dust, self.rescan) {
+            return Ok(holdings);
+        }
+        let generation = self.cache.generation();
+        let holdings = get_global_solana_rpc()
+            .await
+            .get_tokens_with_account(account, dust)
+            .await?;
+        self.cache.seed(account, generation, &holdings);
+        Ok(holdings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::MemoryRepository;
+    use futures_util::StreamExt;
+    use solana_sdk::program_pack::Pack;
+    use spl_token::state::Account as SplTokenAccount;
+    use std::{
+        convert::Infallible,
+        str::FromStr,
+        sync::{Arc, Mutex},
+        task::{Context, Poll},
+    };
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::{
+        codegen::{http, Body, BoxFuture, Service, StdError},
+        server::{Grpc, NamedService, StreamingService},
+        Request, Response, Status, Streaming,
+    };
+
+    const WALLET: &str = "9xHxgDbeQDX51Vof7ruAaYjSYgR87BXRp3ZC62jrmJV1";
+
+    // (connection, x-token, request) of everything the fake server was sent
+    type Received = Arc<Mutex<Vec<(usize, Option<String>, SubscribeRequest)>>>;
+
+    // a geyser server that answers the first request of a stream with an update of a token
+    // account the wallet owns. the first stream ends right after it
+    #[derive(Clone)]
+    struct FakeGeyser {
+        received: Received,
+        connections: Arc<Mutex<usize>>,
+    }
+
+    impl NamedService for FakeGeyser {
+        const NAME: &'static str = "geyser.Geyser";
+    }
+
+    fn token_account_info(
+        pubkey: &Pubkey,
+        owner: &str,
+        mint: &Pubkey,
+        amount: u64,
+    ) -> SubscribeUpdateAccountInfo {
+        let mut data = vec![0; SplTokenAccount::LEN];
+        SplTokenAccount::pack(
+            SplTokenAccount {
+                mint: *mint,
+                owner: Pubkey::from_str(owner).unwrap(),
+                amount,
+                state: spl_token::state::AccountState::Initialized,
+                ..Default::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+        SubscribeUpdateAccountInfo {
+            pubkey: pubkey.to_bytes().to_vec(),
+            lamports: 2_039_280,
+            owner: spl_token::id().to_bytes().to_vec(),
+            data,
+        }
+    }
+
+    fn token_account_update(owner: &str) -> SubscribeUpdate {
+        let info = token_account_info(&Pubkey::new_unique(), owner, &Pubkey::new_unique(), 100);
+        SubscribeUpdate {
+            filters: vec![format!("token_accounts:{}", owner)],
+            update_oneof: Some(UpdateOneof::Account(proto::SubscribeUpdateAccount {
+                account: Some(info),
+                slot: 10,
+                is_startup: false,
+            })),
+        }
+    }
+
+    impl StreamingService<SubscribeRequest> for FakeGeyser {
+        type Response = SubscribeUpdate;
+        type ResponseStream = ReceiverStream<Result<SubscribeUpdate, Status>>;
+        type Future = BoxFuture<Response<Self::ResponseStream>, Status>;
+
+        fn call(&mut self, request: Request<Streaming<SubscribeRequest>>) -> Self::Future {
+            let connection = {
+                let mut connections = self.connections.lock().unwrap();
+                *connections += 1;
+                *connections - 1
+            };
+            let token = request
+                .metadata()
+                .get("x-token")
+                .map(|token| token.to_str().unwrap().to_string());
+            let received = self.received.clone();
+            let (sender, receiver) = mpsc::channel(16);
+            let mut requests = request.into_inner();
+            tokio::spawn(async move {
+                while let Some(Ok(request)) = requests.next().await {
+                    received
+                        .lock()
+                        .unwrap()
+                        .push((connection, token.clone(), request));
+                    if sender.send(Ok(token_account_update(WALLET))).await.is_err()
+                        || connection == 0
+                    {
+                        return;
+                    }
+                }
+            });
+            Box::pin(async move { Ok(Response::new(ReceiverStream::new(receiver))) })
+        }
+    }
+
+    impl<B> Service<http::Request<B>> for FakeGeyser
+    where
+        B: Body + Send + 'static,
+        B::Error: Into<StdError> + Send + 'static,
+    {
+        type Response = http::Response<tonic::body::BoxBody>;
+        type Error = Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: http::Request<B>) -> Self::Future {
+            let service = self.clone();
+            Box::pin(async move {
+                let mut grpc =
+                    Grpc::new(ProstCodec::<SubscribeUpdate, SubscribeRequest>::default());
+                Ok(grpc.streaming(service, request).await)
+            })
+        }
+    }
+
+    async fn fake_geyser(received: Received) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let service = FakeGeyser {
+            received,
+            connections: Arc::new(Mutex::new(0)),
+        };
+        tokio::spawn(async move {
+            tonic::transport::Server::builder()
+                .add_service(service)
+                .serve_with_incoming(TcpListenerStream::new(listener))
+                .await
+                .unwrap()
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_grpc_stream_resubscribes_after_drop() {
+        let received: Received = Arc::new(Mutex::new(vec![]));
+        let url = fake_geyser(received.clone()).await;
+        let manager: SharedRepository = Arc::new(MemoryRepository::new());
+        manager.add_new_account(WALLET).await.unwrap();
+        let stream = GrpcStream {
+            url,
+            x_token: Some("secret".to_string()),
+            commitment: CommitmentLevel::Confirmed,
+            manager,
+            account_refresh: Duration::from_secs(60),
+            max_backoff: Duration::from_millis(20),
+            cache: HoldingsCache::default(),
+            rescan: Duration::from_secs(300),
+        };
+        let (connected, mut status) = watch::channel(false);
+        let (sender, mut wallets) = mpsc::unbounded_channel();
+        tokio::spawn(async move { stream.watch(sender, connected).await });
+
+        // one update before the drop, one after the reconnect
+        for _ in 0..2 {
+            let wallet = tokio::time::timeout(Duration::from_secs(5), wallets.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(wallet, WALLET);
+        }
+        tokio::time::timeout(Duration::from_secs(5), status.wait_for(|c| *c))
+            .await
+            .unwrap()
+            .unwrap();
+
+        let received = received.lock().unwrap().clone();
+        let connections: Vec<usize> = received.iter().map(|(c, _, _)| *c).collect();
+        assert_eq!(connections, vec![0, 1]);
+        let (_, token, request) = &received[1];
+        assert_eq!(token.as_deref(), Some("secret"));
+        assert_eq!(
+            request.commitment,
+            Some(proto::CommitmentLevel::Confirmed as i32)
+        );
+        assert_eq!(
+            request.accounts["wallets"].account,
+            vec![WALLET.to_string()]
+        );
+        let token_accounts = &request.accounts[&format!("token_accounts:{}", WALLET)];
+        assert_eq!(
+            token_accounts.owner,
+            vec![
+                spl_token::id().to_string(),
+                spl_token_2022::id().to_string()
+            ]
+        );
+        assert_eq!(
+            token_accounts.filters[0].filter,
+            Some(Filter::Memcmp(SubscribeRequestFilterAccountsFilterMemcmp {
+                offset: 32,
+                data: Some(MemcmpData::Base58(WALLET.to_string())),
+            }))
+        );
+    }
+
+    #[test]
+    fn test_wallet_of() {
+        let wallets = HashSet::from([WALLET.to_string()]);
+        let Some(UpdateOneof::Account(update)) = token_account_update(WALLET).update_oneof else {
+            unreachable!()
+        };
+        let info = update.account.unwrap();
+        assert_eq!(wallet_of(&info, &wallets), Some(WALLET.to_string()));
+
+        // the wallet account itself, it holds no token data
+        let wallet = SubscribeUpdateAccountInfo {
+            pubkey: Pubkey::from_str(WALLET).unwrap().to_bytes().to_vec(),
+            ..Default::default()
+        };
+        assert_eq!(wallet_of(&wallet, &wallets), Some(WALLET.to_string()));
+        assert_eq!(wallet_of(&info, &HashSet::new()), None);
+    }
+
+    #[test]
+    fn test_holdings_cache() {
+        let cache = HoldingsCache::default();
+        let (pubkey, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let scan = Holdings {
+            slot: 10,
+            accounts: vec![TokenAccount {
+                pubkey: pubkey.to_string(),
+                mint: mint.to_string(),
+                amount: "100000".to_string(),
+                decimals: 0,
+                ui_amount: 100000.0,
+                program: spl_token::id().to_string(),
+            }],
+            ..Default::default()
+        };
+        let dust = DustConfig::default();
+        let rescan = Duration::from_secs(60);
+
+        // nothing is seeded while disconnected
+        cache.seed(WALLET, cache.generation(), &scan);
+        assert!(cache.holdings(WALLET, &dust, rescan).is_none());
+
+        cache.reset(true);
+        let generation = cache.generation();
+        // nor for a wallet the stream does not send yet
+        cache.seed(WALLET, generation, &scan);
+        assert!(cache.holdings(WALLET, &dust, rescan).is_none());
+        cache.subscribe(&HashSet::from([WALLET.to_string()]));
+        // an update that arrives while the scan runs is newer than the scan
+        cache.update(
+            WALLET,
+            &token_account_info(&pubkey, WALLET, &mint, 300000),
+            12,
+        );
+        cache.seed(WALLET, generation, &scan);
+        let holdings = cache.holdings(WALLET, &dust, rescan).unwrap();
+        assert_eq!(holdings.slot, 12);
+        assert_eq!(holdings.accounts[0].amount, "300000");
+        assert_eq!(holdings.accounts[0].ui_amount, 300000.0);
+
+        // an older update does not roll it back, the wallet's own account only moves the slot
+        cache.update(
+            WALLET,
+            &token_account_info(&pubkey, WALLET, &mint, 200000),
+            11,
+        );
+        let wallet = SubscribeUpdateAccountInfo {
+            pubkey: Pubkey::from_str(WALLET).unwrap().to_bytes().to_vec(),
+            ..Default::default()
+        };
+        cache.update(WALLET, &wallet, 13);
+        let holdings = cache.holdings(WALLET, &dust, rescan).unwrap();
+        assert_eq!(holdings.slot, 13);
+        assert_eq!(holdings.accounts[0].amount, "300000");
+
+        // a new mint needs a scan for its decimals
+        let other = Pubkey::new_unique();
+        cache.update(
+            WALLET,
+            &token_account_info(&Pubkey::new_unique(), WALLET, &other, 5),
+            14,
+        );
+        assert!(cache.holdings(WALLET, &dust, rescan).is_none());
+
+        // a scan from before a reconnect would miss what happened in between
+        let generation = cache.generation();
+        cache.reset(true);
+        cache.subscribe(&HashSet::from([WALLET.to_string()]));
+        cache.seed(WALLET, generation, &scan);
+        assert!(cache.holdings(WALLET, &dust, rescan).is_none());
+        cache.seed(WALLET, cache.generation(), &scan);
+        assert_eq!(
+            cache.holdings(WALLET, &dust, rescan).unwrap().accounts[0].amount,
+            "100000"
+        );
+        assert!(cache.holdings(WALLET, &dust, Duration::ZERO).is_none());
+
+        cache.reset(false);
+        assert!(cache.holdings(WALLET, &dust, rescan).is_none());
+    }
+}
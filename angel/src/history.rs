@@ -0,0 +1,384 @@
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use anyhow::Result;
+use solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
+use solana_transaction_status_client_types::{
+    option_serializer::OptionSerializer, UiTransactionStatusMeta, UiTransactionTokenBalance,
+};
+use tokio::time::{sleep, Instant};
+use tracing::{error, info};
+
+use crate::{
+    config::{Config, HistoryConfig},
+    models::{BalanceDelta, IngestedTransaction},
+    repository::SharedRepository,
+    solana_rpc::{get_global_solana_rpc, SolanaRpc},
+    swaps::decode_swap,
+};
+
+// ingest the transactions of every tracked account, one account after the other
+pub async fn history(c: &'static Arc<Config>, manager: SharedRepository) {
+    let interval = Duration::from_secs(c.history.interval);
+    info!(
+        "history ingestion start, page size: {}, backfill pages: {}",
+        c.history.page_size, c.history.backfill_pages
+    );
+    loop {
+        let start = Instant::now();
+        let accounts = match manager.get_all_accounts().await {
+            Ok(accounts) => accounts,
+            Err(e) => {
+                error!("get all accounts error: {:?}", e);
+                vec![]
+            }
+        };
+        let rpc = get_global_solana_rpc().await;
+        let mut ingested = 0;
+        for account in &accounts {
+            match ingest_account(&manager, rpc, &account.account, &c.history).await {
+                Ok(count) => ingested += count,
+                Err(e) => error!(
+                    "ingest history error: {:?}, account: {}",
+                    e, &account.account
+                ),
+            }
+        }
+        info!(
+            "history pass took {:?}, {} transactions of {} accounts",
+            start.elapsed(),
+            ingested,
+            accounts.len()
+        );
+        sleep(interval.saturating_sub(start.elapsed())).await;
+    }
+}
+
+// everything after the stored cursor, oldest first, so a restart picks up where this stopped.
+// an account without a cursor only gets its newest backfill_pages pages
+pub async fn ingest_account(
+    manager: &SharedRepository,
+    rpc: &SolanaRpc,
+    account: &str,
+    config: &HistoryConfig,
+) -> Result<usize> {
+    let until = manager
+        .get_signature_cursor(account)
+        .await?
+        .map(|cursor| cursor.signature);
+
+    let mut signatures: Vec<RpcConfirmedTransactionStatusWithSignature> = vec![];
+    let mut pages = 0;
+    loop {
+        let before = signatures.last().map(|s| s.signature.clone());
+        let page = rpc
+            .get_signatures(
+                account,
+                before.as_deref(),
+                until.as_deref(),
+                config.page_size,
+            )
+            .await?;
+        pages += 1;
+        let full = page.len() == config.page_size;
+        signatures.extend(page);
+        if !full || (until.is_none() && pages >= config.backfill_pages) {
+            break;
+        }
+    }
+
+    for status in signatures.iter().rev() {
+        // a failed transaction changes no balance, it only moves the cursor
+        let transaction = if status.err.is_some() {
+            IngestedTransaction {
+                signature: status.signature.clone(),
+                slot: status.slot,
+                block_time: status.block_time,
+                deltas: vec![],
+                swap: None,
+            }
+        } else {
+            let transaction = rpc.get_parsed_transaction(&status.signature).await?;
+            let deltas = transaction
+                .transaction
+                .meta
+                .as_ref()
+                .map(|meta| token_deltas(account, meta))
+                .unwrap_or_default();
+            IngestedTransaction {
+                signature: status.signature.clone(),
+                slot: transaction.slot,
+                block_time: transaction.block_time.or(status.block_time),
+                swap: decode_swap(account, &transaction.transaction, &deltas),
+                deltas,
+            }
+        };
+        manager
+            .add_ingested_transaction(account, &transaction)
+            .await?;
+    }
+    Ok(signatures.len())
+}
+
+// what the transaction changed in the token balances the account owns, per mint
+pub fn token_deltas(account: &str, meta: &UiTransactionStatusMeta) -> Vec<BalanceDelta> {
+    let mut deltas: BTreeMap<String, BalanceDelta> = BTreeMap::new();
+    let mut add = |balances: &OptionSerializer<Vec<UiTransactionTokenBalance>>, post: bool| {
+        let OptionSerializer::Some(balances) = balances else {
+            return;
+        };
+        for balance in balances {
+            if !matches!(&balance.owner, OptionSerializer::Some(owner) if owner == account) {
+                continue;
+            }
+            let amount = balance.ui_token_amount.amount.parse::<u64>().unwrap_or(0);
+            let delta = deltas
+                .entry(balance.mint.clone())
+                .or_insert_with(|| BalanceDelta {
+                    mint: balance.mint.clone(),
+                    decimals: balance.ui_token_amount.decimals,
+                    ..Default::default()
+                });
+            if post {
+                delta.post_amount += amount;
+            } else {
+                delta.pre_amount += amount;
+            }
+        }
+    };
+    add(&meta.pre_token_balances, false);
+    add(&meta.post_token_balances, true);
+    deltas
+        .into_values()
+        .filter(|delta| delta.pre_amount != delta.post_amount)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::RpcConfig, repository::MemoryRepository};
+    use axum::{extract::State, routing::post, Json, Router};
+    use serde_json::{json, Value};
+    use solana_sdk::signature::Signature;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    };
+    use tokio::net::TcpListener;
+
+    const ACCOUNT: &str = "9xHxgDbeQDX51Vof7ruAaYjSYgR87BXRp3ZC62jrmJV1";
+    const PUMP: &str = "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P";
+
+    fn token_balance(index: u8, mint: &str, owner: &str, amount: u64) -> Value {
+        json!({
+            "accountIndex": index,
+            "mint": mint,
+            "uiTokenAmount": {
+                "uiAmount": amount as f64,
+                "decimals": 0,
+                "amount": amount.to_string(),
+                "uiAmountString": amount.to_string()
+            },
+            "owner": owner,
+            "programId": spl_token::id().to_string()
+        })
+    }
+
+    // slot n buys 100 of mint "m" for 0.001 sol on pump.fun, the account had (n - 1) * 100 before
+    fn meta(slot: u64) -> Value {
+        json!({
+            "err": null,
+            "status": {"Ok": null},
+            "fee": 5000,
+            "preBalances": [1_000_000_000, 1],
+            "postBalances": [1_000_000_000 - 5000 - 1_000_000, 1],
+            "preTokenBalances": [
+                token_balance(1, "m", ACCOUNT, (slot - 1) * 100),
+                token_balance(2, "m", "pool", 1_000_000),
+            ],
+            "postTokenBalances": [
+                token_balance(1, "m", ACCOUNT, slot * 100),
+                token_balance(2, "m", "pool", 1_000_000 - 100),
+                token_balance(3, "other", ACCOUNT, 7),
+            ]
+        })
+    }
+
+    #[test]
+    fn test_token_deltas() {
+        let meta: UiTransactionStatusMeta = serde_json::from_value(meta(3)).unwrap();
+        assert_eq!(
+            token_deltas(ACCOUNT, &meta),
+            vec![
+                BalanceDelta {
+                    mint: "m".to_string(),
+                    pre_amount: 200,
+                    post_amount: 300,
+                    decimals: 0,
+                },
+                // a token account opened in the transaction has no pre balance
+                BalanceDelta {
+                    mint: "other".to_string(),
+                    pre_amount: 0,
+                    post_amount: 7,
+                    decimals: 0,
+                },
+            ]
+        );
+        assert!(token_deltas("nobody", &meta).is_empty());
+    }
+
+    // (signature, slot, failed), oldest first
+    type Chain = Arc<Mutex<Vec<(String, u64, bool)>>>;
+
+    async fn mock_rpc(chain: Chain, transactions: Arc<AtomicUsize>) -> String {
+        async fn handle(
+            State((chain, transactions)): State<(Chain, Arc<AtomicUsize>)>,
+            Json(request): Json<Value>,
+        ) -> Json<Value> {
+            let chain = chain.lock().unwrap().clone();
+            let result = match request["method"].as_str().unwrap() {
+                "getSignaturesForAddress" => {
+                    let config = &request["params"][1];
+                    let position = |key: &str| {
+                        config[key]
+                            .as_str()
+                            .and_then(|sig| chain.iter().position(|(s, _, _)| s == sig))
+                    };
+                    let end = position("before").unwrap_or(chain.len());
+                    let start = position("until").map_or(0, |i| i + 1);
+                    let limit = config["limit"].as_u64().unwrap() as usize;
+                    let page: Vec<Value> = chain[start.min(end)..end]
+                        .iter()
+                        .rev()
+                        .take(limit)
+                        .map(|(signature, slot, failed)| {
+                            json!({
+                                "signature": signature,
+                                "slot": slot,
+                                "err": if *failed { json!({"InstructionError": [0, "InvalidArgument"]}) } else { Value::Null },
+                                "memo": null,
+                                "blockTime": 1733293000 + slot,
+                                "confirmationStatus": "finalized"
+                            })
+                        })
+                        .collect();
+                    json!(page)
+                }
+                "getTransaction" => {
+                    transactions.fetch_add(1, Ordering::SeqCst);
+                    let signature = request["params"][0].as_str().unwrap();
+                    let (_, slot, _) = chain.iter().find(|(s, _, _)| s == signature).unwrap();
+                    json!({
+                        "slot": slot,
+                        "blockTime": 1733293000 + slot,
+                        "transaction": {
+                            "signatures": [signature],
+                            "message": {
+                                "accountKeys": [
+                                    {"pubkey": ACCOUNT, "writable": true, "signer": true, "source": "transaction"},
+                                    {"pubkey": PUMP, "writable": false, "signer": false, "source": "transaction"}
+                                ],
+                                "recentBlockhash": "11111111111111111111111111111111",
+                                "instructions": [
+                                    {"programId": PUMP, "accounts": [], "data": "", "stackHeight": null}
+                                ]
+                            }
+                        },
+                        "meta": meta(*slot)
+                    })
+                }
+                method => panic!("unexpected method {}", method),
+            };
+            Json(json!({"jsonrpc": "2.0", "id": request["id"], "result": result}))
+        }
+
+        let app = Router::new()
+            .route("/", post(handle))
+            .with_state((chain, transactions));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_ingest_and_resume() {
+        let chain: Chain = Arc::new(Mutex::new(
+            [(1, false), (2, true), (3, false)]
+                .into_iter()
+                .map(|(slot, failed)| (Signature::new_unique().to_string(), slot, failed))
+                .collect(),
+        ));
+        let transactions = Arc::new(AtomicUsize::new(0));
+        let url = mock_rpc(chain.clone(), transactions.clone()).await;
+        let rpc = SolanaRpc::new(RpcConfig::default(), vec![(url, 1)], 1000).unwrap();
+        let manager: SharedRepository = Arc::new(MemoryRepository::new());
+        let config = HistoryConfig {
+            page_size: 2,
+            backfill_pages: 5,
+            ..Default::default()
+        };
+
+        // two pages, the failed transaction is not fetched
+        let count = ingest_account(&manager, &rpc, ACCOUNT, &config)
+            .await
+            .unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(transactions.load(Ordering::SeqCst), 2);
+        let cursor = manager
+            .get_signature_cursor(ACCOUNT)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(cursor.slot, 3);
+
+        // after a restart only the new transaction is read
+        chain
+            .lock()
+            .unwrap()
+            .push((Signature::new_unique().to_string(), 4, false));
+        let count = ingest_account(&manager, &rpc, ACCOUNT, &config)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(transactions.load(Ordering::SeqCst), 3);
+
+        let changes = manager
+            .get_token_balance_changes(Some(ACCOUNT), Some("m"), 100)
+            .await
+            .unwrap();
+        let slots: Vec<i64> = changes.iter().map(|c| c.slot).collect();
+        assert_eq!(slots, vec![4, 3, 1]);
+        assert_eq!(changes[0].pre_amount, "300");
+        assert_eq!(changes[0].delta, "100");
+        assert_eq!(changes[0].block_time, Some(1733293004));
+
+        // every successful transaction was a buy
+        let swaps = manager
+            .get_swaps(Some(ACCOUNT), Some("m"), 100)
+            .await
+            .unwrap();
+        let slots: Vec<i64> = swaps.iter().map(|s| s.slot).collect();
+        assert_eq!(slots, vec![4, 3, 1]);
+        assert_eq!(swaps[0].input_amount, "1000000");
+        assert_eq!(swaps[0].output_amount, "100");
+        assert_eq!(swaps[0].price, 0.00001);
+
+        // a new account only reads its newest page
+        let config = HistoryConfig {
+            page_size: 2,
+            backfill_pages: 1,
+            ..Default::default()
+        };
+        let count = ingest_account(
+            &manager,
+            &rpc,
+            "4Nd1mBQtrMJVYVfKf2PJy9NZUZdTAsp7D4xWLs4gDB4T",
+            &config,
+        )
+        .await
+        .unwrap();
+        assert_eq!(count, 2);
+    }
+}
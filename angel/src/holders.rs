@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+// who is behind a token account, wallet when nothing in the registry matches
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HolderKind {
+    #[default]
+    Wallet,
+    RaydiumAmm,
+    RaydiumCpmm,
+    RaydiumClmm,
+    OrcaWhirlpool,
+    Meteora,
+    PumpFunBondingCurve,
+    Burn,
+    Exchange,
+}
+
+// an extra registry entry from the config, address is an owner wallet or the program owning it
+#[derive(Clone, Debug, Validate, Deserialize)]
+pub struct HolderLabel {
+    #[validate(length(min = 32, max = 44))]
+    pub address: String,
+    pub kind: HolderKind,
+    pub name: String,
+}
+
+// owners that are a fixed pda or a well known wallet
+const OWNERS: &[(&str, HolderKind, &str)] = &[
+    (
+        "5Q544fKrFoe6tsEbD7S8EmxGTJYAKtTVhAW5Q5pge4j1",
+        HolderKind::RaydiumAmm,
+        "Raydium AMM v4 authority",
+    ),
+    (
+        "GpMZbSM2GgvTKHJirzeGfMFoaZ8UR2X7F4v8vHTvxFbL",
+        HolderKind::RaydiumCpmm,
+        "Raydium CPMM authority",
+    ),
+    (
+        "1nc1nerator11111111111111111111111111111111",
+        HolderKind::Burn,
+        "Incinerator",
+    ),
+    (
+        "5tzFkiKscXHK5ZXCGbXZxdw7gTjjD1mBwuoFbhUvuAi9",
+        HolderKind::Exchange,
+        "Binance",
+    ),
+    (
+        "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM",
+        HolderKind::Exchange,
+        "Binance",
+    ),
+    (
+        "H8sMJSCQxfKiFTCfDR3DUMLPwcRbM61LGFJ8N4dK3WjS",
+        HolderKind::Exchange,
+        "Coinbase",
+    ),
+    (
+        "5VCwKtCXgCJ6kit5FybXjvriW3xELsFDhYrPSqtJNmcD",
+        HolderKind::Exchange,
+        "OKX",
+    ),
+    (
+        "AC5RDfQFmDS1deWZos921JfqscXdByf8BKHs5ACWjtW2",
+        HolderKind::Exchange,
+        "Bybit",
+    ),
+];
+
+// programs whose accounts (pool state, bonding curve) own the vault token accounts
+const PROGRAMS: &[(&str, HolderKind, &str)] = &[
+    (
+        "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8",
+        HolderKind::RaydiumAmm,
+        "Raydium AMM v4",
+    ),
+    (
+        "CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C",
+        HolderKind::RaydiumCpmm,
+        "Raydium CPMM",
+    ),
+    (
+        "CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK",
+        HolderKind::RaydiumClmm,
+        "Raydium CLMM",
+    ),
+    (
+        "whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc",
+        HolderKind::OrcaWhirlpool,
+        "Orca Whirlpool",
+    ),
+    (
+        "LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo",
+        HolderKind::Meteora,
+        "Meteora DLMM",
+    ),
+    (
+        "Eo7WjKq67rjJQSZxS6z3YkapzY3eMj6Xy8X5EQVn5UaB",
+        HolderKind::Meteora,
+        "Meteora Pools",
+    ),
+    (
+        "24Uqj9JCLxUeoC3hGfh5W3s9FM9uCHDS2SG3LYwBpyTi",
+        HolderKind::Meteora,
+        "Meteora Vault",
+    ),
+    (
+        "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P",
+        HolderKind::PumpFunBondingCurve,
+        "pump.fun",
+    ),
+];
+
+// known owners and programs, the built-in lists plus labels from the config
+pub struct HolderRegistry {
+    entries: HashMap<String, (HolderKind, String)>,
+}
+
+impl HolderRegistry {
+    // config labels win over the built-in entries for the same address
+    pub fn new(labels: &[HolderLabel]) -> Self {
+        let mut entries: HashMap<String, (HolderKind, String)> = OWNERS
+            .iter()
+            .chain(PROGRAMS)
+            .map(|(address, kind, name)| (address.to_string(), (*kind, name.to_string())))
+            .collect();
+        for label in labels {
+            entries.insert(label.address.clone(), (label.kind, label.name.clone()));
+        }
+        Self { entries }
+    }
+
+    // the owner itself first, then the program owning the owner account
+    pub fn classify(&self, owner: &str, owner_program: Option<&str>) -> Option<(HolderKind, &str)> {
+        self.entries
+            .get(owner)
+            .or_else(|| owner_program.and_then(|program| self.entries.get(program)))
+            .map(|(kind, name)| (*kind, name.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::pubkey::Pubkey;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_registry_addresses_are_pubkeys() {
+        for (address, _, _) in OWNERS.iter().chain(PROGRAMS) {
+            assert!(Pubkey::from_str(address).is_ok(), "{}", address);
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        let registry = HolderRegistry::new(&[HolderLabel {
+            address: "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM".to_string(),
+            kind: HolderKind::Exchange,
+            name: "Binance 2".to_string(),
+        }]);
+
+        assert_eq!(
+            registry.classify("5Q544fKrFoe6tsEbD7S8EmxGTJYAKtTVhAW5Q5pge4j1", None),
+            Some((HolderKind::RaydiumAmm, "Raydium AMM v4 authority"))
+        );
+        // a bonding curve pda is matched by the program owning it
+        assert_eq!(
+            registry.classify("curve", Some("6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P")),
+            Some((HolderKind::PumpFunBondingCurve, "pump.fun"))
+        );
+        assert_eq!(
+            registry.classify("9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM", None),
+            Some((HolderKind::Exchange, "Binance 2"))
+        );
+        assert_eq!(
+            registry.classify("wallet", Some("11111111111111111111111111111111")),
+            None
+        );
+    }
+}
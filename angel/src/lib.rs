@@ -1,5 +1,20 @@
+pub mod concentration;
 pub mod config;
 pub mod daemon;
+pub mod grpc;
+pub mod history;
+pub mod holders;
+pub mod metadata;
+pub mod migrate;
 pub mod models;
+pub mod rate_limit;
+pub mod repository;
+pub mod rpc_pool;
+pub mod safety;
 pub mod solana_rpc;
+pub mod source;
+pub mod stream;
+pub mod swaps;
+pub mod telegram;
 pub mod web;
+pub mod webhook;
@@ -1,13 +1,14 @@
 use std::env;
 
-use angel::{config, daemon};
+use angel::{config, daemon, migrate};
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 use tokio::fs;
+use tracing::info;
 use validator::Validate;
 #[derive(Parser)]
 #[clap(
-    version = utils::version::get_version(), 
+    version = utils::version::get_version(),
     about = "Trade bot"
 )]
 #[clap(propagate_version = true)]
@@ -15,6 +16,10 @@ struct Cli {
     #[arg(short, long, default_value = "app.toml")]
     config: String,
 
+    // 启动时不自动执行数据库迁移
+    #[arg(long)]
+    no_migrate: bool,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -22,7 +27,18 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     Daemon, // 不断更新新token
-    Web, // web服务
+    Web,    // web服务
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateAction,
+    }, // 数据库迁移
+}
+
+#[derive(Subcommand)]
+enum MigrateAction {
+    Up,     // 执行所有未执行的迁移
+    Down,   // 回滚最近一次迁移
+    Status, // 查看迁移状态
 }
 
 #[tokio::main]
@@ -37,6 +53,11 @@ async fn main() -> Result<()> {
 
     utils::log::init_tracing();
 
+    if matches!(cli.command, Some(Commands::Daemon | Commands::Web)) && !cli.no_migrate {
+        migrate::up(&c.database_url).await?;
+        info!("database migrations applied");
+    }
+
     match cli.command {
         Some(Commands::Daemon) => {
             daemon::daemon().await;
@@ -44,10 +65,30 @@ async fn main() -> Result<()> {
         Some(Commands::Web) => {
             angel::web::start_server().await?;
         }
+        Some(Commands::Migrate { action }) => match action {
+            MigrateAction::Up => {
+                migrate::up(&c.database_url).await?;
+                println!("migrations applied");
+            }
+            MigrateAction::Down => match migrate::down(&c.database_url).await? {
+                Some(version) => println!("reverted {}", version),
+                None => println!("nothing to revert"),
+            },
+            MigrateAction::Status => {
+                for m in migrate::status(&c.database_url).await? {
+                    let state = match (m.applied, m.changed) {
+                        (true, false) => "applied",
+                        (true, true) => "changed",
+                        _ => "pending",
+                    };
+                    println!("{} {:<8} {}", m.version, state, m.description);
+                }
+            }
+        },
         None => {
             println!("Please specify a subcommand");
         }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
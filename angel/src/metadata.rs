@@ -0,0 +1,226 @@
+use std::{collections::HashSet, str::FromStr};
+
+use anyhow::Result;
+use solana_sdk::{account::Account, pubkey, pubkey::Pubkey};
+use spl_token_2022::{
+    extension::{BaseStateWithExtensions, StateWithExtensions},
+    state::Mint,
+};
+use spl_token_metadata_interface::state::TokenMetadata;
+use tracing::{info, warn};
+
+use crate::{models::Token, repository::SharedRepository, solana_rpc::SolanaRpc};
+
+// metaplex token metadata program
+pub const METADATA_PROGRAM_ID: Pubkey = pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+// the metaplex metadata account of a mint
+pub fn metadata_pda(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"metadata", METADATA_PROGRAM_ID.as_ref(), mint.as_ref()],
+        &METADATA_PROGRAM_ID,
+    )
+    .0
+}
+
+// name, symbol and uri of a metaplex metadata account. the borsh layout starts with
+// key (1), update authority (32) and mint (32), the strings are padded with \0
+pub fn parse_metaplex(data: &[u8]) -> Option<(String, String, String)> {
+    let mut offset = 65;
+    let mut read_string = || {
+        let len = u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?) as usize;
+        let bytes = data.get(offset + 4..offset + 4 + len)?;
+        offset += 4 + len;
+        Some(
+            String::from_utf8_lossy(bytes)
+                .trim_end_matches('\0')
+                .trim()
+                .to_string(),
+        )
+    };
+    Some((read_string()?, read_string()?, read_string()?))
+}
+
+// the token-2022 metadata extension wins over metaplex, a mint can have both
+pub fn token_from_accounts(
+    mint: &str,
+    mint_account: &Account,
+    metaplex: Option<&Account>,
+) -> Result<Token> {
+    let state = StateWithExtensions::<Mint>::unpack(&mint_account.data)?;
+    let mut token = Token {
+        mint: mint.to_string(),
+        decimals: state.base.decimals as i64,
+        program: mint_account.owner.to_string(),
+        source: "none".to_string(),
+        updated_at: chrono::Local::now().timestamp(),
+        ..Default::default()
+    };
+
+    let found = if let Ok(metadata) = state.get_variable_len_extension::<TokenMetadata>() {
+        Some(("token_2022", metadata.name, metadata.symbol, metadata.uri))
+    } else {
+        metaplex
+            .filter(|account| account.owner == METADATA_PROGRAM_ID)
+            .and_then(|account| parse_metaplex(&account.data))
+            .map(|(name, symbol, uri)| ("metaplex", name, symbol, uri))
+    };
+    if let Some((source, name, symbol, uri)) = found {
+        let some = |s: String| if s.is_empty() { None } else { Some(s) };
+        token.source = source.to_string();
+        token.name = some(name);
+        token.symbol = some(symbol);
+        token.uri = some(uri);
+    }
+    Ok(token)
+}
+
+// look up the mints that are not cached yet and store them, returns how many were added
+pub async fn resolve_missing(
+    manager: &SharedRepository,
+    rpc: &SolanaRpc,
+    mints: &[String],
+) -> Result<usize> {
+    let cached: HashSet<String> = manager
+        .get_tokens(mints)
+        .await?
+        .into_iter()
+        .map(|token| token.mint)
+        .collect();
+    let mut missing: Vec<String> = vec![];
+    for mint in mints {
+        if !cached.contains(mint) && !missing.contains(mint) && Pubkey::from_str(mint).is_ok() {
+            missing.push(mint.clone());
+        }
+    }
+    if missing.is_empty() {
+        return Ok(0);
+    }
+
+    let tokens = rpc.get_token_metadata(&missing).await?;
+    // one row that does not fit must not keep the others out
+    let mut saved = 0;
+    for token in &tokens {
+        match manager.add_token(token).await {
+            Ok(()) => saved += 1,
+            Err(e) => warn!("save token metadata error: {:?}, mint: {}", e, token.mint),
+        }
+    }
+    if tokens.len() < missing.len() {
+        warn!(
+            "metadata of {} mints not found",
+            missing.len() - tokens.len()
+        );
+    }
+    info!("resolved metadata of {} mints", saved);
+    Ok(saved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::program_pack::Pack;
+    use spl_token_2022::extension::{
+        metadata_pointer::MetadataPointer, BaseStateWithExtensionsMut, ExtensionType,
+        StateWithExtensionsMut,
+    };
+
+    fn metaplex_data(name: &str, symbol: &str, uri: &str) -> Vec<u8> {
+        let mut data = vec![4];
+        data.extend([0; 64]);
+        // names are padded to a fixed length on chain
+        for (value, len) in [(name, 32), (symbol, 10), (uri, 200)] {
+            let mut padded = value.as_bytes().to_vec();
+            padded.resize(len, 0);
+            data.extend((len as u32).to_le_bytes());
+            data.extend(padded);
+        }
+        data
+    }
+
+    fn mint_account(decimals: u8) -> Account {
+        let mut data = vec![0; Mint::LEN];
+        Mint::pack(
+            Mint {
+                decimals,
+                is_initialized: true,
+                ..Default::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+        Account {
+            owner: spl_token::id(),
+            data,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_metaplex() {
+        let data = metaplex_data("Evil Coin", "EVIL", "https://example.com/evil.json");
+        assert_eq!(
+            parse_metaplex(&data),
+            Some((
+                "Evil Coin".to_string(),
+                "EVIL".to_string(),
+                "https://example.com/evil.json".to_string()
+            ))
+        );
+        assert_eq!(parse_metaplex(&data[..100]), None);
+
+        let metaplex = Account {
+            owner: METADATA_PROGRAM_ID,
+            data,
+            ..Default::default()
+        };
+        let token = token_from_accounts("mint", &mint_account(6), Some(&metaplex)).unwrap();
+        assert_eq!(token.name.as_deref(), Some("Evil Coin"));
+        assert_eq!(token.symbol.as_deref(), Some("EVIL"));
+        assert_eq!(token.decimals, 6);
+        assert_eq!(token.source, "metaplex");
+
+        // without metadata only the mint is known
+        let token = token_from_accounts("mint", &mint_account(9), None).unwrap();
+        assert_eq!(token.name, None);
+        assert_eq!(token.decimals, 9);
+        assert_eq!(token.source, "none");
+    }
+
+    #[test]
+    fn test_token_2022_metadata() {
+        let metadata = TokenMetadata {
+            name: "Hooked".to_string(),
+            symbol: "HOOK".to_string(),
+            uri: "https://example.com/hook.json".to_string(),
+            ..Default::default()
+        };
+        // real mints point the metadata pointer at themselves
+        let space =
+            ExtensionType::try_calculate_account_len::<Mint>(&[ExtensionType::MetadataPointer])
+                .unwrap()
+                + metadata.tlv_size_of().unwrap();
+        let mut data = vec![0; space];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        state.base = Mint {
+            decimals: 2,
+            is_initialized: true,
+            ..Default::default()
+        };
+        state.pack_base();
+        state.init_extension::<MetadataPointer>(true).unwrap();
+        state.init_account_type().unwrap();
+        state.init_variable_len_extension(&metadata, false).unwrap();
+
+        let mint = Account {
+            owner: spl_token_2022::id(),
+            data,
+            ..Default::default()
+        };
+        let token = token_from_accounts("mint", &mint, None).unwrap();
+        assert_eq!(token.name.as_deref(), Some("Hooked"));
+        assert_eq!(token.symbol.as_deref(), Some("HOOK"));
+        assert_eq!(token.program, spl_token_2022::id().to_string());
+        assert_eq!(token.source, "token_2022");
+    }
+}
@@ -0,0 +1,145 @@
+use anyhow::{anyhow, Result};
+use sqlx::{
+    any::install_default_drivers,
+    migrate::{Migrate, MigrateDatabase, Migrator},
+    Any, AnyPool,
+};
+use tracing::info;
+
+// migrations are compiled into the binary, one set per sql dialect
+pub static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("../migrations/sqlite");
+pub static POSTGRES_MIGRATOR: Migrator = sqlx::migrate!("../migrations/postgres");
+
+pub fn migrator(database_url: &str) -> Result<&'static Migrator> {
+    if database_url.starts_with("sqlite:") {
+        Ok(&SQLITE_MIGRATOR)
+    } else if database_url.starts_with("postgres:") || database_url.starts_with("postgresql:") {
+        Ok(&POSTGRES_MIGRATOR)
+    } else {
+        Err(anyhow!("unsupported database url: {}", database_url))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+    pub changed: bool, // applied, but the file was edited afterwards
+}
+
+async fn connect(database_url: &str) -> Result<AnyPool> {
+    install_default_drivers();
+    Ok(AnyPool::connect(database_url).await?)
+}
+
+// create the database if it is missing, then apply every pending migration
+pub async fn up(database_url: &str) -> Result<()> {
+    let migrator = migrator(database_url)?;
+    install_default_drivers();
+    if !Any::database_exists(database_url).await? {
+        info!("create database: {}", database_url);
+        Any::create_database(database_url).await?;
+    }
+
+    let pool = connect(database_url).await?;
+    migrator.run(&pool).await?;
+    pool.close().await;
+    Ok(())
+}
+
+// revert the latest applied migration, returns its version
+pub async fn down(database_url: &str) -> Result<Option<i64>> {
+    let migrator = migrator(database_url)?;
+    let pool = connect(database_url).await?;
+
+    let mut applied = applied_versions(&pool).await?;
+    applied.sort();
+    let Some(latest) = applied.pop() else {
+        return Ok(None);
+    };
+    migrator
+        .undo(&pool, applied.last().copied().unwrap_or(0))
+        .await?;
+    pool.close().await;
+    Ok(Some(latest))
+}
+
+pub async fn status(database_url: &str) -> Result<Vec<MigrationStatus>> {
+    let migrator = migrator(database_url)?;
+    let pool = connect(database_url).await?;
+
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let applied = conn.list_applied_migrations().await?;
+    drop(conn);
+    pool.close().await;
+
+    Ok(migrator
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .map(|m| {
+            let record = applied.iter().find(|a| a.version == m.version);
+            MigrationStatus {
+                version: m.version,
+                description: m.description.to_string(),
+                applied: record.is_some(),
+                changed: record.is_some_and(|a| a.checksum != m.checksum),
+            }
+        })
+        .collect())
+}
+
+async fn applied_versions(pool: &AnyPool) -> Result<Vec<i64>> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    Ok(conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|m| m.version)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_up_down_status() {
+        let path = std::env::temp_dir().join(format!("angel_{}.sqlite3", rand::random::<u32>()));
+        let url = format!("sqlite://{}", path.display());
+
+        // up creates the missing database file
+        up(&url).await.unwrap();
+        let all = status(&url).await.unwrap();
+        assert!(!all.is_empty());
+        assert!(all.iter().all(|m| m.applied && !m.changed));
+
+        let latest = all.last().unwrap().version;
+        assert_eq!(down(&url).await.unwrap(), Some(latest));
+        let after = status(&url).await.unwrap();
+        assert!(!after.last().unwrap().applied);
+        assert!(after[..after.len() - 1].iter().all(|m| m.applied));
+
+        // running up twice is fine
+        up(&url).await.unwrap();
+        up(&url).await.unwrap();
+        assert!(status(&url).await.unwrap().iter().all(|m| m.applied));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_migrator() {
+        assert!(std::ptr::eq(
+            migrator("sqlite://./data/db.sqlite3").unwrap(),
+            &SQLITE_MIGRATOR
+        ));
+        assert!(std::ptr::eq(
+            migrator("postgres://u@localhost/evil").unwrap(),
+            &POSTGRES_MIGRATOR
+        ));
+        assert!(migrator("mysql://u@localhost/evil").is_err());
+    }
+}
@@ -1,20 +1,143 @@
 use std::sync::Arc;
 
-use anyhow::Result;
-use serde::Serialize;
-use sqlx::SqlitePool;
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::{AnyConnection, AnyPool};
 use tokio::sync::OnceCell;
 use tracing::info;
 
-use crate::config::get_global_config;
+use crate::{
+    config::get_global_config,
+    repository::Repository,
+    solana_rpc::{Holdings, MintExtensions},
+    swaps::{Dex, SwapSide},
+};
 
+// the same queries run on sqlite and postgres, so they stick to $N placeholders and to sql
+// both understand. the backend is picked by the scheme of database_url
 pub struct ModelsManager {
-    pool: SqlitePool,
+    pool: AnyPool,
 }
 impl ModelsManager {
-    pub fn new(pool: SqlitePool) -> Self {
+    pub fn new(pool: AnyPool) -> Self {
         Self { pool }
     }
+
+    // sqlite://... or postgres://...
+    pub async fn connect(database_url: &str) -> Result<Self> {
+        sqlx::any::install_default_drivers();
+        let pool = AnyPool::connect(database_url).await?;
+        Ok(Self::new(pool))
+    }
+
+    // the amount changes with every trade, the extensions of a mint can change too
+    async fn update_coin(&self, id: i64, coin: &NewCoin) -> Result<()> {
+        sqlx::query(
+            "UPDATE coins SET amount = $1, decimals = $2, pubkey = $3, program = $4, \
+             transfer_fee_bps = $5, transfer_fee_max = $6, permanent_delegate = $7, \
+             non_transferable = $8 WHERE id = $9;",
+        )
+        .bind(coin.amount.to_string())
+        .bind(coin.decimals as i64)
+        .bind(&coin.pubkey)
+        .bind(&coin.program)
+        .bind(coin.extensions.transfer_fee_bps.map(i64::from))
+        .bind(coin.extensions.transfer_fee_max.map(|max| max.to_string()))
+        .bind(coin.extensions.permanent_delegate.as_deref())
+        .bind(coin.extensions.non_transferable as i64)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // the statements of add_holding_snapshots, inside the transaction of the caller
+    async fn insert_holding_snapshots(
+        conn: &mut AnyConnection,
+        account: &str,
+        holdings: &Holdings,
+    ) -> Result<()> {
+        let observed_at = chrono::Local::now().timestamp();
+
+        let positions = holdings.positions()?;
+
+        let latest = sqlx::query_as::<_, HoldingSnapshot>(
+            "SELECT * FROM holding_snapshots h WHERE account = $1 AND id = \
+             (SELECT MAX(id) FROM holding_snapshots WHERE account = h.account AND mint = h.mint);",
+        )
+        .bind(account)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        for (mint, position) in &positions {
+            sqlx::query(
+                "INSERT INTO holding_snapshots (account, mint, amount, decimals, ui_amount, slot, observed_at) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7);",
+            )
+            .bind(account)
+            .bind(mint)
+            .bind(position.amount.to_string())
+            .bind(position.decimals as i64)
+            .bind(position.ui_amount)
+            .bind(holdings.slot as i64)
+            .bind(observed_at)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        for last in latest {
+            if last.amount == "0" || positions.contains_key(&last.mint) {
+                continue;
+            }
+            sqlx::query(
+                "INSERT INTO holding_snapshots (account, mint, amount, decimals, ui_amount, slot, observed_at) \
+                 VALUES ($1, $2, '0', $3, 0, $4, $5);",
+            )
+            .bind(account)
+            .bind(&last.mint)
+            .bind(last.decimals)
+            .bind(holdings.slot as i64)
+            .bind(observed_at)
+            .execute(&mut *conn)
+            .await?;
+            info!("exit position: {}, account: {}", last.mint, account);
+        }
+
+        Ok(())
+    }
+
+    // the statements of add_position_events, inside the transaction of the caller
+    async fn insert_position_events(
+        conn: &mut AnyConnection,
+        events: &[PositionEvent],
+    ) -> Result<Vec<PositionEvent>> {
+        let mut saved = Vec::with_capacity(events.len());
+        for event in events {
+            let id: i64 = sqlx::query_scalar(
+                "INSERT INTO position_events \
+                 (account, token, event_type, previous_amount, amount, delta, decimals, ui_delta, slot, created_at) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) RETURNING id;",
+            )
+            .bind(&event.account)
+            .bind(&event.token)
+            .bind(event.event_type.as_str())
+            .bind(&event.previous_amount)
+            .bind(&event.amount)
+            .bind(&event.delta)
+            .bind(event.decimals)
+            .bind(event.ui_delta)
+            .bind(event.slot)
+            .bind(event.created_at)
+            .fetch_one(&mut *conn)
+            .await?;
+            saved.push(PositionEvent {
+                id,
+                ..event.clone()
+            });
+        }
+        Ok(saved)
+    }
 }
 
 pub static GLOBAL_MANAGER: OnceCell<Arc<ModelsManager>> = OnceCell::const_new();
@@ -23,128 +146,950 @@ pub async fn get_global_manager() -> &'static Arc<ModelsManager> {
     GLOBAL_MANAGER
         .get_or_init(|| async {
             let config = get_global_config().await;
-            let pool = SqlitePool::connect(&config.database_url)
+            let manager = ModelsManager::connect(&config.database_url)
                 .await
                 .expect("Failed to connect to database");
 
-            Arc::new(ModelsManager::new(pool))
+            Arc::new(manager)
         })
         .await
 }
 
-#[derive(Debug, sqlx::FromRow, Serialize)]
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
 pub struct Coin {
     pub id: i64,
     pub account: String,
     pub token: String,
     pub created_at: i64,
     pub deleted: i64,
+    pub program: String, // spl token or token-2022 program id
+    pub transfer_fee_bps: Option<i64>,
+    pub transfer_fee_max: Option<String>, // raw u64 amount
+    pub permanent_delegate: Option<String>,
+    pub non_transferable: i64, // 1 is non-transferable
+    pub amount: String,        // raw u64 amount over all token accounts of the mint
+    pub decimals: i64,
+    pub pubkey: Option<String>, // the token account holding the most
+}
+
+impl Coin {
+    // true when the stored row is not what the chain says now
+    fn differs_from(&self, coin: &NewCoin) -> bool {
+        self.amount != coin.amount.to_string()
+            || self.decimals != coin.decimals as i64
+            || self.pubkey.as_deref() != Some(coin.pubkey.as_str())
+            || self.program != coin.program
+            || self.transfer_fee_bps != coin.extensions.transfer_fee_bps.map(i64::from)
+            || self.transfer_fee_max != coin.extensions.transfer_fee_max.map(|max| max.to_string())
+            || self.permanent_delegate != coin.extensions.permanent_delegate
+            || self.non_transferable != coin.extensions.non_transferable as i64
+    }
+}
+
+// a coin an account holds, as the daemon sees it on chain
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NewCoin {
+    pub token: String,
+    pub amount: u64,
+    pub decimals: u8,
+    pub pubkey: String,
+    pub program: String,
+    pub extensions: MintExtensions,
+}
+
+impl NewCoin {
+    pub fn new(token: &str) -> Self {
+        Self {
+            token: token.to_string(),
+            program: spl_token::id().to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+// how a transaction changed what the account holds of a mint
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+pub struct TokenBalanceChange {
+    pub id: i64,
+    pub account: String,
+    pub signature: String,
+    pub slot: i64,
+    pub block_time: Option<i64>,
+    pub mint: String,
+    pub pre_amount: String,  // raw u64 amount before the transaction
+    pub post_amount: String, // raw u64 amount after the transaction
+    pub delta: String,       // signed raw amount change
+    pub decimals: i64,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, Serialize)]
+pub struct SignatureCursor {
+    pub account: String,
+    pub signature: String, // newest ingested signature
+    pub slot: i64,
+    pub updated_at: i64,
+}
+
+// a transaction of a tracked account as the history ingestion sees it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IngestedTransaction {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub deltas: Vec<BalanceDelta>, // mints whose balance changed, empty for failed transactions
+    pub swap: Option<NewSwap>,     // when the account traded on a known dex
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BalanceDelta {
+    pub mint: String,
+    pub pre_amount: u64,
+    pub post_amount: u64,
+    pub decimals: u8,
+}
+
+impl BalanceDelta {
+    pub fn delta(&self) -> i128 {
+        self.post_amount as i128 - self.pre_amount as i128
+    }
+}
+
+// a swap decoded from a transaction, see swaps::decode_swap
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewSwap {
+    pub dex: Dex,
+    pub program: String,
+    pub side: SwapSide,
+    pub input_mint: String,
+    pub input_amount: u64,
+    pub input_decimals: u8,
+    pub output_mint: String,
+    pub output_amount: u64,
+    pub output_decimals: u8,
+    pub base_mint: String,  // the token bought or sold
+    pub quote_mint: String, // what the price is in
+    pub price: f64,         // quote per base in ui amounts
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+pub struct Swap {
+    pub id: i64,
+    pub account: String,
+    pub signature: String,
+    pub slot: i64,
+    pub block_time: Option<i64>,
+    #[sqlx(try_from = "String")]
+    pub dex: Dex,
+    pub program: String,
+    #[sqlx(try_from = "String")]
+    pub side: SwapSide,
+    pub input_mint: String,
+    pub input_amount: String, // raw u64 amount paid
+    pub input_decimals: i64,
+    pub output_mint: String,
+    pub output_amount: String, // raw u64 amount received
+    pub output_decimals: i64,
+    pub base_mint: String,
+    pub quote_mint: String,
+    pub price: f64,
+    pub created_at: i64,
+}
+
+impl Swap {
+    pub fn input_ui_amount(&self) -> f64 {
+        ui_amount(&self.input_amount, self.input_decimals)
+    }
+
+    pub fn output_ui_amount(&self) -> f64 {
+        ui_amount(&self.output_amount, self.output_decimals)
+    }
+}
+
+fn ui_amount(amount: &str, decimals: i64) -> f64 {
+    amount.parse::<u64>().unwrap_or(0) as f64 / 10f64.powi(decimals as i32)
+}
+
+// name, symbol and decimals of a mint, cached in the tokens table
+#[derive(Debug, Clone, Default, PartialEq, sqlx::FromRow, Serialize)]
+pub struct Token {
+    pub mint: String,
+    pub name: Option<String>, // none when the mint has no metadata
+    pub symbol: Option<String>,
+    pub uri: Option<String>,
+    pub decimals: i64,
+    pub program: String, // spl token or token-2022 program id
+    pub source: String,  // metaplex, token_2022 or none
+    pub updated_at: i64,
 }
 
-#[derive(Debug, sqlx::FromRow, Serialize)]
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
 pub struct Account {
     pub id: i64,
     pub account: String,
     pub created_at: i64,
     pub deleted: i64,
+    pub last_observed_slot: Option<i64>, // none until the daemon has observed the account
+    pub label: Option<String>,
+    pub note: Option<String>,
+    #[sqlx(try_from = "String")]
+    pub tags: Tags,
 }
 
-impl ModelsManager {
-    pub async fn add_new_account(&self, mint: String) -> Result<()> {
+impl Account {
+    // fields left out of the update are kept, an empty label or note clears it
+    pub fn apply(&mut self, update: &AccountUpdate) {
+        let text = |value: &String| Some(value.trim().to_string()).filter(|v| !v.is_empty());
+        if let Some(label) = &update.label {
+            self.label = text(label);
+        }
+        if let Some(note) = &update.note {
+            self.note = text(note);
+        }
+        if let Some(tags) = &update.tags {
+            self.tags = Tags::new(tags);
+        }
+    }
+}
+
+// tags of an account, trimmed, without duplicates and stored comma separated
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Tags(pub Vec<String>);
+
+impl Tags {
+    pub fn new(tags: &[String]) -> Self {
+        let mut unique: Vec<String> = vec![];
+        for tag in tags.iter().map(|tag| tag.trim()) {
+            if !tag.is_empty() && !unique.iter().any(|u| u == tag) {
+                unique.push(tag.to_string());
+            }
+        }
+        Tags(unique)
+    }
+
+    pub fn joined(&self) -> String {
+        self.0.join(",")
+    }
+}
+
+// stored as text, see joined
+impl TryFrom<String> for Tags {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        Ok(Tags(
+            value
+                .split(',')
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_string)
+                .collect(),
+        ))
+    }
+}
+
+// what PATCH /api/v1/accounts/{address} may change
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AccountUpdate {
+    pub label: Option<String>,
+    pub note: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
+impl AccountUpdate {
+    pub fn check(&self) -> Result<()> {
+        if self.label.as_ref().is_some_and(|l| l.chars().count() > 64) {
+            bail!("label is longer than 64 characters");
+        }
+        if self.note.as_ref().is_some_and(|n| n.chars().count() > 1000) {
+            bail!("note is longer than 1000 characters");
+        }
+        if let Some(tags) = &self.tags {
+            if tags.len() > 16 {
+                bail!("more than 16 tags");
+            }
+            if let Some(tag) = tags
+                .iter()
+                .find(|t| t.contains(',') || t.chars().count() > 32)
+            {
+                bail!("tag must be at most 32 characters without commas: {}", tag);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+pub struct HoldingSnapshot {
+    pub id: i64,
+    pub account: String,
+    pub mint: String,
+    pub amount: String, // raw u64 amount, "0" means exited
+    pub decimals: i64,
+    pub ui_amount: f64,
+    pub slot: i64,
+    pub observed_at: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PositionEventType {
+    NewPosition,
+    Increased,
+    Decreased,
+    Exited,
+}
+
+impl PositionEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PositionEventType::NewPosition => "new_position",
+            PositionEventType::Increased => "increased",
+            PositionEventType::Decreased => "decreased",
+            PositionEventType::Exited => "exited",
+        }
+    }
+}
+
+// stored as text, see as_str
+impl TryFrom<String> for PositionEventType {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        match value.as_str() {
+            "new_position" => Ok(PositionEventType::NewPosition),
+            "increased" => Ok(PositionEventType::Increased),
+            "decreased" => Ok(PositionEventType::Decreased),
+            "exited" => Ok(PositionEventType::Exited),
+            _ => Err(anyhow::anyhow!("unknown position event type: {}", value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+pub struct PositionEvent {
+    pub id: i64,
+    pub account: String,
+    pub token: String,
+    #[sqlx(try_from = "String")]
+    pub event_type: PositionEventType,
+    pub previous_amount: String, // raw u64 amount before the change
+    pub amount: String,          // raw u64 amount after the change
+    pub delta: String,           // signed raw amount change
+    pub decimals: i64,
+    pub ui_delta: f64,
+    pub slot: i64,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, Serialize)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub url: String,
+    pub event_id: i64,
+    pub payload: String,
+    pub attempts: i64,
+    pub status_code: Option<i64>, // http status of the last attempt
+    pub error: Option<String>,    // error of the last attempt
+    pub delivered: i64,           // 1 is delivered
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+#[async_trait]
+impl Repository for ModelsManager {
+    async fn add_new_account(&self, account: &str) -> Result<()> {
         // judge if the account exists
-        let sql_str = format!(
-            "SELECT * FROM accounts WHERE account = '{}' AND DELETED = 0;",
-            mint
-        );
-        let account = sqlx::query_as::<_, Account>(&sql_str)
-            .fetch_one(&self.pool)
-            .await
-            .ok();
-        if account.is_some() {
+        if self.get_account_with_mint(account).await?.is_some() {
+            return Ok(());
+        }
+        // a deleted account keeps its row, labels included
+        if self.restore_account(account).await? {
             return Ok(());
         }
 
         // insert new account
-        let sql_str = format!(
-            "INSERT INTO accounts (account, created_at, deleted) VALUES ('{}', {}, 0);",
-            mint,
-            chrono::Local::now().timestamp()
-        );
-        sqlx::query(&sql_str).execute(&self.pool).await?;
+        sqlx::query("INSERT INTO accounts (account, created_at, deleted) VALUES ($1, $2, 0);")
+            .bind(account)
+            .bind(chrono::Local::now().timestamp())
+            .execute(&self.pool)
+            .await?;
 
         Ok(())
     }
 
-    pub async fn get_account_with_mint(&self, mint: String) -> Result<Option<Account>> {
-        let sql_str = format!(
-            "SELECT * FROM accounts WHERE account = '{}' AND DELETED = 0;",
-            mint
-        );
-        let account = sqlx::query_as::<_, Account>(&sql_str)
-            .fetch_optional(&self.pool)
+    async fn delete_account(&self, account: &str) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        let deleted =
+            sqlx::query("UPDATE accounts SET deleted = 1 WHERE account = $1 AND deleted = 0;")
+                .bind(account)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+        sqlx::query("DELETE FROM coins WHERE account = $1 AND DELETED = 0;")
+            .bind(account)
+            .execute(&mut *tx)
             .await?;
+        tx.commit().await?;
+
+        Ok(deleted > 0)
+    }
+
+    async fn restore_account(&self, account: &str) -> Result<bool> {
+        // changes made while deleted were never tracked, so start from a new baseline
+        let restored = sqlx::query(
+            "UPDATE accounts SET deleted = 0, last_observed_slot = NULL WHERE account = $1 AND deleted = 1;",
+        )
+        .bind(account)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        Ok(restored > 0)
+    }
+
+    async fn update_account(
+        &self,
+        account: &str,
+        update: &AccountUpdate,
+    ) -> Result<Option<Account>> {
+        let Some(mut existing) = self.get_account_with_mint(account).await? else {
+            return Ok(None);
+        };
+        existing.apply(update);
+        sqlx::query("UPDATE accounts SET label = $1, note = $2, tags = $3 WHERE id = $4;")
+            .bind(&existing.label)
+            .bind(&existing.note)
+            .bind(existing.tags.joined())
+            .bind(existing.id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(Some(existing))
+    }
+
+    async fn get_account_with_mint(&self, account: &str) -> Result<Option<Account>> {
+        let account = sqlx::query_as::<_, Account>(
+            "SELECT * FROM accounts WHERE account = $1 AND DELETED = 0;",
+        )
+        .bind(account)
+        .fetch_optional(&self.pool)
+        .await?;
 
         Ok(account)
     }
 
-    pub async fn get_all_accounts(&self) -> Result<Vec<Account>> {
-        let sql_str = "SELECT * FROM accounts WHERE DELETED = 0;";
-        let accounts = sqlx::query_as::<_, Account>(sql_str)
+    async fn get_all_accounts(&self) -> Result<Vec<Account>> {
+        let accounts = sqlx::query_as::<_, Account>("SELECT * FROM accounts WHERE DELETED = 0;")
             .fetch_all(&self.pool)
             .await?;
 
         Ok(accounts)
     }
 
-    pub async fn add_new_coin(&self, account: &str, token: &str) -> Result<()> {
+    async fn add_new_coin(&self, account: &str, coin: &NewCoin) -> Result<()> {
         // judge if the coin exists
-        let sql_str = format!(
-            "SELECT * FROM coins WHERE account = '{}' AND token = '{}' AND DELETED = 0;",
-            account, token
-        );
-        let coin = sqlx::query_as::<_, Coin>(&sql_str)
-            .fetch_one(&self.pool)
-            .await
-            .ok();
+        let existing = sqlx::query_as::<_, Coin>(
+            "SELECT * FROM coins WHERE account = $1 AND token = $2 AND DELETED = 0;",
+        )
+        .bind(account)
+        .bind(&coin.token)
+        .fetch_optional(&self.pool)
+        .await?;
 
-        if coin.is_some() {
+        if let Some(existing) = existing {
+            if existing.differs_from(coin) {
+                self.update_coin(existing.id, coin).await?;
+            }
             return Ok(());
         }
 
         // insert new coin
-        let sql_str = format!(
-            "INSERT INTO coins (account, token, created_at, deleted) VALUES ('{}', '{}', {}, 0);",
-            account,
-            token,
-            chrono::Local::now().timestamp()
-        );
-        // execute sql
-        sqlx::query(&sql_str).execute(&self.pool).await?;
+        sqlx::query(
+            "INSERT INTO coins (account, token, created_at, deleted, amount, decimals, pubkey, \
+             program, transfer_fee_bps, transfer_fee_max, permanent_delegate, non_transferable) \
+             VALUES ($1, $2, $3, 0, $4, $5, $6, $7, $8, $9, $10, $11);",
+        )
+        .bind(account)
+        .bind(&coin.token)
+        .bind(chrono::Local::now().timestamp())
+        .bind(coin.amount.to_string())
+        .bind(coin.decimals as i64)
+        .bind(&coin.pubkey)
+        .bind(&coin.program)
+        .bind(coin.extensions.transfer_fee_bps.map(i64::from))
+        .bind(coin.extensions.transfer_fee_max.map(|max| max.to_string()))
+        .bind(coin.extensions.permanent_delegate.as_deref())
+        .bind(coin.extensions.non_transferable as i64)
+        .execute(&self.pool)
+        .await?;
 
-        info!("add new coin: {}, account: {}", token, account);
+        info!("add new coin: {}, account: {}", coin.token, account);
 
         Ok(())
     }
 
-    pub async fn del_coin_with_account(&self, account: &str) -> Result<()> {
-        let sql_str = format!(
-            "DELETE FROM coins WHERE account = '{}' AND DELETED = 0;",
-            account
-        );
-        sqlx::query(&sql_str).execute(&self.pool).await?;
+    async fn del_coin_with_account(&self, account: &str) -> Result<()> {
+        sqlx::query("DELETE FROM coins WHERE account = $1 AND DELETED = 0;")
+            .bind(account)
+            .execute(&self.pool)
+            .await?;
 
         Ok(())
     }
 
-    pub async fn get_coin_with_token(&self, token: String) -> Result<Option<Coin>> {
-        let sql_str = format!(
-            "SELECT * FROM coins WHERE token = '{}' AND DELETED = 0;",
-            token
-        );
-        let coin = sqlx::query_as::<_, Coin>(&sql_str)
-            .fetch_optional(&self.pool)
-            .await?;
+    async fn sync_coins_with_account(&self, account: &str, coins: &[NewCoin]) -> Result<()> {
+        let existing =
+            sqlx::query_as::<_, Coin>("SELECT * FROM coins WHERE account = $1 AND DELETED = 0;")
+                .bind(account)
+                .fetch_all(&self.pool)
+                .await?;
+
+        for coin in &existing {
+            if coins.iter().any(|c| c.token == coin.token) {
+                continue;
+            }
+            sqlx::query("DELETE FROM coins WHERE id = $1;")
+                .bind(coin.id)
+                .execute(&self.pool)
+                .await?;
+            info!("del coin: {}, account: {}", coin.token, account);
+        }
+
+        for coin in coins {
+            match existing.iter().find(|c| c.token == coin.token) {
+                Some(stored) if stored.differs_from(coin) => {
+                    self.update_coin(stored.id, coin).await?
+                }
+                Some(_) => {}
+                None => self.add_new_coin(account, coin).await?,
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn get_coin_with_token(&self, token: &str) -> Result<Option<Coin>> {
+        let coin =
+            sqlx::query_as::<_, Coin>("SELECT * FROM coins WHERE token = $1 AND DELETED = 0;")
+                .bind(token)
+                .fetch_optional(&self.pool)
+                .await?;
 
         Ok(coin)
     }
+
+    async fn get_coins_with_token(&self, token: &str) -> Result<Vec<Coin>> {
+        let coins = sqlx::query_as::<_, Coin>(
+            "SELECT * FROM coins WHERE token = $1 AND deleted = 0 ORDER BY created_at, id;",
+        )
+        .bind(token)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(coins)
+    }
+
+    async fn add_holding_snapshots(&self, account: &str, holdings: &Holdings) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        Self::insert_holding_snapshots(&mut tx, account, holdings).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn record_holdings(
+        &self,
+        account: &str,
+        holdings: &Holdings,
+        events: &[PositionEvent],
+    ) -> Result<Vec<PositionEvent>> {
+        let mut tx = self.pool.begin().await?;
+        Self::insert_holding_snapshots(&mut tx, account, holdings).await?;
+        let saved = Self::insert_position_events(&mut tx, events).await?;
+        sqlx::query("UPDATE accounts SET last_observed_slot = $1 WHERE account = $2;")
+            .bind(holdings.slot as i64)
+            .bind(account)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(saved)
+    }
+
+    async fn get_holding_timeline(
+        &self,
+        account: &str,
+        mint: &str,
+    ) -> Result<Vec<HoldingSnapshot>> {
+        let snapshots = sqlx::query_as::<_, HoldingSnapshot>(
+            "SELECT * FROM holding_snapshots WHERE account = $1 AND mint = $2 ORDER BY observed_at, id;",
+        )
+        .bind(account)
+        .bind(mint)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(snapshots)
+    }
+
+    async fn get_current_holdings(&self, account: &str) -> Result<Vec<HoldingSnapshot>> {
+        let snapshots = sqlx::query_as::<_, HoldingSnapshot>(
+            "SELECT * FROM holding_snapshots h WHERE account = $1 AND amount != '0' AND id = \
+             (SELECT MAX(id) FROM holding_snapshots WHERE account = h.account AND mint = h.mint) \
+             ORDER BY mint;",
+        )
+        .bind(account)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(snapshots)
+    }
+
+    async fn add_position_events(&self, events: &[PositionEvent]) -> Result<Vec<PositionEvent>> {
+        let mut tx = self.pool.begin().await?;
+        let saved = Self::insert_position_events(&mut tx, events).await?;
+        tx.commit().await?;
+
+        Ok(saved)
+    }
+
+    async fn get_position_events(
+        &self,
+        account: Option<&str>,
+        token: Option<&str>,
+        since: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<PositionEvent>> {
+        // QueryBuilder<Any> writes ? placeholders which postgres rejects, so number them here
+        let mut sql = "SELECT * FROM position_events WHERE 1 = 1".to_string();
+        let mut binds = 0;
+        let mut filter = |column: &str, op: &str| {
+            binds += 1;
+            sql.push_str(&format!(" AND {} {} ${}", column, op, binds));
+        };
+        if account.is_some() {
+            filter("account", "=");
+        }
+        if token.is_some() {
+            filter("token", "=");
+        }
+        if since.is_some() {
+            filter("created_at", ">=");
+        }
+        sql.push_str(&format!(
+            " ORDER BY created_at DESC, id DESC LIMIT ${};",
+            binds + 1
+        ));
+
+        let mut query = sqlx::query_as::<_, PositionEvent>(&sql);
+        if let Some(account) = account {
+            query = query.bind(account);
+        }
+        if let Some(token) = token {
+            query = query.bind(token);
+        }
+        if let Some(since) = since {
+            query = query.bind(since);
+        }
+        let events = query.bind(limit).fetch_all(&self.pool).await?;
+
+        Ok(events)
+    }
+
+    async fn add_webhook_delivery(&self, url: &str, event_id: i64, payload: &str) -> Result<i64> {
+        let now = chrono::Local::now().timestamp();
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO webhook_deliveries \
+             (url, event_id, payload, attempts, delivered, created_at, updated_at) \
+             VALUES ($1, $2, $3, 0, 0, $4, $5) RETURNING id;",
+        )
+        .bind(url)
+        .bind(event_id)
+        .bind(payload)
+        .bind(now)
+        .bind(now)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(id)
+    }
+
+    async fn update_webhook_delivery(
+        &self,
+        id: i64,
+        attempts: i64,
+        status_code: Option<i64>,
+        error: Option<&str>,
+        delivered: bool,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE webhook_deliveries SET attempts = $1, status_code = $2, error = $3, delivered = $4, updated_at = $5 \
+             WHERE id = $6;",
+        )
+        .bind(attempts)
+        .bind(status_code)
+        .bind(error)
+        .bind(delivered as i64)
+        .bind(chrono::Local::now().timestamp())
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_webhook_deliveries_with_event(
+        &self,
+        event_id: i64,
+    ) -> Result<Vec<WebhookDelivery>> {
+        let deliveries = sqlx::query_as::<_, WebhookDelivery>(
+            "SELECT * FROM webhook_deliveries WHERE event_id = $1 ORDER BY id;",
+        )
+        .bind(event_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(deliveries)
+    }
+
+    async fn add_token(&self, token: &Token) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO tokens (mint, name, symbol, uri, decimals, program, source, updated_at) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8) ON CONFLICT (mint) DO UPDATE SET \
+             name = excluded.name, symbol = excluded.symbol, uri = excluded.uri, \
+             decimals = excluded.decimals, program = excluded.program, \
+             source = excluded.source, updated_at = excluded.updated_at;",
+        )
+        .bind(&token.mint)
+        .bind(token.name.as_deref())
+        .bind(token.symbol.as_deref())
+        .bind(token.uri.as_deref())
+        .bind(token.decimals)
+        .bind(&token.program)
+        .bind(&token.source)
+        .bind(token.updated_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn add_ingested_transaction(
+        &self,
+        account: &str,
+        transaction: &IngestedTransaction,
+    ) -> Result<()> {
+        let now = chrono::Local::now().timestamp();
+        let mut tx = self.pool.begin().await?;
+        for delta in &transaction.deltas {
+            sqlx::query(
+                "INSERT INTO token_balance_changes (account, signature, slot, block_time, mint, \
+                 pre_amount, post_amount, delta, decimals, created_at) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) \
+                 ON CONFLICT (account, signature, mint) DO NOTHING;",
+            )
+            .bind(account)
+            .bind(&transaction.signature)
+            .bind(transaction.slot as i64)
+            .bind(transaction.block_time)
+            .bind(&delta.mint)
+            .bind(delta.pre_amount.to_string())
+            .bind(delta.post_amount.to_string())
+            .bind(delta.delta().to_string())
+            .bind(delta.decimals as i64)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        if let Some(swap) = &transaction.swap {
+            sqlx::query(
+                "INSERT INTO swaps (account, signature, slot, block_time, dex, program, side, \
+                 input_mint, input_amount, input_decimals, output_mint, output_amount, \
+                 output_decimals, base_mint, quote_mint, price, created_at) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17) \
+                 ON CONFLICT (account, signature) DO NOTHING;",
+            )
+            .bind(account)
+            .bind(&transaction.signature)
+            .bind(transaction.slot as i64)
+            .bind(transaction.block_time)
+            .bind(swap.dex.as_str())
+            .bind(&swap.program)
+            .bind(swap.side.as_str())
+            .bind(&swap.input_mint)
+            .bind(swap.input_amount.to_string())
+            .bind(swap.input_decimals as i64)
+            .bind(&swap.output_mint)
+            .bind(swap.output_amount.to_string())
+            .bind(swap.output_decimals as i64)
+            .bind(&swap.base_mint)
+            .bind(&swap.quote_mint)
+            .bind(swap.price)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query(
+            "INSERT INTO signature_cursors (account, signature, slot, updated_at) \
+             VALUES ($1, $2, $3, $4) ON CONFLICT (account) DO UPDATE SET \
+             signature = excluded.signature, slot = excluded.slot, updated_at = excluded.updated_at;",
+        )
+        .bind(account)
+        .bind(&transaction.signature)
+        .bind(transaction.slot as i64)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_signature_cursor(&self, account: &str) -> Result<Option<SignatureCursor>> {
+        let cursor = sqlx::query_as::<_, SignatureCursor>(
+            "SELECT * FROM signature_cursors WHERE account = $1;",
+        )
+        .bind(account)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(cursor)
+    }
+
+    async fn get_token_balance_changes(
+        &self,
+        account: Option<&str>,
+        mint: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<TokenBalanceChange>> {
+        let mut sql = "SELECT * FROM token_balance_changes WHERE 1 = 1".to_string();
+        let mut binds = 0;
+        for (column, value) in [("account", account), ("mint", mint)] {
+            if value.is_some() {
+                binds += 1;
+                sql.push_str(&format!(" AND {} = ${}", column, binds));
+            }
+        }
+        sql.push_str(&format!(
+            " ORDER BY slot DESC, id DESC LIMIT ${};",
+            binds + 1
+        ));
+
+        let mut query = sqlx::query_as::<_, TokenBalanceChange>(&sql);
+        for value in [account, mint].into_iter().flatten() {
+            query = query.bind(value);
+        }
+        let changes = query.bind(limit).fetch_all(&self.pool).await?;
+
+        Ok(changes)
+    }
+
+    async fn get_swaps(
+        &self,
+        account: Option<&str>,
+        mint: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<Swap>> {
+        let mut sql = "SELECT * FROM swaps WHERE 1 = 1".to_string();
+        let mut binds = 0;
+        if account.is_some() {
+            binds += 1;
+            sql.push_str(&format!(" AND account = ${}", binds));
+        }
+        if mint.is_some() {
+            binds += 1;
+            sql.push_str(&format!(
+                " AND (input_mint = ${} OR output_mint = ${})",
+                binds,
+                binds + 1
+            ));
+            binds += 1;
+        }
+        sql.push_str(&format!(
+            " ORDER BY slot DESC, id DESC LIMIT ${};",
+            binds + 1
+        ));
+
+        let mut query = sqlx::query_as::<_, Swap>(&sql);
+        if let Some(account) = account {
+            query = query.bind(account);
+        }
+        if let Some(mint) = mint {
+            query = query.bind(mint).bind(mint);
+        }
+        let swaps = query.bind(limit).fetch_all(&self.pool).await?;
+
+        Ok(swaps)
+    }
+
+    // the base is one of the sides, which the mint indexes cover
+    async fn get_token_swaps(&self, account: Option<&str>, token: &str) -> Result<Vec<Swap>> {
+        let swaps = match account {
+            Some(account) => sqlx::query_as::<_, Swap>(
+                "SELECT * FROM swaps WHERE (input_mint = $1 OR output_mint = $1) AND base_mint = $1 AND account = $2 ORDER BY slot, id;",
+            )
+            .bind(token)
+            .bind(account)
+            .fetch_all(&self.pool)
+            .await?,
+            None => sqlx::query_as::<_, Swap>(
+                "SELECT * FROM swaps WHERE (input_mint = $1 OR output_mint = $1) AND base_mint = $1 ORDER BY slot, id;",
+            )
+            .bind(token)
+            .fetch_all(&self.pool)
+            .await?,
+        };
+
+        Ok(swaps)
+    }
+
+    async fn get_tokens(&self, mints: &[String]) -> Result<Vec<Token>> {
+        if mints.is_empty() {
+            return Ok(vec![]);
+        }
+        let placeholders: Vec<String> = (1..=mints.len()).map(|i| format!("${}", i)).collect();
+        let sql = format!(
+            "SELECT * FROM tokens WHERE mint IN ({}) ORDER BY mint;",
+            placeholders.join(", ")
+        );
+        let mut query = sqlx::query_as::<_, Token>(&sql);
+        for mint in mints {
+            query = query.bind(mint);
+        }
+        Ok(query.fetch_all(&self.pool).await?)
+    }
+}
+
+// sqlite in memory with all migrations applied, for tests
+#[cfg(test)]
+impl ModelsManager {
+    pub(crate) async fn new_in_memory() -> Self {
+        // one connection, otherwise every connection gets its own in-memory database
+        sqlx::any::install_default_drivers();
+        let pool = sqlx::any::AnyPoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::migrate::SQLITE_MIGRATOR.run(&pool).await.unwrap();
+        ModelsManager::new(pool)
+    }
+
+    // a fresh database on the server in ANGEL_TEST_POSTGRES_URL, None when it is not set
+    pub(crate) async fn new_postgres_for_test() -> Option<Self> {
+        let url = std::env::var("ANGEL_TEST_POSTGRES_URL").ok()?;
+        sqlx::any::install_default_drivers();
+        let name = format!("angel_test_{}", rand::random::<u32>());
+        let admin = AnyPool::connect(&url).await.unwrap();
+        sqlx::query(&format!("CREATE DATABASE {};", name))
+            .execute(&admin)
+            .await
+            .unwrap();
+
+        let (base, _) = url.rsplit_once('/').unwrap();
+        let pool = AnyPool::connect(&format!("{}/{}", base, name))
+            .await
+            .unwrap();
+        crate::migrate::POSTGRES_MIGRATOR.run(&pool).await.unwrap();
+        Some(ModelsManager::new(pool))
+    }
 }
@@ -0,0 +1,66 @@
+use std::{collections::HashMap, time::Duration};
+
+use tokio::{
+    sync::Mutex,
+    time::{sleep_until, Instant},
+};
+
+// spaces out requests per key (an rpc endpoint), at most `rps` requests per second each
+pub struct RateLimiter {
+    interval: Duration,
+    next: Mutex<HashMap<String, Instant>>, // key -> earliest time the next request may start
+}
+
+impl RateLimiter {
+    pub fn new(rps: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / rps.max(1),
+            next: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // wait for a free slot on the key, callers are served in the order they arrive
+    pub async fn acquire(&self, key: &str) {
+        let at = {
+            let mut next = self.next.lock().await;
+            let now = Instant::now();
+            let at = next.get(key).map_or(now, |&at| at.max(now));
+            next.insert(key.to_string(), at + self.interval);
+            at
+        };
+        sleep_until(at).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_acquire_spacing() {
+        let limiter = Arc::new(RateLimiter::new(20));
+        let start = Instant::now();
+
+        let mut tasks = vec![];
+        for _ in 0..5 {
+            let limiter = limiter.clone();
+            tasks.push(tokio::spawn(async move {
+                limiter.acquire("a").await;
+                Instant::now() - start
+            }));
+        }
+        let mut done = vec![];
+        for task in tasks {
+            done.push(task.await.unwrap());
+        }
+        done.sort();
+        assert!(done[0] < Duration::from_millis(50));
+        assert!(done[4] >= Duration::from_millis(200));
+
+        // other endpoints have their own budget
+        let before = Instant::now();
+        limiter.acquire("b").await;
+        assert!(before.elapsed() < Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,1178 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::{
+    models::{
+        Account, AccountUpdate, Coin, HoldingSnapshot, IngestedTransaction, NewCoin, PositionEvent,
+        SignatureCursor, Swap, Tags, Token, TokenBalanceChange, WebhookDelivery,
+    },
+    solana_rpc::Holdings,
+};
+
+// storage used by the web handlers, the daemon and the notifiers. ModelsManager keeps it in
+// the database, MemoryRepository keeps it in memory for tests
+#[async_trait]
+pub trait Repository: Send + Sync {
+    // a deleted account is tracked again, with its labels
+    async fn add_new_account(&self, account: &str) -> Result<()>;
+    async fn get_account_with_mint(&self, account: &str) -> Result<Option<Account>>;
+    async fn get_all_accounts(&self) -> Result<Vec<Account>>;
+    // stop tracking, the row and the history stay so the account can be restored. its coins
+    // go, it no longer counts as a holder. false when the account is not tracked
+    async fn delete_account(&self, account: &str) -> Result<bool>;
+    // false when there is no deleted account to restore
+    async fn restore_account(&self, account: &str) -> Result<bool>;
+    // none when the account is not tracked
+    async fn update_account(
+        &self,
+        account: &str,
+        update: &AccountUpdate,
+    ) -> Result<Option<Account>>;
+
+    // a coin the account already has only gets its amount, program and extensions refreshed
+    async fn add_new_coin(&self, account: &str, coin: &NewCoin) -> Result<()>;
+    async fn del_coin_with_account(&self, account: &str) -> Result<()>;
+    // make the coins of an account match the coins it holds now, instead of wiping them
+    async fn sync_coins_with_account(&self, account: &str, coins: &[NewCoin]) -> Result<()>;
+    async fn get_coin_with_token(&self, token: &str) -> Result<Option<Coin>>;
+    // every tracked account holding the token, the earliest seen first
+    async fn get_coins_with_token(&self, token: &str) -> Result<Vec<Coin>>;
+
+    // record what the account holds in this pass, one row per mint. mints that were held
+    // in the previous pass but are gone now get a zero row so the exit shows up in the timeline
+    async fn add_holding_snapshots(&self, account: &str, holdings: &Holdings) -> Result<()>;
+    // position timeline of an account on one mint, oldest first
+    async fn get_holding_timeline(&self, account: &str, mint: &str)
+        -> Result<Vec<HoldingSnapshot>>;
+    // one daemon pass: the snapshots, the events diffed from them and the slot the account was
+    // observed at, as the account's last_observed_slot. all or nothing, so failed events are
+    // diffed again on the next pass. returns the events with their ids filled in
+    async fn record_holdings(
+        &self,
+        account: &str,
+        holdings: &Holdings,
+        events: &[PositionEvent],
+    ) -> Result<Vec<PositionEvent>>;
+    // latest snapshot of every mint the account currently holds
+    async fn get_current_holdings(&self, account: &str) -> Result<Vec<HoldingSnapshot>>;
+
+    // returns the events with their ids filled in
+    async fn add_position_events(&self, events: &[PositionEvent]) -> Result<Vec<PositionEvent>>;
+    // newest first, every filter is optional. since is a unix timestamp in seconds
+    async fn get_position_events(
+        &self,
+        account: Option<&str>,
+        token: Option<&str>,
+        since: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<PositionEvent>>;
+
+    async fn add_webhook_delivery(&self, url: &str, event_id: i64, payload: &str) -> Result<i64>;
+    // called after every attempt, status_code and error describe the last attempt
+    async fn update_webhook_delivery(
+        &self,
+        id: i64,
+        attempts: i64,
+        status_code: Option<i64>,
+        error: Option<&str>,
+        delivered: bool,
+    ) -> Result<()>;
+    async fn get_webhook_deliveries_with_event(
+        &self,
+        event_id: i64,
+    ) -> Result<Vec<WebhookDelivery>>;
+
+    // the balance changes and swap of one transaction and the cursor moving to it, in one go. a
+    // transaction that was stored before only moves the cursor
+    async fn add_ingested_transaction(
+        &self,
+        account: &str,
+        transaction: &IngestedTransaction,
+    ) -> Result<()>;
+    async fn get_signature_cursor(&self, account: &str) -> Result<Option<SignatureCursor>>;
+    // newest first, both filters are optional
+    async fn get_token_balance_changes(
+        &self,
+        account: Option<&str>,
+        mint: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<TokenBalanceChange>>;
+    // newest first, mint matches either side of the swap
+    async fn get_swaps(
+        &self,
+        account: Option<&str>,
+        mint: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<Swap>>;
+    // every swap with the token as the base, oldest first, nothing is left out of the totals
+    async fn get_token_swaps(&self, account: Option<&str>, token: &str) -> Result<Vec<Swap>>;
+
+    // a token that is already cached is replaced
+    async fn add_token(&self, token: &Token) -> Result<()>;
+    // the cached tokens among the mints, in mint order
+    async fn get_tokens(&self, mints: &[String]) -> Result<Vec<Token>>;
+}
+
+pub type SharedRepository = Arc<dyn Repository>;
+
+#[derive(Default)]
+struct Tables {
+    last_id: i64,
+    accounts: Vec<Account>,
+    coins: Vec<Coin>,
+    snapshots: Vec<HoldingSnapshot>,
+    events: Vec<PositionEvent>,
+    deliveries: Vec<WebhookDelivery>,
+    tokens: Vec<Token>,
+    balance_changes: Vec<TokenBalanceChange>,
+    cursors: HashMap<String, SignatureCursor>,
+    swaps: Vec<Swap>,
+}
+
+impl Tables {
+    fn next_id(&mut self) -> i64 {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    // latest snapshot per mint, in mint order
+    fn latest_snapshots(&self, account: &str) -> Vec<HoldingSnapshot> {
+        let mut latest: HashMap<&str, &HoldingSnapshot> = HashMap::new();
+        for snapshot in self.snapshots.iter().filter(|s| s.account == account) {
+            latest.insert(&snapshot.mint, snapshot);
+        }
+        let mut snapshots: Vec<HoldingSnapshot> = latest.into_values().cloned().collect();
+        snapshots.sort_by(|a, b| a.mint.cmp(&b.mint));
+        snapshots
+    }
+}
+
+// keeps everything in memory, for tests of the handlers and the daemon
+#[derive(Default)]
+pub struct MemoryRepository {
+    tables: Mutex<Tables>,
+}
+
+impl MemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl Repository for MemoryRepository {
+    async fn add_new_account(&self, account: &str) -> Result<()> {
+        let mut tables = self.tables.lock().unwrap();
+        if let Some(existing) = tables.accounts.iter_mut().find(|a| a.account == account) {
+            if existing.deleted == 1 {
+                existing.deleted = 0;
+                existing.last_observed_slot = None;
+            }
+            return Ok(());
+        }
+        let id = tables.next_id();
+        tables.accounts.push(Account {
+            id,
+            account: account.to_string(),
+            created_at: chrono::Local::now().timestamp(),
+            deleted: 0,
+            last_observed_slot: None,
+            label: None,
+            note: None,
+            tags: Tags::default(),
+        });
+        Ok(())
+    }
+
+    async fn get_account_with_mint(&self, account: &str) -> Result<Option<Account>> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables
+            .accounts
+            .iter()
+            .find(|a| a.account == account && a.deleted == 0)
+            .cloned())
+    }
+
+    async fn get_all_accounts(&self) -> Result<Vec<Account>> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables
+            .accounts
+            .iter()
+            .filter(|a| a.deleted == 0)
+            .cloned()
+            .collect())
+    }
+
+    async fn delete_account(&self, account: &str) -> Result<bool> {
+        let mut tables = self.tables.lock().unwrap();
+        let Some(existing) = tables
+            .accounts
+            .iter_mut()
+            .find(|a| a.account == account && a.deleted == 0)
+        else {
+            return Ok(false);
+        };
+        existing.deleted = 1;
+        tables.coins.retain(|c| c.account != account);
+        Ok(true)
+    }
+
+    async fn restore_account(&self, account: &str) -> Result<bool> {
+        let mut tables = self.tables.lock().unwrap();
+        let Some(existing) = tables
+            .accounts
+            .iter_mut()
+            .find(|a| a.account == account && a.deleted == 1)
+        else {
+            return Ok(false);
+        };
+        existing.deleted = 0;
+        existing.last_observed_slot = None;
+        Ok(true)
+    }
+
+    async fn update_account(
+        &self,
+        account: &str,
+        update: &AccountUpdate,
+    ) -> Result<Option<Account>> {
+        let mut tables = self.tables.lock().unwrap();
+        let Some(existing) = tables
+            .accounts
+            .iter_mut()
+            .find(|a| a.account == account && a.deleted == 0)
+        else {
+            return Ok(None);
+        };
+        existing.apply(update);
+        Ok(Some(existing.clone()))
+    }
+
+    async fn add_new_coin(&self, account: &str, coin: &NewCoin) -> Result<()> {
+        let mut tables = self.tables.lock().unwrap();
+        let extensions = &coin.extensions;
+        if let Some(existing) = tables
+            .coins
+            .iter_mut()
+            .find(|c| c.account == account && c.token == coin.token && c.deleted == 0)
+        {
+            existing.amount = coin.amount.to_string();
+            existing.decimals = coin.decimals as i64;
+            existing.pubkey = Some(coin.pubkey.clone());
+            existing.program = coin.program.clone();
+            existing.transfer_fee_bps = extensions.transfer_fee_bps.map(i64::from);
+            existing.transfer_fee_max = extensions.transfer_fee_max.map(|max| max.to_string());
+            existing.permanent_delegate = extensions.permanent_delegate.clone();
+            existing.non_transferable = extensions.non_transferable as i64;
+            return Ok(());
+        }
+        let id = tables.next_id();
+        tables.coins.push(Coin {
+            id,
+            account: account.to_string(),
+            token: coin.token.clone(),
+            created_at: chrono::Local::now().timestamp(),
+            deleted: 0,
+            amount: coin.amount.to_string(),
+            decimals: coin.decimals as i64,
+            pubkey: Some(coin.pubkey.clone()),
+            program: coin.program.clone(),
+            transfer_fee_bps: extensions.transfer_fee_bps.map(i64::from),
+            transfer_fee_max: extensions.transfer_fee_max.map(|max| max.to_string()),
+            permanent_delegate: extensions.permanent_delegate.clone(),
+            non_transferable: extensions.non_transferable as i64,
+        });
+        Ok(())
+    }
+
+    async fn del_coin_with_account(&self, account: &str) -> Result<()> {
+        let mut tables = self.tables.lock().unwrap();
+        tables
+            .coins
+            .retain(|c| !(c.account == account && c.deleted == 0));
+        Ok(())
+    }
+
+    async fn sync_coins_with_account(&self, account: &str, coins: &[NewCoin]) -> Result<()> {
+        self.tables
+            .lock()
+            .unwrap()
+            .coins
+            .retain(|c| c.account != account || coins.iter().any(|coin| coin.token == c.token));
+        for coin in coins {
+            self.add_new_coin(account, coin).await?;
+        }
+        Ok(())
+    }
+
+    async fn get_coin_with_token(&self, token: &str) -> Result<Option<Coin>> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables
+            .coins
+            .iter()
+            .find(|c| c.token == token && c.deleted == 0)
+            .cloned())
+    }
+
+    async fn get_coins_with_token(&self, token: &str) -> Result<Vec<Coin>> {
+        let tables = self.tables.lock().unwrap();
+        let mut coins: Vec<Coin> = tables
+            .coins
+            .iter()
+            .filter(|c| c.token == token && c.deleted == 0)
+            .cloned()
+            .collect();
+        coins.sort_by_key(|c| (c.created_at, c.id));
+        Ok(coins)
+    }
+
+    async fn add_holding_snapshots(&self, account: &str, holdings: &Holdings) -> Result<()> {
+        let observed_at = chrono::Local::now().timestamp();
+        let positions = holdings.positions()?;
+
+        let mut tables = self.tables.lock().unwrap();
+        let latest = tables.latest_snapshots(account);
+        for (mint, position) in &positions {
+            let id = tables.next_id();
+            tables.snapshots.push(HoldingSnapshot {
+                id,
+                account: account.to_string(),
+                mint: mint.clone(),
+                amount: position.amount.to_string(),
+                decimals: position.decimals as i64,
+                ui_amount: position.ui_amount,
+                slot: holdings.slot as i64,
+                observed_at,
+            });
+        }
+        for last in latest {
+            if last.amount == "0" || positions.contains_key(&last.mint) {
+                continue;
+            }
+            let id = tables.next_id();
+            tables.snapshots.push(HoldingSnapshot {
+                id,
+                amount: "0".to_string(),
+                ui_amount: 0.0,
+                slot: holdings.slot as i64,
+                observed_at,
+                ..last
+            });
+        }
+        Ok(())
+    }
+
+    async fn get_holding_timeline(
+        &self,
+        account: &str,
+        mint: &str,
+    ) -> Result<Vec<HoldingSnapshot>> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables
+            .snapshots
+            .iter()
+            .filter(|s| s.account == account && s.mint == mint)
+            .cloned()
+            .collect())
+    }
+
+    async fn record_holdings(
+        &self,
+        account: &str,
+        holdings: &Holdings,
+        events: &[PositionEvent],
+    ) -> Result<Vec<PositionEvent>> {
+        self.add_holding_snapshots(account, holdings).await?;
+        let saved = self.add_position_events(events).await?;
+        let mut tables = self.tables.lock().unwrap();
+        if let Some(existing) = tables.accounts.iter_mut().find(|a| a.account == account) {
+            existing.last_observed_slot = Some(holdings.slot as i64);
+        }
+        Ok(saved)
+    }
+
+    async fn get_current_holdings(&self, account: &str) -> Result<Vec<HoldingSnapshot>> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables
+            .latest_snapshots(account)
+            .into_iter()
+            .filter(|s| s.amount != "0")
+            .collect())
+    }
+
+    async fn add_position_events(&self, events: &[PositionEvent]) -> Result<Vec<PositionEvent>> {
+        let mut tables = self.tables.lock().unwrap();
+        let mut saved = Vec::with_capacity(events.len());
+        for event in events {
+            let event = PositionEvent {
+                id: tables.next_id(),
+                ..event.clone()
+            };
+            tables.events.push(event.clone());
+            saved.push(event);
+        }
+        Ok(saved)
+    }
+
+    async fn get_position_events(
+        &self,
+        account: Option<&str>,
+        token: Option<&str>,
+        since: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<PositionEvent>> {
+        let tables = self.tables.lock().unwrap();
+        let mut events: Vec<PositionEvent> = tables
+            .events
+            .iter()
+            .filter(|e| account.is_none_or(|account| e.account == account))
+            .filter(|e| token.is_none_or(|token| e.token == token))
+            .filter(|e| since.is_none_or(|since| e.created_at >= since))
+            .cloned()
+            .collect();
+        events.sort_by_key(|e| std::cmp::Reverse((e.created_at, e.id)));
+        events.truncate(limit.max(0) as usize);
+        Ok(events)
+    }
+
+    async fn add_webhook_delivery(&self, url: &str, event_id: i64, payload: &str) -> Result<i64> {
+        let now = chrono::Local::now().timestamp();
+        let mut tables = self.tables.lock().unwrap();
+        let id = tables.next_id();
+        tables.deliveries.push(WebhookDelivery {
+            id,
+            url: url.to_string(),
+            event_id,
+            payload: payload.to_string(),
+            attempts: 0,
+            status_code: None,
+            error: None,
+            delivered: 0,
+            created_at: now,
+            updated_at: now,
+        });
+        Ok(id)
+    }
+
+    async fn update_webhook_delivery(
+        &self,
+        id: i64,
+        attempts: i64,
+        status_code: Option<i64>,
+        error: Option<&str>,
+        delivered: bool,
+    ) -> Result<()> {
+        let mut tables = self.tables.lock().unwrap();
+        if let Some(delivery) = tables.deliveries.iter_mut().find(|d| d.id == id) {
+            delivery.attempts = attempts;
+            delivery.status_code = status_code;
+            delivery.error = error.map(str::to_string);
+            delivery.delivered = delivered as i64;
+            delivery.updated_at = chrono::Local::now().timestamp();
+        }
+        Ok(())
+    }
+
+    async fn get_webhook_deliveries_with_event(
+        &self,
+        event_id: i64,
+    ) -> Result<Vec<WebhookDelivery>> {
+        let tables = self.tables.lock().unwrap();
+        Ok(tables
+            .deliveries
+            .iter()
+            .filter(|d| d.event_id == event_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn add_ingested_transaction(
+        &self,
+        account: &str,
+        transaction: &IngestedTransaction,
+    ) -> Result<()> {
+        let now = chrono::Local::now().timestamp();
+        let mut tables = self.tables.lock().unwrap();
+        for delta in &transaction.deltas {
+            if tables.balance_changes.iter().any(|c| {
+                c.account == account && c.signature == transaction.signature && c.mint == delta.mint
+            }) {
+                continue;
+            }
+            let id = tables.next_id();
+            tables.balance_changes.push(TokenBalanceChange {
+                id,
+                account: account.to_string(),
+                signature: transaction.signature.clone(),
+                slot: transaction.slot as i64,
+                block_time: transaction.block_time,
+                mint: delta.mint.clone(),
+                pre_amount: delta.pre_amount.to_string(),
+                post_amount: delta.post_amount.to_string(),
+                delta: delta.delta().to_string(),
+                decimals: delta.decimals as i64,
+                created_at: now,
+            });
+        }
+        let stored = tables
+            .swaps
+            .iter()
+            .any(|s| s.account == account && s.signature == transaction.signature);
+        if let (Some(swap), false) = (&transaction.swap, stored) {
+            let id = tables.next_id();
+            tables.swaps.push(Swap {
+                id,
+                account: account.to_string(),
+                signature: transaction.signature.clone(),
+                slot: transaction.slot as i64,
+                block_time: transaction.block_time,
+                dex: swap.dex,
+                program: swap.program.clone(),
+                side: swap.side,
+                input_mint: swap.input_mint.clone(),
+                input_amount: swap.input_amount.to_string(),
+                input_decimals: swap.input_decimals as i64,
+                output_mint: swap.output_mint.clone(),
+                output_amount: swap.output_amount.to_string(),
+                output_decimals: swap.output_decimals as i64,
+                base_mint: swap.base_mint.clone(),
+                quote_mint: swap.quote_mint.clone(),
+                price: swap.price,
+                created_at: now,
+            });
+        }
+        tables.cursors.insert(
+            account.to_string(),
+            SignatureCursor {
+                account: account.to_string(),
+                signature: transaction.signature.clone(),
+                slot: transaction.slot as i64,
+                updated_at: now,
+            },
+        );
+        Ok(())
+    }
+
+    async fn get_signature_cursor(&self, account: &str) -> Result<Option<SignatureCursor>> {
+        Ok(self.tables.lock().unwrap().cursors.get(account).cloned())
+    }
+
+    async fn get_token_balance_changes(
+        &self,
+        account: Option<&str>,
+        mint: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<TokenBalanceChange>> {
+        let tables = self.tables.lock().unwrap();
+        let mut changes: Vec<TokenBalanceChange> = tables
+            .balance_changes
+            .iter()
+            .filter(|c| account.is_none_or(|account| c.account == account))
+            .filter(|c| mint.is_none_or(|mint| c.mint == mint))
+            .cloned()
+            .collect();
+        changes.sort_by_key(|c| std::cmp::Reverse((c.slot, c.id)));
+        changes.truncate(limit.max(0) as usize);
+        Ok(changes)
+    }
+
+    async fn get_swaps(
+        &self,
+        account: Option<&str>,
+        mint: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<Swap>> {
+        let tables = self.tables.lock().unwrap();
+        let mut swaps: Vec<Swap> = tables
+            .swaps
+            .iter()
+            .filter(|s| account.is_none_or(|account| s.account == account))
+            .filter(|s| mint.is_none_or(|mint| s.input_mint == mint || s.output_mint == mint))
+            .cloned()
+            .collect();
+        swaps.sort_by_key(|s| std::cmp::Reverse((s.slot, s.id)));
+        swaps.truncate(limit.max(0) as usize);
+        Ok(swaps)
+    }
+
+    async fn get_token_swaps(&self, account: Option<&str>, token: &str) -> Result<Vec<Swap>> {
+        let tables = self.tables.lock().unwrap();
+        let mut swaps: Vec<Swap> = tables
+            .swaps
+            .iter()
+            .filter(|s| account.is_none_or(|account| s.account == account))
+            .filter(|s| s.base_mint == token)
+            .cloned()
+            .collect();
+        swaps.sort_by_key(|s| (s.slot, s.id));
+        Ok(swaps)
+    }
+
+    async fn add_token(&self, token: &Token) -> Result<()> {
+        let mut tables = self.tables.lock().unwrap();
+        tables.tokens.retain(|t| t.mint != token.mint);
+        tables.tokens.push(token.clone());
+        Ok(())
+    }
+
+    async fn get_tokens(&self, mints: &[String]) -> Result<Vec<Token>> {
+        let tables = self.tables.lock().unwrap();
+        let mut tokens: Vec<Token> = tables
+            .tokens
+            .iter()
+            .filter(|t| mints.contains(&t.mint))
+            .cloned()
+            .collect();
+        tokens.sort_by(|a, b| a.mint.cmp(&b.mint));
+        Ok(tokens)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{BalanceDelta, ModelsManager, NewSwap, PositionEventType},
+        solana_rpc::{MintExtensions, TokenAccount},
+        swaps::{Dex, SwapSide},
+    };
+
+    // every check runs against both implementations, so they cannot drift apart. set
+    // ANGEL_TEST_POSTGRES_URL=postgres://user@host/db to run them on postgres as well
+    async fn repositories() -> Vec<Box<dyn Repository>> {
+        let mut repositories: Vec<Box<dyn Repository>> = vec![
+            Box::new(ModelsManager::new_in_memory().await),
+            Box::new(MemoryRepository::new()),
+        ];
+        if let Some(manager) = ModelsManager::new_postgres_for_test().await {
+            repositories.push(Box::new(manager));
+        }
+        repositories
+    }
+
+    fn token(mint: &str, amount: u64, decimals: u8) -> TokenAccount {
+        TokenAccount {
+            pubkey: format!("{}-ata", mint),
+            mint: mint.to_string(),
+            amount: amount.to_string(),
+            decimals,
+            ui_amount: amount as f64 / 10f64.powi(decimals as i32),
+            program: spl_token::id().to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_holding_timeline() {
+        for manager in repositories().await {
+            let pass = |slot, accounts| Holdings {
+                slot,
+                accounts,
+                ..Default::default()
+            };
+            manager
+                .add_holding_snapshots("evil", &pass(1, vec![token("a", 100, 2), token("b", 5, 0)]))
+                .await
+                .unwrap();
+            manager
+                .add_holding_snapshots("evil", &pass(2, vec![token("a", 300, 2)]))
+                .await
+                .unwrap();
+            manager
+                .add_holding_snapshots("evil", &pass(3, vec![token("a", 300, 2)]))
+                .await
+                .unwrap();
+
+            let timeline = manager.get_holding_timeline("evil", "a").await.unwrap();
+            let amounts: Vec<&str> = timeline.iter().map(|s| s.amount.as_str()).collect();
+            assert_eq!(amounts, vec!["100", "300", "300"]);
+            assert_eq!(timeline[1].slot, 2);
+            assert_eq!(timeline[1].ui_amount, 3.0);
+
+            // b was sold between pass 1 and 2, the exit is recorded once
+            let timeline = manager.get_holding_timeline("evil", "b").await.unwrap();
+            let amounts: Vec<&str> = timeline.iter().map(|s| s.amount.as_str()).collect();
+            assert_eq!(amounts, vec!["5", "0"]);
+            assert_eq!(timeline[1].slot, 2);
+
+            let current = manager.get_current_holdings("evil").await.unwrap();
+            assert_eq!(current.len(), 1);
+            assert_eq!(current[0].mint, "a");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_holdings() {
+        for manager in repositories().await {
+            manager.add_new_account("evil").await.unwrap();
+            let account = manager
+                .get_account_with_mint("evil")
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(account.last_observed_slot.is_none());
+
+            // nothing held is still an observation
+            let empty = Holdings {
+                slot: 5,
+                ..Default::default()
+            };
+            let saved = manager.record_holdings("evil", &empty, &[]).await.unwrap();
+            assert!(saved.is_empty());
+            let account = manager
+                .get_account_with_mint("evil")
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(account.last_observed_slot, Some(5));
+
+            let holdings = Holdings {
+                slot: 6,
+                accounts: vec![token("a", 100, 0)],
+                ..Default::default()
+            };
+            let event = PositionEvent {
+                id: 0,
+                account: "evil".to_string(),
+                token: "a".to_string(),
+                event_type: PositionEventType::NewPosition,
+                previous_amount: "0".to_string(),
+                amount: "100".to_string(),
+                delta: "100".to_string(),
+                decimals: 0,
+                ui_delta: 100.0,
+                slot: 6,
+                created_at: 1,
+            };
+            let saved = manager
+                .record_holdings("evil", &holdings, &[event])
+                .await
+                .unwrap();
+            assert!(saved[0].id > 0);
+            assert_eq!(manager.get_current_holdings("evil").await.unwrap().len(), 1);
+            let account = manager
+                .get_account_with_mint("evil")
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(account.last_observed_slot, Some(6));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_holding_snapshots_sum_token_accounts() {
+        for manager in repositories().await {
+            let holdings = Holdings {
+                slot: 7,
+                accounts: vec![token("a", 200, 2), token("a", 50, 2)],
+                ..Default::default()
+            };
+            manager
+                .add_holding_snapshots("evil", &holdings)
+                .await
+                .unwrap();
+
+            let timeline = manager.get_holding_timeline("evil", "a").await.unwrap();
+            assert_eq!(timeline.len(), 1);
+            assert_eq!(timeline[0].amount, "250");
+            assert_eq!(timeline[0].ui_amount, 2.5);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_position_events_filter() {
+        for manager in repositories().await {
+            let event = |account: &str, token: &str, created_at| PositionEvent {
+                id: 0,
+                account: account.to_string(),
+                token: token.to_string(),
+                event_type: PositionEventType::NewPosition,
+                previous_amount: "0".to_string(),
+                amount: "10".to_string(),
+                delta: "10".to_string(),
+                decimals: 0,
+                ui_delta: 10.0,
+                slot: 1,
+                created_at,
+            };
+            manager
+                .add_position_events(&[
+                    event("evil", "a", 10),
+                    event("evil", "b", 20),
+                    event("bad", "a", 30),
+                ])
+                .await
+                .unwrap();
+
+            let events = manager
+                .get_position_events(None, Some("a"), None, 100)
+                .await
+                .unwrap();
+            let accounts: Vec<&str> = events.iter().map(|e| e.account.as_str()).collect();
+            assert_eq!(accounts, vec!["bad", "evil"]);
+            assert_eq!(events[0].event_type, PositionEventType::NewPosition);
+
+            let events = manager
+                .get_position_events(Some("evil"), None, Some(15), 100)
+                .await
+                .unwrap();
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].token, "b");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_coins_with_account() {
+        for manager in repositories().await {
+            manager
+                .sync_coins_with_account("evil", &[NewCoin::new("a"), NewCoin::new("b")])
+                .await
+                .unwrap();
+
+            // c is a token-2022 mint, b got a transfer fee since the last pass
+            let token_2022 = NewCoin {
+                program: spl_token_2022::id().to_string(),
+                extensions: MintExtensions {
+                    permanent_delegate: Some("delegate".to_string()),
+                    non_transferable: true,
+                    ..Default::default()
+                },
+                ..NewCoin::new("c")
+            };
+            let fee = MintExtensions {
+                transfer_fee_bps: Some(100),
+                transfer_fee_max: Some(u64::MAX),
+                ..Default::default()
+            };
+            manager
+                .sync_coins_with_account(
+                    "evil",
+                    &[
+                        NewCoin {
+                            amount: u64::MAX,
+                            decimals: 9,
+                            pubkey: "b-ata".to_string(),
+                            extensions: fee,
+                            ..NewCoin::new("b")
+                        },
+                        token_2022,
+                    ],
+                )
+                .await
+                .unwrap();
+
+            assert!(manager.get_coin_with_token("a").await.unwrap().is_none());
+            let b = manager.get_coin_with_token("b").await.unwrap().unwrap();
+            assert_eq!(b.account, "evil");
+            assert_eq!(b.program, spl_token::id().to_string());
+            assert_eq!(b.amount, "18446744073709551615");
+            assert_eq!(b.decimals, 9);
+            assert_eq!(b.pubkey.as_deref(), Some("b-ata"));
+            assert_eq!(b.transfer_fee_bps, Some(100));
+            assert_eq!(b.transfer_fee_max, Some(u64::MAX.to_string()));
+            let c = manager.get_coin_with_token("c").await.unwrap().unwrap();
+            assert_eq!(c.program, spl_token_2022::id().to_string());
+            assert_eq!(c.permanent_delegate.as_deref(), Some("delegate"));
+            assert_eq!(c.non_transferable, 1);
+            assert_eq!(c.transfer_fee_bps, None);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_coins_with_token() {
+        for manager in repositories().await {
+            for account in ["evil", "bad"] {
+                manager
+                    .add_new_coin(account, &NewCoin::new("a"))
+                    .await
+                    .unwrap();
+            }
+            manager
+                .add_new_coin("evil", &NewCoin::new("b"))
+                .await
+                .unwrap();
+
+            let coins = manager.get_coins_with_token("a").await.unwrap();
+            let accounts: Vec<&str> = coins.iter().map(|c| c.account.as_str()).collect();
+            assert_eq!(accounts, vec!["evil", "bad"]);
+            assert!(manager.get_coins_with_token("c").await.unwrap().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tokens() {
+        for manager in repositories().await {
+            let token = Token {
+                mint: "b".to_string(),
+                name: Some("Evil".to_string()),
+                symbol: Some("EVIL".to_string()),
+                uri: Some("https://example.com/evil.json".to_string()),
+                decimals: 6,
+                program: spl_token::id().to_string(),
+                source: "metaplex".to_string(),
+                updated_at: 1,
+            };
+            manager.add_token(&token).await.unwrap();
+            let unnamed = Token {
+                mint: "a".to_string(),
+                program: spl_token::id().to_string(),
+                source: "none".to_string(),
+                ..Default::default()
+            };
+            manager.add_token(&unnamed).await.unwrap();
+            // resolving again replaces the cached row, token-2022 metadata has no length limit
+            let renamed = Token {
+                symbol: Some("EVIL2".repeat(100)),
+                uri: Some(format!("https://example.com/{}", "a".repeat(2000))),
+                updated_at: 2,
+                ..token.clone()
+            };
+            manager.add_token(&renamed).await.unwrap();
+
+            let mints = ["b", "a", "c"].map(String::from);
+            let tokens = manager.get_tokens(&mints).await.unwrap();
+            assert_eq!(tokens, vec![unnamed, renamed]);
+            assert!(manager.get_tokens(&[]).await.unwrap().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ingested_transactions() {
+        for manager in repositories().await {
+            assert!(manager
+                .get_signature_cursor("evil")
+                .await
+                .unwrap()
+                .is_none());
+            let buy = IngestedTransaction {
+                signature: "sig1".to_string(),
+                slot: 10,
+                block_time: Some(1733293394),
+                deltas: vec![BalanceDelta {
+                    mint: "a".to_string(),
+                    pre_amount: 0,
+                    post_amount: u64::MAX,
+                    decimals: 6,
+                }],
+                swap: Some(NewSwap {
+                    dex: Dex::PumpFun,
+                    program: "pump".to_string(),
+                    side: SwapSide::Buy,
+                    input_mint: "sol".to_string(),
+                    input_amount: 2_000_000_000,
+                    input_decimals: 9,
+                    output_mint: "a".to_string(),
+                    output_amount: u64::MAX,
+                    output_decimals: 6,
+                    base_mint: "a".to_string(),
+                    quote_mint: "sol".to_string(),
+                    price: 0.25,
+                }),
+            };
+            manager
+                .add_ingested_transaction("evil", &buy)
+                .await
+                .unwrap();
+            // stored again after a restart, nothing is duplicated
+            manager
+                .add_ingested_transaction("evil", &buy)
+                .await
+                .unwrap();
+            let failed = IngestedTransaction {
+                signature: "sig2".to_string(),
+                slot: 11,
+                ..Default::default()
+            };
+            manager
+                .add_ingested_transaction("evil", &failed)
+                .await
+                .unwrap();
+            let sell = IngestedTransaction {
+                signature: "sig3".to_string(),
+                slot: 12,
+                block_time: None,
+                deltas: vec![BalanceDelta {
+                    mint: "a".to_string(),
+                    pre_amount: u64::MAX,
+                    post_amount: 0,
+                    decimals: 6,
+                }],
+                swap: None,
+            };
+            manager
+                .add_ingested_transaction("bad", &sell)
+                .await
+                .unwrap();
+
+            let cursor = manager.get_signature_cursor("evil").await.unwrap().unwrap();
+            assert_eq!((cursor.signature.as_str(), cursor.slot), ("sig2", 11));
+
+            let changes = manager
+                .get_token_balance_changes(None, Some("a"), 100)
+                .await
+                .unwrap();
+            let signatures: Vec<&str> = changes.iter().map(|c| c.signature.as_str()).collect();
+            assert_eq!(signatures, vec!["sig3", "sig1"]);
+            assert_eq!(changes[0].delta, "-18446744073709551615");
+            assert_eq!(changes[0].block_time, None);
+            assert_eq!(changes[1].post_amount, u64::MAX.to_string());
+            assert_eq!(changes[1].block_time, Some(1733293394));
+
+            let changes = manager
+                .get_token_balance_changes(Some("evil"), None, 1)
+                .await
+                .unwrap();
+            assert_eq!(changes.len(), 1);
+            assert_eq!(changes[0].signature, "sig1");
+
+            // stored once although the transaction was added twice
+            let swaps = manager.get_swaps(None, Some("sol"), 100).await.unwrap();
+            assert_eq!(swaps.len(), 1);
+            assert_eq!(swaps[0].account, "evil");
+            assert_eq!((swaps[0].dex, swaps[0].side), (Dex::PumpFun, SwapSide::Buy));
+            assert_eq!(swaps[0].output_amount, u64::MAX.to_string());
+            assert_eq!(swaps[0].price, 0.25);
+            assert_eq!(swaps[0].block_time, Some(1733293394));
+            assert_eq!(
+                manager
+                    .get_swaps(Some("evil"), Some("a"), 100)
+                    .await
+                    .unwrap()
+                    .len(),
+                1
+            );
+            assert!(manager
+                .get_swaps(Some("bad"), None, 100)
+                .await
+                .unwrap()
+                .is_empty());
+            assert_eq!(
+                manager.get_token_swaps(None, "a").await.unwrap()[0].id,
+                swaps[0].id
+            );
+            assert!(manager
+                .get_token_swaps(Some("evil"), "sol")
+                .await
+                .unwrap()
+                .is_empty());
+            assert!(manager
+                .get_token_swaps(Some("bad"), "a")
+                .await
+                .unwrap()
+                .is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_account_input_is_not_sql() {
+        for manager in repositories().await {
+            let address = "x'); DELETE FROM accounts; --";
+            manager.add_new_account("evil").await.unwrap();
+            manager.add_new_account(address).await.unwrap();
+            manager.add_new_account(address).await.unwrap();
+
+            let accounts = manager.get_all_accounts().await.unwrap();
+            assert_eq!(accounts.len(), 2);
+            let account = manager.get_account_with_mint(address).await.unwrap();
+            assert_eq!(account.unwrap().account, address);
+            assert!(manager
+                .get_account_with_mint("' OR '1'='1")
+                .await
+                .unwrap()
+                .is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_account_lifecycle() {
+        for manager in repositories().await {
+            manager.add_new_account("evil").await.unwrap();
+            manager
+                .add_new_coin("evil", &NewCoin::new("mint"))
+                .await
+                .unwrap();
+            let update = AccountUpdate {
+                label: Some(" whale ".to_string()),
+                note: Some("bought the top".to_string()),
+                tags: Some(vec![
+                    "kol".to_string(),
+                    " sniper".to_string(),
+                    "kol".to_string(),
+                ]),
+            };
+            let account = manager
+                .update_account("evil", &update)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(account.label.as_deref(), Some("whale"));
+            assert_eq!(account.tags.0, vec!["kol", "sniper"]);
+
+            // only the fields sent change, an empty one is cleared
+            let update = AccountUpdate {
+                note: Some("".to_string()),
+                ..Default::default()
+            };
+            manager.update_account("evil", &update).await.unwrap();
+            let account = manager
+                .get_account_with_mint("evil")
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(account.label.as_deref(), Some("whale"));
+            assert!(account.note.is_none());
+            assert_eq!(
+                account.tags,
+                Tags(vec!["kol".to_string(), "sniper".to_string()])
+            );
+
+            assert!(manager.delete_account("evil").await.unwrap());
+            assert!(!manager.delete_account("evil").await.unwrap());
+            assert!(manager.get_all_accounts().await.unwrap().is_empty());
+            assert!(manager
+                .get_coins_with_token("mint")
+                .await
+                .unwrap()
+                .is_empty());
+            assert!(manager
+                .update_account("evil", &AccountUpdate::default())
+                .await
+                .unwrap()
+                .is_none());
+
+            assert!(manager.restore_account("evil").await.unwrap());
+            assert!(!manager.restore_account("evil").await.unwrap());
+            let account = manager
+                .get_account_with_mint("evil")
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(account.label.as_deref(), Some("whale"));
+            // the next pass after a restore is a baseline again
+            assert!(account.last_observed_slot.is_none());
+
+            // adding a deleted account again restores the same row
+            let holdings = Holdings {
+                slot: 7,
+                ..Default::default()
+            };
+            manager
+                .record_holdings("evil", &holdings, &[])
+                .await
+                .unwrap();
+            manager.add_new_account("evil").await.unwrap();
+            let accounts = manager.get_all_accounts().await.unwrap();
+            assert_eq!(accounts[0].last_observed_slot, Some(7));
+            manager.delete_account("evil").await.unwrap();
+            manager.add_new_account("evil").await.unwrap();
+            let accounts = manager.get_all_accounts().await.unwrap();
+            assert_eq!(accounts.len(), 1);
+            assert_eq!(accounts[0].id, account.id);
+            assert!(accounts[0].last_observed_slot.is_none());
+        }
+    }
+}
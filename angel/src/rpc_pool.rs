@@ -0,0 +1,283 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use tracing::warn;
+
+// what came back from one request to an endpoint
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Ok(Duration), // answered, with the latency
+    Error,
+    RateLimited,
+}
+
+#[derive(Debug)]
+struct Endpoint {
+    url: String,
+    weight: u32,
+    requests: u64,
+    errors: u64,
+    rate_limited: u64,
+    consecutive_failures: u32,
+    latency_ms: Option<f64>, // moving average of successful requests
+    ejected_until: Option<Instant>,
+}
+
+impl Endpoint {
+    fn is_healthy(&self, now: Instant) -> bool {
+        self.ejected_until.is_none_or(|until| until <= now)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointStatus {
+    pub url: String,
+    pub weight: u32,
+    pub healthy: bool,
+    pub ejected_for: u64, // seconds until the endpoint is tried again, 0 when healthy
+    pub current: bool,    // the endpoint sticky mode is using
+    pub requests: u64,
+    pub errors: u64,
+    pub rate_limited: u64,
+    pub error_rate: f64,
+    pub latency_ms: Option<f64>,
+}
+
+// rpc endpoints with health tracking, unhealthy ones are ejected for a while
+pub struct RpcPool {
+    endpoints: Mutex<Vec<Endpoint>>,
+    current: Mutex<Option<usize>>,
+    sticky: bool,
+    eject_after: u32,
+    eject_for: Duration,
+}
+
+impl RpcPool {
+    // endpoints are (url, weight), a weight of 0 is only used when everything else is ejected
+    pub fn new(
+        endpoints: Vec<(String, u32)>,
+        sticky: bool,
+        eject_after: u32,
+        eject_for: Duration,
+    ) -> Result<Self> {
+        if endpoints.is_empty() {
+            bail!("rpc pool needs at least one endpoint");
+        }
+        Ok(Self {
+            endpoints: Mutex::new(
+                endpoints
+                    .into_iter()
+                    .map(|(url, weight)| Endpoint {
+                        url,
+                        weight,
+                        requests: 0,
+                        errors: 0,
+                        rate_limited: 0,
+                        consecutive_failures: 0,
+                        latency_ms: None,
+                        ejected_until: None,
+                    })
+                    .collect(),
+            ),
+            current: Mutex::new(None),
+            sticky,
+            eject_after: eject_after.max(1),
+            eject_for,
+        })
+    }
+
+    // weighted random over the healthy endpoints, sticky mode keeps one until it is ejected
+    pub fn pick(&self) -> String {
+        let endpoints = self.endpoints.lock().unwrap();
+        let mut current = self.current.lock().unwrap();
+        let now = Instant::now();
+
+        if self.sticky {
+            if let Some(index) = *current {
+                if endpoints[index].is_healthy(now) {
+                    return endpoints[index].url.clone();
+                }
+            }
+        }
+
+        let healthy: Vec<usize> = (0..endpoints.len())
+            .filter(|&i| endpoints[i].is_healthy(now))
+            .collect();
+        let total: u64 = healthy.iter().map(|&i| endpoints[i].weight as u64).sum();
+        let index = if total > 0 {
+            let mut point = rand::random::<u64>() % total;
+            *healthy
+                .iter()
+                .find(|&&i| {
+                    let weight = endpoints[i].weight as u64;
+                    if point < weight {
+                        return true;
+                    }
+                    point -= weight;
+                    false
+                })
+                .unwrap()
+        } else if let Some(&index) = healthy.first() {
+            index
+        } else {
+            // everything is ejected, the one that comes back first is the best bet
+            (0..endpoints.len())
+                .min_by_key(|&i| endpoints[i].ejected_until)
+                .unwrap()
+        };
+
+        if self.sticky && *current != Some(index) {
+            if let Some(previous) = *current {
+                warn!(
+                    "rpc failover from {} to {}",
+                    endpoints[previous].url, endpoints[index].url
+                );
+            }
+            *current = Some(index);
+        }
+        endpoints[index].url.clone()
+    }
+
+    pub fn report(&self, url: &str, outcome: Outcome) {
+        let mut endpoints = self.endpoints.lock().unwrap();
+        let Some(endpoint) = endpoints.iter_mut().find(|e| e.url == url) else {
+            return;
+        };
+
+        endpoint.requests += 1;
+        match outcome {
+            Outcome::Ok(latency) => {
+                let ms = latency.as_secs_f64() * 1000.0;
+                endpoint.latency_ms = Some(match endpoint.latency_ms {
+                    Some(avg) => avg * 0.8 + ms * 0.2,
+                    None => ms,
+                });
+                endpoint.consecutive_failures = 0;
+                endpoint.ejected_until = None;
+                return;
+            }
+            Outcome::Error => endpoint.errors += 1,
+            Outcome::RateLimited => endpoint.rate_limited += 1,
+        }
+
+        endpoint.consecutive_failures += 1;
+        if endpoint.consecutive_failures >= self.eject_after {
+            warn!(
+                "rpc endpoint {} ejected for {:?} after {} failures",
+                endpoint.url, self.eject_for, endpoint.consecutive_failures
+            );
+            endpoint.consecutive_failures = 0;
+            endpoint.ejected_until = Some(Instant::now() + self.eject_for);
+        }
+    }
+
+    pub fn status(&self) -> Vec<EndpointStatus> {
+        let endpoints = self.endpoints.lock().unwrap();
+        let current = *self.current.lock().unwrap();
+        let now = Instant::now();
+        endpoints
+            .iter()
+            .enumerate()
+            .map(|(i, e)| EndpointStatus {
+                url: e.url.clone(),
+                weight: e.weight,
+                healthy: e.is_healthy(now),
+                ejected_for: e
+                    .ejected_until
+                    .map_or(0, |until| until.saturating_duration_since(now).as_secs()),
+                current: self.sticky && current == Some(i),
+                requests: e.requests,
+                errors: e.errors,
+                rate_limited: e.rate_limited,
+                error_rate: if e.requests == 0 {
+                    0.0
+                } else {
+                    (e.errors + e.rate_limited) as f64 / e.requests as f64
+                },
+                latency_ms: e.latency_ms,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pool(sticky: bool) -> RpcPool {
+        RpcPool::new(
+            vec![
+                ("a".to_string(), 3),
+                ("b".to_string(), 1),
+                ("c".to_string(), 0),
+            ],
+            sticky,
+            2,
+            Duration::from_secs(60),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_no_endpoints() {
+        assert!(RpcPool::new(vec![], false, 2, Duration::from_secs(60)).is_err());
+    }
+
+    #[test]
+    fn test_weighted_pick() {
+        let pool = pool(false);
+        let picks: Vec<String> = (0..400).map(|_| pool.pick()).collect();
+        let a = picks.iter().filter(|url| *url == "a").count();
+        let b = picks.iter().filter(|url| *url == "b").count();
+        assert_eq!(a + b, 400);
+        assert!(a > b * 2, "a: {}, b: {}", a, b);
+    }
+
+    #[test]
+    fn test_eject_and_recover() {
+        let pool = pool(false);
+        pool.report("a", Outcome::Error);
+        pool.report("a", Outcome::Ok(Duration::from_millis(10)));
+        pool.report("a", Outcome::Error);
+        assert!(pool.status()[0].healthy);
+
+        pool.report("a", Outcome::RateLimited);
+        let status = pool.status();
+        assert!(!status[0].healthy);
+        assert!(status[0].ejected_for > 0);
+        assert_eq!(status[0].requests, 4);
+        assert_eq!(status[0].errors, 2);
+        assert_eq!(status[0].rate_limited, 1);
+        assert_eq!(status[0].error_rate, 0.75);
+        assert_eq!(status[0].latency_ms, Some(10.0));
+        assert!((0..50).all(|_| pool.pick() == "b"));
+
+        // with every weighted endpoint gone the zero weight one still serves
+        pool.report("b", Outcome::Error);
+        pool.report("b", Outcome::Error);
+        assert_eq!(pool.pick(), "c");
+
+        // and when nothing is left, the first to come back is used
+        pool.report("c", Outcome::Error);
+        pool.report("c", Outcome::Error);
+        assert_eq!(pool.pick(), "a");
+    }
+
+    #[test]
+    fn test_sticky_failover() {
+        let pool = pool(true);
+        let first = pool.pick();
+        assert!((0..50).all(|_| pool.pick() == first));
+        assert!(pool.status().iter().any(|s| s.url == first && s.current));
+
+        pool.report(&first, Outcome::Error);
+        pool.report(&first, Outcome::Error);
+        let second = pool.pick();
+        assert_ne!(second, first);
+        assert!((0..50).all(|_| pool.pick() == second));
+    }
+}
@@ -0,0 +1,210 @@
+use anyhow::{bail, Result};
+use serde::Serialize;
+use solana_sdk::{account::Account, program_option::COption, pubkey::Pubkey};
+use spl_token_2022::{
+    extension::{
+        default_account_state::DefaultAccountState, mint_close_authority::MintCloseAuthority,
+        transfer_hook::TransferHook, BaseStateWithExtensions, StateWithExtensions,
+    },
+    state::{AccountState, Mint},
+};
+
+use crate::solana_rpc::MintExtensions;
+
+// something about the mint that lets its authorities hurt a holder
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SafetyRisk {
+    MintAuthority,   // more can be minted
+    FreezeAuthority, // token accounts can be frozen
+    TransferFee,
+    PermanentDelegate,
+    NonTransferable,
+    TransferHook,       // a program runs on every transfer and can block it
+    DefaultFrozen,      // new token accounts start frozen
+    MintCloseAuthority, // the mint can be closed and recreated
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenSafety {
+    pub token: String,
+    pub program: String, // spl token or token-2022
+    pub supply: String,  // raw u64 supply
+    pub decimals: u8,
+    pub ui_supply: f64,
+    pub mint_authority: Option<String>,
+    pub freeze_authority: Option<String>,
+    pub mint_renounced: bool,
+    pub freeze_renounced: bool,
+    pub extensions: MintExtensions,
+    pub transfer_hook: Option<String>, // program called on transfers
+    pub default_frozen: bool,
+    pub close_authority: Option<String>,
+    pub risks: Vec<SafetyRisk>,
+    pub safe: bool, // nothing in risks
+}
+
+impl TokenSafety {
+    pub fn from_mint_account(token: &str, account: &Account) -> Result<Self> {
+        let program = account.owner;
+        if program != spl_token::id() && program != spl_token_2022::id() {
+            bail!("{} is not a token mint, owner is {}", token, program);
+        }
+        let mint = StateWithExtensions::<Mint>::unpack(&account.data)?;
+        let extensions = MintExtensions::from_mint_data(&account.data)?;
+
+        let authority = |key: COption<Pubkey>| Option::<Pubkey>::from(key).map(|k| k.to_string());
+        let mint_authority = authority(mint.base.mint_authority);
+        let freeze_authority = authority(mint.base.freeze_authority);
+        let transfer_hook = mint
+            .get_extension::<TransferHook>()
+            .ok()
+            .and_then(|hook| Option::<Pubkey>::from(hook.program_id))
+            .map(|program_id| program_id.to_string());
+        let default_frozen = mint
+            .get_extension::<DefaultAccountState>()
+            .is_ok_and(|state| state.state == AccountState::Frozen as u8);
+        let close_authority = mint
+            .get_extension::<MintCloseAuthority>()
+            .ok()
+            .and_then(|close| Option::<Pubkey>::from(close.close_authority))
+            .map(|close| close.to_string());
+
+        let mut risks = vec![];
+        let checks = [
+            (mint_authority.is_some(), SafetyRisk::MintAuthority),
+            (freeze_authority.is_some(), SafetyRisk::FreezeAuthority),
+            (
+                extensions.transfer_fee_bps.is_some_and(|bps| bps > 0),
+                SafetyRisk::TransferFee,
+            ),
+            (
+                extensions.permanent_delegate.is_some(),
+                SafetyRisk::PermanentDelegate,
+            ),
+            (extensions.non_transferable, SafetyRisk::NonTransferable),
+            (transfer_hook.is_some(), SafetyRisk::TransferHook),
+            (default_frozen, SafetyRisk::DefaultFrozen),
+            (close_authority.is_some(), SafetyRisk::MintCloseAuthority),
+        ];
+        for (found, risk) in checks {
+            if found {
+                risks.push(risk);
+            }
+        }
+
+        Ok(Self {
+            token: token.to_string(),
+            program: program.to_string(),
+            supply: mint.base.supply.to_string(),
+            decimals: mint.base.decimals,
+            ui_supply: mint.base.supply as f64 / 10f64.powi(mint.base.decimals as i32),
+            mint_renounced: mint_authority.is_none(),
+            freeze_renounced: freeze_authority.is_none(),
+            mint_authority,
+            freeze_authority,
+            extensions,
+            transfer_hook,
+            default_frozen,
+            close_authority,
+            safe: risks.is_empty(),
+            risks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::program_pack::Pack;
+    use spl_token_2022::extension::{
+        BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut,
+    };
+
+    fn account(owner: Pubkey, data: Vec<u8>) -> Account {
+        Account {
+            owner,
+            data,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_renounced_spl_token() {
+        let mut data = vec![0; Mint::LEN];
+        Mint::pack(
+            Mint {
+                supply: 1_000_000_000,
+                decimals: 6,
+                is_initialized: true,
+                ..Default::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+
+        let safety =
+            TokenSafety::from_mint_account("mint", &account(spl_token::id(), data)).unwrap();
+        assert!(safety.safe);
+        assert!(safety.mint_renounced && safety.freeze_renounced);
+        assert_eq!(safety.supply, "1000000000");
+        assert_eq!(safety.ui_supply, 1000.0);
+        assert_eq!(safety.program, spl_token::id().to_string());
+    }
+
+    #[test]
+    fn test_risky_token_2022() {
+        let authority = Pubkey::new_unique();
+        let hook = Pubkey::new_unique();
+        let space = ExtensionType::try_calculate_account_len::<Mint>(&[
+            ExtensionType::TransferHook,
+            ExtensionType::DefaultAccountState,
+            ExtensionType::MintCloseAuthority,
+        ])
+        .unwrap();
+        let mut data = vec![0; space];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        state
+            .init_extension::<TransferHook>(true)
+            .unwrap()
+            .program_id = Some(hook).try_into().unwrap();
+        state
+            .init_extension::<DefaultAccountState>(true)
+            .unwrap()
+            .state = AccountState::Frozen as u8;
+        state
+            .init_extension::<MintCloseAuthority>(true)
+            .unwrap()
+            .close_authority = Some(authority).try_into().unwrap();
+        state.base = Mint {
+            mint_authority: COption::Some(authority),
+            freeze_authority: COption::Some(authority),
+            decimals: 9,
+            is_initialized: true,
+            ..Default::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+
+        let safety =
+            TokenSafety::from_mint_account("mint", &account(spl_token_2022::id(), data)).unwrap();
+        assert!(!safety.safe);
+        assert!(!safety.mint_renounced);
+        assert_eq!(safety.freeze_authority, Some(authority.to_string()));
+        assert_eq!(safety.transfer_hook, Some(hook.to_string()));
+        assert_eq!(
+            safety.risks,
+            vec![
+                SafetyRisk::MintAuthority,
+                SafetyRisk::FreezeAuthority,
+                SafetyRisk::TransferHook,
+                SafetyRisk::DefaultFrozen,
+                SafetyRisk::MintCloseAuthority,
+            ]
+        );
+
+        // a wallet is not a mint
+        let wallet = account(solana_sdk::system_program::id(), vec![]);
+        assert!(TokenSafety::from_mint_account("wallet", &wallet).is_err());
+    }
+}
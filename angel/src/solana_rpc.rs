@@ -1,9 +1,53 @@
-use anyhow::Result;
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
-use solana_account_decoder::UiAccountData;
-use solana_client::{rpc_client::RpcClient, rpc_response::RpcTokenAccountBalance};
-use solana_sdk::pubkey::Pubkey;
-use tracing::debug;
+use solana_account_decoder::{parse_token::UiTokenAmount, UiAccountData};
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    nonblocking::rpc_client::RpcClient,
+    rpc_client::GetConfirmedSignaturesForAddress2Config,
+    rpc_config::RpcTransactionConfig,
+    rpc_custom_error::JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY,
+    rpc_request::{RpcError, TokenAccountsFilter},
+    rpc_response::{RpcConfirmedTransactionStatusWithSignature, RpcTokenAccountBalance},
+};
+use solana_sdk::{
+    account::Account,
+    commitment_config::{CommitmentConfig, CommitmentLevel},
+    pubkey::Pubkey,
+    signature::Signature,
+};
+use solana_transaction_status_client_types::{
+    EncodedConfirmedTransactionWithStatusMeta, UiTransactionEncoding,
+};
+use spl_token_2022::{
+    extension::{
+        non_transferable::NonTransferable, permanent_delegate::PermanentDelegate,
+        transfer_fee::TransferFeeConfig, BaseStateWithExtensions, StateWithExtensions,
+    },
+    state::{Account as TokenAccountState, Mint},
+};
+use tokio::{sync::OnceCell, time::Instant};
+use tracing::{debug, warn};
+
+use crate::{
+    config::{get_global_config, DustConfig, RpcConfig},
+    metadata::{metadata_pda, token_from_accounts},
+    models::Token,
+    rate_limit::RateLimiter,
+    rpc_pool::{Outcome, RpcPool},
+    safety::TokenSafety,
+};
+
+// keys per getMultipleAccounts request
+pub const MULTIPLE_ACCOUNTS_LIMIT: usize = 100;
 
 pub type TokenAccounts = Vec<TokenAccount>;
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -11,7 +55,127 @@ pub struct TokenAccount {
     pub pubkey: String,
     pub mint: String,
     pub amount: String, // 区块链计数123123
-    pub ui_amount: f64, // 就是我们看到的数量123.123
+    pub decimals: u8,
+    pub ui_amount: f64,  // 就是我们看到的数量123.123
+    pub program: String, // spl token 或 token-2022 的program id
+}
+
+impl TokenAccount {
+    // the same token account read again by pubkey, none once it was closed, handed to another
+    // owner or reopened for another mint, the next owner scan picks up what replaced it
+    pub fn refreshed(&self, owner: &Pubkey, account: Option<&Account>) -> Option<TokenAccount> {
+        let account = account?;
+        if account.owner.to_string() != self.program {
+            return None;
+        }
+        let state = StateWithExtensions::<TokenAccountState>::unpack(&account.data).ok()?;
+        if state.base.owner != *owner || state.base.mint.to_string() != self.mint {
+            return None;
+        }
+        Some(TokenAccount {
+            amount: state.base.amount.to_string(),
+            ui_amount: spl_token_2022::amount_to_ui_amount(state.base.amount, self.decimals),
+            ..self.clone()
+        })
+    }
+}
+
+// mint extensions of token-2022 that matter before buying, spl token mints have none
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct MintExtensions {
+    pub transfer_fee_bps: Option<u16>, // fee of the newest transfer fee config
+    pub transfer_fee_max: Option<u64>, // raw amount cap of that fee
+    pub permanent_delegate: Option<String>, // can move or burn anyone's tokens
+    pub non_transferable: bool,
+}
+
+impl MintExtensions {
+    pub fn from_mint_data(data: &[u8]) -> Result<Self> {
+        let mint = StateWithExtensions::<Mint>::unpack(data)?;
+        let mut extensions = MintExtensions::default();
+        if let Ok(fee) = mint.get_extension::<TransferFeeConfig>() {
+            extensions.transfer_fee_bps =
+                Some(fee.newer_transfer_fee.transfer_fee_basis_points.into());
+            extensions.transfer_fee_max = Some(fee.newer_transfer_fee.maximum_fee.into());
+        }
+        if let Ok(delegate) = mint.get_extension::<PermanentDelegate>() {
+            extensions.permanent_delegate =
+                Option::<Pubkey>::from(delegate.delegate).map(|pubkey| pubkey.to_string());
+        }
+        extensions.non_transferable = mint.get_extension::<NonTransferable>().is_ok();
+        Ok(extensions)
+    }
+}
+
+// token accounts of an owner, tagged with the slot the rpc node answered at
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Holdings {
+    pub slot: u64,
+    pub accounts: TokenAccounts,
+    pub extensions: BTreeMap<String, MintExtensions>, // mint -> extensions, token-2022 mints only
+    pub skipped: TokenAccounts,                       // dust, not tracked but kept for debugging
+}
+
+// all token accounts of one mint added together
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct Position {
+    pub amount: u64,
+    pub decimals: u8,
+    pub ui_amount: f64,
+    pub program: String,
+    pub pubkey: String, // the token account holding the most
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TokenAccountOwner {
+    pub owner: String,
+    pub owner_program: Option<String>,
+}
+
+impl Holdings {
+    // the threshold applies to what the account holds of a mint, across all its token accounts
+    pub fn skip_dust(&mut self, account: &str, dust: &DustConfig) {
+        let mut totals: HashMap<&str, f64> = HashMap::new();
+        for token in &self.accounts {
+            *totals.entry(token.mint.as_str()).or_default() += token.ui_amount;
+        }
+        let dust_mints: Vec<String> = totals
+            .into_iter()
+            .filter(|(mint, ui_amount)| dust.is_dust(account, mint, *ui_amount))
+            .map(|(mint, _)| mint.to_string())
+            .collect();
+
+        let (skipped, kept): (TokenAccounts, TokenAccounts) = std::mem::take(&mut self.accounts)
+            .into_iter()
+            .partition(|token| dust_mints.contains(&token.mint));
+        self.accounts = kept;
+        self.skipped.extend(skipped);
+    }
+
+    // an owner may have several token accounts for one mint, sum them up by mint
+    pub fn positions(&self) -> Result<BTreeMap<String, Position>> {
+        let mut positions: BTreeMap<String, Position> = BTreeMap::new();
+        let mut largest: HashMap<&str, u64> = HashMap::new();
+        for token in &self.accounts {
+            let amount = token.amount.parse::<u64>()?;
+            let position = positions
+                .entry(token.mint.clone())
+                .or_insert_with(|| Position {
+                    decimals: token.decimals,
+                    program: token.program.clone(),
+                    ..Default::default()
+                });
+            position.amount += amount;
+            position.ui_amount += token.ui_amount;
+
+            let max = largest.entry(token.mint.as_str()).or_default();
+            if position.pubkey.is_empty() || amount > *max {
+                *max = amount;
+                position.pubkey = token.pubkey.clone();
+            }
+        }
+        Ok(positions)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -40,48 +204,706 @@ struct Parsed {
     account_type: String,
 }
 
-// rpc https://solana.com/docs/rpc/http/gettokenaccountsbyowner
-pub async fn get_tokens_with_account(account: &str, rpc_url: &str) -> Result<Vec<TokenAccount>> {
-    let client = RpcClient::new(rpc_url);
-
-    let account_pubkey = Pubkey::from_str_const(account);
-    let token_accounts = client.get_token_accounts_by_owner(
-        &account_pubkey,
-        solana_client::rpc_request::TokenAccountsFilter::ProgramId(spl_token::id()),
-    )?;
-
-    let mut accounts: TokenAccounts = vec![];
-    for token_account in token_accounts {
-        let account_data = token_account.account.data;
-        match account_data {
-            UiAccountData::Json(parsed_account) => {
-                let parsed: Parsed = serde_json::from_value(parsed_account.parsed)?;
-                if parsed.info.token_amount.ui_amount < 100000.0 {
-                    debug!("skip token account: {:?}", parsed);
-                    continue;
+// one nonblocking client per endpoint, shared by the daemon and the web handlers,
+// every request goes to an endpoint picked by the pool and waits for its rate limit
+pub struct SolanaRpc {
+    config: RpcConfig,
+    clients: Mutex<HashMap<String, Arc<RpcClient>>>,
+    pool: RpcPool,
+    limiter: RateLimiter,
+}
+
+pub static GLOBAL_SOLANA_RPC: OnceCell<Arc<SolanaRpc>> = OnceCell::const_new();
+
+pub async fn get_global_solana_rpc() -> &'static Arc<SolanaRpc> {
+    GLOBAL_SOLANA_RPC
+        .get_or_init(|| async {
+            let config = get_global_config().await;
+            Arc::new(
+                SolanaRpc::new(
+                    config.rpc.clone(),
+                    config.rpc_endpoints(),
+                    config.solana_rpc_rps,
+                )
+                .expect("Failed to build solana rpc"),
+            )
+        })
+        .await
+}
+
+fn is_rate_limited(error: &ClientError) -> bool {
+    matches!(error.kind(), ClientErrorKind::Reqwest(e) if e.status().is_some_and(|status| status.as_u16() == 429))
+}
+
+// rate limits, server errors and dropped connections are worth another try, bad input is not
+fn is_retryable(error: &ClientError) -> bool {
+    match error.kind() {
+        ClientErrorKind::Io(_) => true,
+        ClientErrorKind::Reqwest(e) => {
+            e.is_timeout()
+                || e.is_connect()
+                || e.is_request()
+                || e.status()
+                    .is_some_and(|status| status.as_u16() == 429 || status.is_server_error())
+        }
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. }) => {
+            *code == JSON_RPC_SERVER_ERROR_NODE_UNHEALTHY
+        }
+        _ => false,
+    }
+}
+
+impl SolanaRpc {
+    // endpoints are (url, weight), rps is the limit of each endpoint
+    // fails without endpoints, the config check makes sure there is one
+    pub fn new(config: RpcConfig, endpoints: Vec<(String, u32)>, rps: u32) -> Result<Self> {
+        Ok(Self {
+            pool: RpcPool::new(
+                endpoints,
+                config.sticky,
+                config.eject_after,
+                Duration::from_secs(config.eject_secs),
+            )?,
+            limiter: RateLimiter::new(rps),
+            clients: Mutex::new(HashMap::new()),
+            config,
+        })
+    }
+
+    pub fn pool(&self) -> &RpcPool {
+        &self.pool
+    }
+
+    pub fn client(&self, rpc_url: &str) -> Arc<RpcClient> {
+        let mut clients = self.clients.lock().unwrap();
+        clients
+            .entry(rpc_url.to_string())
+            .or_insert_with(|| {
+                Arc::new(RpcClient::new_with_timeout_and_commitment(
+                    rpc_url.to_string(),
+                    Duration::from_secs(self.config.timeout),
+                    CommitmentConfig {
+                        commitment: self.config.commitment,
+                    },
+                ))
+            })
+            .clone()
+    }
+
+    // signatures and transactions are not served at processed, confirmed is the closest
+    fn history_commitment(&self) -> CommitmentConfig {
+        match self.config.commitment {
+            CommitmentLevel::Processed => CommitmentConfig::confirmed(),
+            commitment => CommitmentConfig { commitment },
+        }
+    }
+
+    // backoff_ms * 2^(attempt-1), the upper half is random so workers do not retry in lockstep
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .config
+            .backoff_ms
+            .saturating_mul(1 << (attempt - 1).min(16));
+        let jitter = rand::random::<u64>() % (delay / 2 + 1);
+        Duration::from_millis(delay / 2 + jitter)
+    }
+
+    // a retry asks the pool again, so it lands on another endpoint once the failing one is ejected
+    async fn with_retry<T, F, Fut>(&self, call: F) -> Result<T>
+    where
+        F: Fn(Arc<RpcClient>) -> Fut,
+        Fut: Future<Output = Result<T, ClientError>>,
+    {
+        let mut attempt = 0;
+        loop {
+            let rpc_url = self.pool.pick();
+            self.limiter.acquire(&rpc_url).await;
+            let start = Instant::now();
+            let result = call(self.client(&rpc_url)).await;
+            self.pool.report(
+                &rpc_url,
+                match &result {
+                    Err(e) if is_rate_limited(e) => Outcome::RateLimited,
+                    Err(e) if is_retryable(e) => Outcome::Error,
+                    // the endpoint answered, even if it was an error about our input
+                    _ => Outcome::Ok(start.elapsed()),
+                },
+            );
+            match result {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt < self.config.max_retries && is_retryable(&e) => {
+                    attempt += 1;
+                    let delay = self.backoff(attempt);
+                    warn!(
+                        "rpc error: {:?}, url: {}, retry {} in {:?}",
+                        e, rpc_url, attempt, delay
+                    );
+                    tokio::time::sleep(delay).await;
                 }
-                accounts.push(TokenAccount {
-                    pubkey: token_account.pubkey.to_string(),
-                    mint: parsed.info.mint,
-                    amount: parsed.info.token_amount.amount,
-                    ui_amount: parsed.info.token_amount.ui_amount,
-                });
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    // rpc https://solana.com/docs/rpc/http/gettokenaccountsbyowner
+    // spl token and token-2022 keep their accounts under different programs, so both are asked.
+    // mints the account holds less of than the dust threshold end up in skipped
+    pub async fn get_tokens_with_account(
+        &self,
+        account: &str,
+        dust: &DustConfig,
+    ) -> Result<Holdings> {
+        let account_pubkey = Pubkey::from_str(account)?;
+
+        let mut holdings = Holdings {
+            slot: u64::MAX,
+            ..Default::default()
+        };
+        for program in [spl_token::id(), spl_token_2022::id()] {
+            let response = self
+                .with_retry(|client| async move {
+                    client
+                        .get_token_accounts_by_owner_with_commitment(
+                            &account_pubkey,
+                            TokenAccountsFilter::ProgramId(program),
+                            client.commitment(),
+                        )
+                        .await
+                })
+                .await?;
+            // the older of the two answers, both lists are at least that fresh
+            holdings.slot = holdings.slot.min(response.context.slot);
+
+            for token_account in response.value {
+                let account_data = token_account.account.data;
+                match account_data {
+                    UiAccountData::Json(parsed_account) => {
+                        let parsed: Parsed = serde_json::from_value(parsed_account.parsed)?;
+                        holdings.accounts.push(TokenAccount {
+                            pubkey: token_account.pubkey.to_string(),
+                            mint: parsed.info.mint,
+                            amount: parsed.info.token_amount.amount,
+                            decimals: parsed.info.token_amount.decimals,
+                            ui_amount: parsed.info.token_amount.ui_amount,
+                            program: program.to_string(),
+                        });
+                    }
+                    UiAccountData::LegacyBinary(_) | UiAccountData::Binary(_, _) => {
+                        continue;
+                    }
+                }
+            }
+        }
+
+        holdings.skip_dust(account, dust);
+        if !holdings.skipped.is_empty() {
+            debug!(
+                "skip {} dust token accounts, account: {}",
+                holdings.skipped.len(),
+                account
+            );
+        }
+        holdings.extensions = self.get_mint_extensions(&holdings.accounts).await?;
+        Ok(holdings)
+    }
+
+    // extensions live on the mint account, not on the token account, so the mints are fetched
+    async fn get_mint_extensions(
+        &self,
+        accounts: &[TokenAccount],
+    ) -> Result<BTreeMap<String, MintExtensions>> {
+        let token_2022 = spl_token_2022::id().to_string();
+        let mut mints: Vec<Pubkey> = vec![];
+        for account in accounts.iter().filter(|a| a.program == token_2022) {
+            let mint = Pubkey::from_str(&account.mint)?;
+            if !mints.contains(&mint) {
+                mints.push(mint);
             }
-            UiAccountData::LegacyBinary(_) | UiAccountData::Binary(_, _) => {
+        }
+
+        let mut extensions = BTreeMap::new();
+        let found = self.get_multiple_accounts(&mints).await?;
+        for (mint, account) in mints.iter().zip(found) {
+            let Some(account) = account else {
+                continue;
+            };
+            match MintExtensions::from_mint_data(&account.data) {
+                Ok(mint_extensions) => {
+                    extensions.insert(mint.to_string(), mint_extensions);
+                }
+                Err(e) => warn!("parse mint extensions error: {:?}, mint: {}", e, mint),
+            }
+        }
+        Ok(extensions)
+    }
+
+    // one entry per key, none for accounts that do not exist
+    pub async fn get_multiple_accounts(&self, keys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        Ok(self.get_multiple_accounts_with_slot(keys).await?.1)
+    }
+
+    // rpc https://solana.com/docs/rpc/http/getmultipleaccounts
+    // also returns the slot of the oldest answer, every account is at least that fresh
+    pub async fn get_multiple_accounts_with_slot(
+        &self,
+        keys: &[Pubkey],
+    ) -> Result<(u64, Vec<Option<Account>>)> {
+        let mut slot = u64::MAX;
+        let mut accounts = Vec::with_capacity(keys.len());
+        // getMultipleAccounts takes at most 100 keys
+        for chunk in keys.chunks(MULTIPLE_ACCOUNTS_LIMIT) {
+            let response = self
+                .with_retry(|client| async move {
+                    client
+                        .get_multiple_accounts_with_commitment(chunk, client.commitment())
+                        .await
+                })
+                .await?;
+            slot = slot.min(response.context.slot);
+            accounts.extend(response.value);
+        }
+        Ok((slot, accounts))
+    }
+
+    // the owner of each token account, and the program owning that owner account
+    // (a pool state or bonding curve), none when the owner has no account of its own
+    pub async fn get_token_account_owners(
+        &self,
+        addresses: &[String],
+    ) -> Result<HashMap<String, TokenAccountOwner>> {
+        let keys = addresses
+            .iter()
+            .map(|address| Pubkey::from_str(address))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut owners = vec![];
+        for (address, account) in addresses
+            .iter()
+            .zip(self.get_multiple_accounts(&keys).await?)
+        {
+            let Some(account) = account else {
                 continue;
+            };
+            match StateWithExtensions::<TokenAccountState>::unpack(&account.data) {
+                Ok(state) => owners.push((address.clone(), state.base.owner)),
+                Err(e) => warn!("parse token account error: {:?}, address: {}", e, address),
             }
         }
+
+        let owner_keys: Vec<Pubkey> = owners.iter().map(|(_, owner)| *owner).collect();
+        let owner_accounts = self.get_multiple_accounts(&owner_keys).await?;
+        Ok(owners
+            .into_iter()
+            .zip(owner_accounts)
+            .map(|((address, owner), account)| {
+                let owner = TokenAccountOwner {
+                    owner: owner.to_string(),
+                    owner_program: account.map(|a| a.owner.to_string()),
+                };
+                (address, owner)
+            })
+            .collect())
+    }
+
+    pub async fn get_token_largest_accounts(
+        &self,
+        token: &str,
+    ) -> Result<Vec<RpcTokenAccountBalance>> {
+        let token_pubkey = Pubkey::from_str(token)?;
+        self.with_retry(
+            |client| async move { client.get_token_largest_accounts(&token_pubkey).await },
+        )
+        .await
+    }
+
+    // decimals and metadata of the mints, from the mint accounts and the metaplex metadata
+    // accounts. mints that do not exist are left out
+    pub async fn get_token_metadata(&self, mints: &[String]) -> Result<Vec<Token>> {
+        let mint_keys = mints
+            .iter()
+            .map(|mint| Pubkey::from_str(mint))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut keys = mint_keys.clone();
+        keys.extend(mint_keys.iter().map(metadata_pda));
+        let mut accounts = self.get_multiple_accounts(&keys).await?;
+        let metaplex = accounts.split_off(mints.len());
+
+        let mut tokens = vec![];
+        for ((mint, account), metaplex) in mints.iter().zip(accounts).zip(metaplex) {
+            let Some(account) = account else {
+                continue;
+            };
+            match token_from_accounts(mint, &account, metaplex.as_ref()) {
+                Ok(token) => tokens.push(token),
+                Err(e) => warn!("parse token metadata error: {:?}, mint: {}", e, mint),
+            }
+        }
+        Ok(tokens)
+    }
+
+    // rpc https://solana.com/docs/rpc/http/getsignaturesforaddress
+    // newest first, older than `before` and newer than `until`
+    pub async fn get_signatures(
+        &self,
+        account: &str,
+        before: Option<&str>,
+        until: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+        let account_pubkey = Pubkey::from_str(account)?;
+        let before = before.map(Signature::from_str).transpose()?;
+        let until = until.map(Signature::from_str).transpose()?;
+        let commitment = self.history_commitment();
+        self.with_retry(|client| async move {
+            client
+                .get_signatures_for_address_with_config(
+                    &account_pubkey,
+                    GetConfirmedSignaturesForAddress2Config {
+                        before,
+                        until,
+                        limit: Some(limit),
+                        commitment: Some(commitment),
+                    },
+                )
+                .await
+        })
+        .await
+    }
+
+    // the transaction with its pre/post token balances, versioned transactions included
+    pub async fn get_parsed_transaction(
+        &self,
+        signature: &str,
+    ) -> Result<EncodedConfirmedTransactionWithStatusMeta> {
+        let signature = Signature::from_str(signature)?;
+        let commitment = self.history_commitment();
+        self.with_retry(|client| async move {
+            client
+                .get_transaction_with_config(
+                    &signature,
+                    RpcTransactionConfig {
+                        encoding: Some(UiTransactionEncoding::JsonParsed),
+                        commitment: Some(commitment),
+                        max_supported_transaction_version: Some(0),
+                    },
+                )
+                .await
+        })
+        .await
+    }
+
+    // authorities, supply and risky extensions, read from the mint account
+    pub async fn get_token_safety(&self, token: &str) -> Result<TokenSafety> {
+        let token_pubkey = Pubkey::from_str(token)?;
+        match self
+            .get_multiple_accounts(&[token_pubkey])
+            .await?
+            .pop()
+            .flatten()
+        {
+            Some(account) => TokenSafety::from_mint_account(token, &account),
+            None => Err(anyhow!("mint {} not found", token)),
+        }
+    }
+
+    pub async fn get_token_supply(&self, token: &str) -> Result<UiTokenAmount> {
+        let token_pubkey = Pubkey::from_str(token)?;
+        self.with_retry(|client| async move { client.get_token_supply(&token_pubkey).await })
+            .await
     }
-    Ok(accounts)
 }
 
-pub async fn get_token_largest_accounts(
-    token: &str,
-    rpc_url: &str,
-) -> Result<Vec<RpcTokenAccountBalance>> {
-    let client = RpcClient::new(rpc_url);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::State, routing::post, Json, Router};
+    use reqwest::StatusCode;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::net::TcpListener;
+
+    // a json-rpc stand-in that fails `failures` times before answering
+    async fn mock_rpc(failures: usize, status: StatusCode) -> (String, Arc<AtomicUsize>) {
+        type Hits = (Arc<AtomicUsize>, usize, StatusCode);
+        async fn handle(
+            State((hits, failures, status)): State<Hits>,
+            Json(request): Json<serde_json::Value>,
+        ) -> (StatusCode, Json<serde_json::Value>) {
+            if hits.fetch_add(1, Ordering::SeqCst) < failures {
+                return (status, Json(serde_json::json!({})));
+            }
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": request["id"],
+                    "result": {
+                        "context": {"slot": 7},
+                        "value": [{
+                            "address": Pubkey::new_unique().to_string(),
+                            "amount": "100",
+                            "decimals": 0,
+                            "uiAmount": 100.0,
+                            "uiAmountString": "100"
+                        }]
+                    }
+                })),
+            )
+        }
 
-    let token_pubkey = Pubkey::from_str_const(token);
-    let token_accounts = client.get_token_largest_accounts(&token_pubkey)?;
-    Ok(token_accounts)
+        let hits = Arc::new(AtomicUsize::new(0));
+        let app =
+            Router::new()
+                .route("/", post(handle))
+                .with_state((hits.clone(), failures, status));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, hits)
+    }
+
+    fn rpc(urls: &[&str], max_retries: u32) -> SolanaRpc {
+        SolanaRpc::new(
+            RpcConfig {
+                max_retries,
+                backoff_ms: 1,
+                eject_after: 1,
+                ..Default::default()
+            },
+            urls.iter().map(|url| (url.to_string(), 1)).collect(),
+            1000,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_retry_on_server_error() {
+        let (url, hits) = mock_rpc(2, StatusCode::BAD_GATEWAY).await;
+        let token = Pubkey::new_unique().to_string();
+
+        let accounts = rpc(&[&url], 3)
+            .get_token_largest_accounts(&token)
+            .await
+            .unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].amount.amount, "100");
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+
+        let (url, hits) = mock_rpc(5, StatusCode::SERVICE_UNAVAILABLE).await;
+        assert!(rpc(&[&url], 1)
+            .get_token_largest_accounts(&token)
+            .await
+            .is_err());
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failover_to_healthy_endpoint() {
+        let (bad, bad_hits) = mock_rpc(usize::MAX, StatusCode::SERVICE_UNAVAILABLE).await;
+        let (good, good_hits) = mock_rpc(0, StatusCode::OK).await;
+        let rpc = rpc(&[&bad, &good], 3);
+        let token = Pubkey::new_unique().to_string();
+
+        for _ in 0..5 {
+            rpc.get_token_largest_accounts(&token).await.unwrap();
+        }
+        // the failing endpoint is ejected after its first error and not asked again
+        assert!(bad_hits.load(Ordering::SeqCst) <= 1);
+        assert_eq!(good_hits.load(Ordering::SeqCst), 5);
+        let status = rpc.pool().status();
+        let bad_status = status.iter().find(|s| s.url == bad).unwrap();
+        assert!(!bad_status.healthy);
+        assert_eq!(bad_status.errors, bad_status.requests);
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_on_client_error() {
+        let (url, hits) = mock_rpc(5, StatusCode::BAD_REQUEST).await;
+        let token = Pubkey::new_unique().to_string();
+        assert!(rpc(&[&url], 3)
+            .get_token_largest_accounts(&token)
+            .await
+            .is_err());
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        // a bad address never reaches the rpc
+        assert!(rpc(&[&url], 3)
+            .get_token_largest_accounts("not a pubkey")
+            .await
+            .is_err());
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_skip_dust() {
+        let token = |mint: &str, ui_amount: f64| TokenAccount {
+            pubkey: Pubkey::new_unique().to_string(),
+            mint: mint.to_string(),
+            amount: (ui_amount as u64).to_string(),
+            decimals: 0,
+            ui_amount,
+            program: spl_token::id().to_string(),
+        };
+        let mut holdings = Holdings {
+            accounts: vec![
+                token("meme", 60000.0),
+                token("meme", 60000.0),
+                token("spam", 5.0),
+                token("sol", 2.0),
+            ],
+            ..Default::default()
+        };
+        let dust = DustConfig {
+            mints: [("sol".to_string(), 1.0)].into(),
+            ..Default::default()
+        };
+
+        // two token accounts of one mint count together
+        holdings.skip_dust("evil", &dust);
+        let kept: Vec<&str> = holdings.accounts.iter().map(|t| t.mint.as_str()).collect();
+        assert_eq!(kept, vec!["meme", "meme", "sol"]);
+        assert_eq!(holdings.skipped.len(), 1);
+        assert_eq!(holdings.skipped[0].mint, "spam");
+
+        let positions = holdings.positions().unwrap();
+        assert_eq!(positions["meme"].amount, 120000);
+        assert_eq!(positions["meme"].pubkey, holdings.accounts[0].pubkey);
+    }
+
+    #[test]
+    fn test_mint_extensions() {
+        use solana_sdk::program_pack::Pack;
+        use spl_token_2022::extension::{
+            BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut,
+        };
+
+        let delegate = Pubkey::new_unique();
+        let space = ExtensionType::try_calculate_account_len::<Mint>(&[
+            ExtensionType::TransferFeeConfig,
+            ExtensionType::PermanentDelegate,
+            ExtensionType::NonTransferable,
+        ])
+        .unwrap();
+        let mut data = vec![0; space];
+        let mut state = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        let fee = state.init_extension::<TransferFeeConfig>(true).unwrap();
+        fee.newer_transfer_fee.transfer_fee_basis_points = 250.into();
+        fee.newer_transfer_fee.maximum_fee = 1000.into();
+        state
+            .init_extension::<PermanentDelegate>(true)
+            .unwrap()
+            .delegate = Some(delegate).try_into().unwrap();
+        state.init_extension::<NonTransferable>(true).unwrap();
+        state.base = Mint {
+            decimals: 6,
+            is_initialized: true,
+            ..Default::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+
+        assert_eq!(
+            MintExtensions::from_mint_data(&data).unwrap(),
+            MintExtensions {
+                transfer_fee_bps: Some(250),
+                transfer_fee_max: Some(1000),
+                permanent_delegate: Some(delegate.to_string()),
+                non_transferable: true,
+            }
+        );
+
+        // a plain mint has nothing to report
+        let mut data = vec![0; Mint::LEN];
+        Mint::pack(
+            Mint {
+                decimals: 6,
+                is_initialized: true,
+                ..Default::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+        assert_eq!(
+            MintExtensions::from_mint_data(&data).unwrap(),
+            MintExtensions::default()
+        );
+    }
+
+    #[test]
+    fn test_token_account_refreshed() {
+        use solana_sdk::program_pack::Pack;
+        use spl_token_2022::state::AccountState;
+
+        let owner = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let account = |owner: Pubkey, mint: Pubkey, amount: u64| {
+            let mut data = vec![0; TokenAccountState::LEN];
+            TokenAccountState::pack(
+                TokenAccountState {
+                    mint,
+                    owner,
+                    amount,
+                    state: AccountState::Initialized,
+                    ..Default::default()
+                },
+                &mut data,
+            )
+            .unwrap();
+            Account {
+                lamports: 2039280,
+                data,
+                owner: spl_token::id(),
+                executable: false,
+                rent_epoch: 0,
+            }
+        };
+        let known = TokenAccount {
+            pubkey: Pubkey::new_unique().to_string(),
+            mint: mint.to_string(),
+            amount: "1000".to_string(),
+            decimals: 3,
+            ui_amount: 1.0,
+            program: spl_token::id().to_string(),
+        };
+
+        let refreshed = known
+            .refreshed(&owner, Some(&account(owner, mint, 2500)))
+            .unwrap();
+        assert_eq!(refreshed.amount, "2500");
+        assert_eq!(refreshed.ui_amount, 2.5);
+        assert_eq!(refreshed.pubkey, known.pubkey);
+
+        // closed, handed to someone else, reopened for another mint
+        assert!(known.refreshed(&owner, None).is_none());
+        let other = Pubkey::new_unique();
+        assert!(known
+            .refreshed(&owner, Some(&account(other, mint, 1)))
+            .is_none());
+        assert!(known
+            .refreshed(&owner, Some(&account(owner, other, 1)))
+            .is_none());
+    }
+
+    #[test]
+    fn test_client_is_shared_and_backoff_grows() {
+        let rpc = SolanaRpc::new(
+            RpcConfig::default(),
+            vec![("http://a.com".to_string(), 1)],
+            10,
+        )
+        .unwrap();
+        assert!(Arc::ptr_eq(
+            &rpc.client("http://a.com"),
+            &rpc.client("http://a.com")
+        ));
+        assert!(!Arc::ptr_eq(
+            &rpc.client("http://a.com"),
+            &rpc.client("http://b.com")
+        ));
+
+        for attempt in 1..5 {
+            let base = 500u64 << (attempt - 1);
+            let delay = rpc.backoff(attempt).as_millis() as u64;
+            assert!(delay >= base / 2 && delay <= base, "{} {}", attempt, delay);
+        }
+    }
 }
@@ -0,0 +1,192 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::Deserialize;
+use tokio::{
+    sync::{mpsc::UnboundedSender, watch},
+    time::{sleep, Instant},
+};
+
+use crate::{
+    config::{Config, DustConfig},
+    grpc::GrpcStream,
+    repository::SharedRepository,
+    solana_rpc::{get_global_solana_rpc, Holdings},
+    stream::PubsubStream,
+};
+
+// how the daemon learns that the holdings of a tracked wallet changed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HoldingsSourceKind {
+    #[default]
+    Polling, // every solana_rpc_curl_interval seconds
+    Pubsub, // solana pubsub websocket
+    Grpc,   // yellowstone geyser grpc
+}
+
+#[async_trait]
+pub trait HoldingsSource: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // runs for the life of the daemon and sends every wallet whose holdings may have changed.
+    // connected is true while changes arrive, polling is only a safety net then
+    async fn watch(&self, wallets: UnboundedSender<String>, connected: watch::Sender<bool>);
+
+    // what a wallet holds now, the streams only say when to look
+    async fn holdings(&self, account: &str, dust: &DustConfig) -> Result<Holdings> {
+        get_global_solana_rpc()
+            .await
+            .get_tokens_with_account(account, dust)
+            .await
+    }
+}
+
+pub type SharedHoldingsSource = Arc<dyn HoldingsSource>;
+
+// nothing but the polling passes of the daemon
+pub struct PollingSource;
+
+#[async_trait]
+impl HoldingsSource for PollingSource {
+    fn name(&self) -> &'static str {
+        "polling"
+    }
+
+    async fn watch(&self, _wallets: UnboundedSender<String>, _connected: watch::Sender<bool>) {
+        std::future::pending::<()>().await
+    }
+}
+
+pub fn holdings_source(c: &Config, manager: SharedRepository) -> Result<SharedHoldingsSource> {
+    Ok(match c.stream.source {
+        HoldingsSourceKind::Polling => Arc::new(PollingSource),
+        HoldingsSourceKind::Pubsub => Arc::new(PubsubStream::new(c, manager)),
+        HoldingsSourceKind::Grpc => Arc::new(GrpcStream::new(c, manager)?),
+    })
+}
+
+// reconnect delay of the streams, doubles up to max and starts over after a healthy connection
+pub struct Backoff {
+    initial: Duration,
+    current: Duration,
+    max: Duration,
+}
+
+impl Backoff {
+    pub fn new(max: Duration) -> Self {
+        let initial = max.min(Duration::from_secs(1));
+        Self {
+            initial,
+            current: initial,
+            max,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.current = self.initial;
+    }
+
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.current;
+        self.current = (self.current * 2).min(self.max);
+        delay
+    }
+}
+
+// wait until the next polling pass is due. while a stream is connected polling is only a
+// safety net at poll_interval, when it drops the regular interval applies right away
+pub async fn wait_next_pass(start: Instant, c: &Config, connected: &mut watch::Receiver<bool>) {
+    loop {
+        let interval = if *connected.borrow() {
+            c.stream.poll_interval
+        } else {
+            c.solana_rpc_curl_interval
+        };
+        let remaining = Duration::from_secs(interval).saturating_sub(start.elapsed());
+        if remaining.is_zero() {
+            return;
+        }
+        tokio::select! {
+            _ = sleep(remaining) => return,
+            changed = connected.changed() => {
+                // the source is gone, nothing will change
+                if changed.is_err() {
+                    sleep(remaining).await;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::MemoryRepository;
+
+    fn config(source: &str) -> Config {
+        format!(
+            r#"
+            database_url="sqlite::memory:"
+            host_uri="127.0.0.1:2211"
+            solana_rpc_url="http://127.0.0.1:8899"
+            solana_rpc_curl_interval=1
+            check_largest_account_hold_coin=100000000.0
+
+            [stream]
+            source="{}"
+            "#,
+            source
+        )
+        .parse()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_holdings_source() {
+        let manager: SharedRepository = Arc::new(MemoryRepository::new());
+        for (source, name) in [("polling", "polling"), ("pubsub", "pubsub")] {
+            let source = holdings_source(&config(source), manager.clone()).unwrap();
+            assert_eq!(source.name(), name);
+        }
+        // grpc has no default endpoint
+        assert!(holdings_source(&config("grpc"), manager.clone()).is_err());
+    }
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::new(Duration::from_secs(5));
+        let delays: Vec<u64> = (0..5).map(|_| backoff.next_delay().as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 5, 5]);
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+        assert_eq!(
+            Backoff::new(Duration::from_millis(20)).next_delay(),
+            Duration::from_millis(20)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wait_next_pass() {
+        let c = config("pubsub");
+        let (connected, mut status) = watch::channel(false);
+        let start = Instant::now();
+
+        // connected, the safety net interval is far away, a drop ends the wait early
+        connected.send_replace(true);
+        let wait = tokio::spawn(async move {
+            wait_next_pass(start, &c, &mut status).await;
+            start.elapsed()
+        });
+        sleep(Duration::from_millis(100)).await;
+        assert!(!wait.is_finished());
+        connected.send_replace(false);
+        let elapsed = tokio::time::timeout(Duration::from_secs(5), wait)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(elapsed >= Duration::from_secs(1) && elapsed < Duration::from_secs(3));
+    }
+}
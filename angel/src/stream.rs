@@ -0,0 +1,379 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use solana_sdk::commitment_config::CommitmentLevel;
+use tokio::{
+    sync::{mpsc::UnboundedSender, watch},
+    time::{interval, sleep},
+};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::{error, info, warn};
+
+use crate::{
+    config::Config,
+    repository::SharedRepository,
+    source::{Backoff, HoldingsSource},
+};
+
+// a subscription and the tracked wallet it belongs to
+#[derive(Debug, Clone, PartialEq)]
+struct Subscription {
+    wallet: String,
+    unsubscribe: &'static str, // method that ends it
+}
+
+// solana pubsub over a websocket. every tracked wallet gets a programSubscribe per token
+// program on the accounts it owns, plus an accountSubscribe on itself because closing a token
+// account sends no program notification but refunds the rent to the wallet. a notification
+// only names the wallet, the daemon reads its holdings again
+pub struct PubsubStream {
+    url: String,
+    commitment: CommitmentLevel,
+    manager: SharedRepository,
+    account_refresh: Duration,
+    max_backoff: Duration,
+}
+
+impl PubsubStream {
+    pub fn new(c: &Config, manager: SharedRepository) -> Self {
+        Self {
+            url: c.stream_ws_url(),
+            commitment: c.rpc.commitment,
+            manager,
+            account_refresh: Duration::from_secs(c.stream.account_refresh),
+            max_backoff: Duration::from_secs(c.stream.max_backoff),
+        }
+    }
+
+    async fn connection(
+        &self,
+        wallets: &UnboundedSender<String>,
+        connected: &watch::Sender<bool>,
+        confirmed: &mut bool,
+    ) -> Result<()> {
+        let (socket, _) = connect_async(self.url.as_str()).await?;
+        let (mut write, mut read) = socket.split();
+        info!("pubsub stream connected: {}", self.url);
+
+        let mut next_id: u64 = 0;
+        let mut pending: HashMap<u64, Subscription> = HashMap::new();
+        let mut subscriptions: HashMap<u64, Subscription> = HashMap::new();
+        let mut subscribed: HashSet<String> = HashSet::new();
+        // the first tick subscribes every account, the later ones pick up added and removed
+        // accounts and keep idle connections open
+        let mut refresh = interval(self.account_refresh);
+        loop {
+            tokio::select! {
+                _ = refresh.tick() => {
+                    let accounts: HashSet<String> = self
+                        .manager
+                        .get_all_accounts()
+                        .await?
+                        .into_iter()
+                        .map(|account| account.account)
+                        .collect();
+                    for wallet in accounts.difference(&subscribed) {
+                        for (id, request, subscription) in self.subscribe(wallet, &mut next_id) {
+                            write.send(Message::Text(request.to_string())).await?;
+                            pending.insert(id, subscription);
+                        }
+                    }
+                    let removed: Vec<u64> = subscriptions
+                        .iter()
+                        .filter(|(_, s)| !accounts.contains(&s.wallet))
+                        .map(|(id, _)| *id)
+                        .collect();
+                    for subscription_id in removed {
+                        let subscription = subscriptions.remove(&subscription_id).unwrap();
+                        next_id += 1;
+                        let request = json!({
+                            "jsonrpc": "2.0",
+                            "id": next_id,
+                            "method": subscription.unsubscribe,
+                            "params": [subscription_id],
+                        });
+                        write.send(Message::Text(request.to_string())).await?;
+                    }
+                    subscribed = accounts;
+                    if pending.is_empty() {
+                        connected.send_replace(true);
+                    }
+                    write.send(Message::Ping(vec![])).await?;
+                }
+                message = read.next() => {
+                    let text = match message {
+                        None | Some(Ok(Message::Close(_))) => return Ok(()),
+                        Some(Err(e)) => return Err(e.into()),
+                        Some(Ok(Message::Text(text))) => text,
+                        Some(Ok(_)) => continue,
+                    };
+                    let message: Value = serde_json::from_str(&text)?;
+                    if let Some(id) = message["id"].as_u64() {
+                        let Some(subscription) = pending.remove(&id) else {
+                            continue;
+                        };
+                        match message["result"].as_u64() {
+                            Some(subscription_id) => {
+                                subscriptions.insert(subscription_id, subscription);
+                                *confirmed = true;
+                            }
+                            None => bail!(
+                                "subscribe error: {}, wallet: {}",
+                                message["error"], subscription.wallet
+                            ),
+                        }
+                        if pending.is_empty() {
+                            connected.send_replace(true);
+                        }
+                    } else if let Some(subscription_id) =
+                        message["params"]["subscription"].as_u64()
+                    {
+                        if let Some(subscription) = subscriptions.get(&subscription_id) {
+                            wallets.send(subscription.wallet.clone())?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // (request id, request, subscription) for the wallet itself and its token accounts
+    fn subscribe(&self, wallet: &str, next_id: &mut u64) -> Vec<(u64, Value, Subscription)> {
+        let mut requests = vec![];
+        let mut request = |method: &str, params: Value, unsubscribe: &'static str| {
+            *next_id += 1;
+            requests.push((
+                *next_id,
+                json!({"jsonrpc": "2.0", "id": *next_id, "method": method, "params": params}),
+                Subscription {
+                    wallet: wallet.to_string(),
+                    unsubscribe,
+                },
+            ));
+        };
+        request(
+            "accountSubscribe",
+            json!([wallet, {"encoding": "base64", "commitment": self.commitment}]),
+            "accountUnsubscribe",
+        );
+        // the owner of a token account is at offset 32, for both token programs
+        for program in [spl_token::id(), spl_token_2022::id()] {
+            request(
+                "programSubscribe",
+                json!([
+                    program.to_string(),
+                    {
+                        "encoding": "base64",
+                        "commitment": self.commitment,
+                        "filters": [{"memcmp": {"offset": 32, "bytes": wallet}}]
+                    }
+                ]),
+                "programUnsubscribe",
+            );
+        }
+        requests
+    }
+}
+
+#[async_trait]
+impl HoldingsSource for PubsubStream {
+    fn name(&self) -> &'static str {
+        "pubsub"
+    }
+
+    // reconnects forever, the backoff starts over once a connection confirmed a subscription
+    async fn watch(&self, wallets: UnboundedSender<String>, connected: watch::Sender<bool>) {
+        info!("pubsub stream start, url: {}", self.url);
+        let mut backoff = Backoff::new(self.max_backoff);
+        loop {
+            let mut confirmed = false;
+            match self.connection(&wallets, &connected, &mut confirmed).await {
+                Ok(()) => warn!("pubsub stream closed by the server"),
+                Err(e) => error!("pubsub stream error: {:?}", e),
+            }
+            connected.send_replace(false);
+            if confirmed {
+                backoff.reset();
+            }
+            let delay = backoff.next_delay();
+            warn!("pubsub stream reconnect in {:?}, polling meanwhile", delay);
+            sleep(delay).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::MemoryRepository;
+    use std::sync::{Arc, Mutex};
+    use tokio::{net::TcpListener, sync::mpsc};
+    use tokio_tungstenite::accept_async;
+
+    const WALLET: &str = "9xHxgDbeQDX51Vof7ruAaYjSYgR87BXRp3ZC62jrmJV1";
+
+    // answers every request, and every program subscribe with a notification. with drop_first
+    // the first connection is dropped right after its first notification
+    async fn mock_pubsub(requests: Arc<Mutex<Vec<(usize, Value)>>>, drop_first: bool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            for connection in 0.. {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut socket = accept_async(stream).await.unwrap();
+                let requests = requests.clone();
+                tokio::spawn(async move {
+                    let mut subscription = 100 * connection as u64;
+                    while let Some(Ok(message)) = socket.next().await {
+                        let Message::Text(text) = message else {
+                            continue;
+                        };
+                        let request: Value = serde_json::from_str(&text).unwrap();
+                        requests.lock().unwrap().push((connection, request.clone()));
+                        subscription += 1;
+                        let response =
+                            json!({"jsonrpc": "2.0", "result": subscription, "id": request["id"]});
+                        socket
+                            .send(Message::Text(response.to_string()))
+                            .await
+                            .unwrap();
+                        if request["method"] == "programSubscribe" {
+                            let notification = json!({
+                                "jsonrpc": "2.0",
+                                "method": "programNotification",
+                                "params": {"result": {}, "subscription": subscription}
+                            });
+                            socket
+                                .send(Message::Text(notification.to_string()))
+                                .await
+                                .unwrap();
+                            if drop_first && connection == 0 {
+                                socket.close(None).await.unwrap();
+                                return;
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_stream_resubscribes_after_drop() {
+        let requests = Arc::new(Mutex::new(vec![]));
+        let url = mock_pubsub(requests.clone(), true).await;
+        let manager: SharedRepository = Arc::new(MemoryRepository::new());
+        manager.add_new_account(WALLET).await.unwrap();
+        let (connected, mut status) = watch::channel(false);
+        let stream = PubsubStream {
+            url,
+            commitment: CommitmentLevel::Confirmed,
+            manager,
+            account_refresh: Duration::from_secs(60),
+            max_backoff: Duration::from_millis(20),
+        };
+        let (sender, mut wallets) = mpsc::unbounded_channel();
+        tokio::spawn(async move { stream.watch(sender, connected).await });
+
+        // one notification before the drop, one after the reconnect
+        for _ in 0..2 {
+            let wallet = tokio::time::timeout(Duration::from_secs(5), wallets.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(wallet, WALLET);
+        }
+        let requests = requests.lock().unwrap().clone();
+        let second: Vec<&Value> = requests
+            .iter()
+            .filter(|(connection, _)| *connection == 1)
+            .map(|(_, request)| request)
+            .collect();
+        assert_eq!(second.len(), 3);
+        assert_eq!(second[0]["method"], "accountSubscribe");
+        assert_eq!(second[0]["params"][0], WALLET);
+        assert_eq!(second[1]["method"], "programSubscribe");
+        assert_eq!(
+            second[1]["params"][0],
+            "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"
+        );
+        assert_eq!(
+            second[1]["params"][1]["filters"][0]["memcmp"]["bytes"],
+            WALLET
+        );
+        assert_eq!(second[1]["params"][1]["commitment"], "confirmed");
+        assert_eq!(
+            second[2]["params"][0],
+            "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb"
+        );
+
+        // every subscription of the second connection was confirmed
+        tokio::time::timeout(Duration::from_secs(5), status.wait_for(|c| *c))
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stream_unsubscribes_deleted_account() {
+        let requests = Arc::new(Mutex::new(vec![]));
+        let url = mock_pubsub(requests.clone(), false).await;
+        let manager: SharedRepository = Arc::new(MemoryRepository::new());
+        manager.add_new_account(WALLET).await.unwrap();
+        let (connected, _status) = watch::channel(false);
+        let stream = PubsubStream {
+            url,
+            commitment: CommitmentLevel::Confirmed,
+            manager: manager.clone(),
+            account_refresh: Duration::from_millis(50),
+            max_backoff: Duration::from_millis(20),
+        };
+        let (sender, mut wallets) = mpsc::unbounded_channel();
+        tokio::spawn(async move { stream.watch(sender, connected).await });
+        tokio::time::timeout(Duration::from_secs(5), wallets.recv())
+            .await
+            .unwrap()
+            .unwrap();
+
+        // the next refresh ends every subscription of the wallet
+        manager.delete_account(WALLET).await.unwrap();
+        let unsubscribed = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let mut unsubscribed: Vec<(String, u64)> = requests
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter(|(_, r)| r["method"].as_str().unwrap().ends_with("Unsubscribe"))
+                    .map(|(_, r)| {
+                        (
+                            r["method"].as_str().unwrap().to_string(),
+                            r["params"][0].as_u64().unwrap(),
+                        )
+                    })
+                    .collect();
+                if unsubscribed.len() == 3 {
+                    unsubscribed.sort_by_key(|(_, id)| *id);
+                    return unsubscribed;
+                }
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            unsubscribed,
+            vec![
+                ("accountUnsubscribe".to_string(), 1),
+                ("programUnsubscribe".to_string(), 2),
+                ("programUnsubscribe".to_string(), 3),
+            ]
+        );
+    }
+}
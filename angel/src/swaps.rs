@@ -0,0 +1,647 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::Serialize;
+use solana_transaction_status_client_types::{
+    option_serializer::OptionSerializer, EncodedTransaction, EncodedTransactionWithStatusMeta,
+    UiInstruction, UiMessage, UiParsedInstruction, UiTransactionStatusMeta,
+    UiTransactionTokenBalance,
+};
+
+use crate::models::{BalanceDelta, NewSwap, Swap};
+
+// sol is stored as wrapped sol so both ways of paying with it look the same
+pub const WSOL_MINT: &str = "So11111111111111111111111111111111111111112";
+
+// mints a price is expressed in
+const QUOTE_MINTS: &[&str] = &[
+    WSOL_MINT,
+    "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v", // usdc
+    "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BnEwNYb", // usdt
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dex {
+    Raydium,
+    Orca,
+    Meteora,
+    Jupiter,
+    PumpFun,
+}
+
+impl Dex {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Dex::Raydium => "raydium",
+            Dex::Orca => "orca",
+            Dex::Meteora => "meteora",
+            Dex::Jupiter => "jupiter",
+            Dex::PumpFun => "pump_fun",
+        }
+    }
+}
+
+// stored as text, see as_str
+impl TryFrom<String> for Dex {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        match value.as_str() {
+            "raydium" => Ok(Dex::Raydium),
+            "orca" => Ok(Dex::Orca),
+            "meteora" => Ok(Dex::Meteora),
+            "jupiter" => Ok(Dex::Jupiter),
+            "pump_fun" => Ok(Dex::PumpFun),
+            _ => Err(anyhow::anyhow!("unknown dex: {}", value)),
+        }
+    }
+}
+
+// buy and sell are against a quote mint, swap is anything else
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SwapSide {
+    Buy,
+    Sell,
+    Swap,
+}
+
+impl SwapSide {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SwapSide::Buy => "buy",
+            SwapSide::Sell => "sell",
+            SwapSide::Swap => "swap",
+        }
+    }
+}
+
+// stored as text, see as_str
+impl TryFrom<String> for SwapSide {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        match value.as_str() {
+            "buy" => Ok(SwapSide::Buy),
+            "sell" => Ok(SwapSide::Sell),
+            "swap" => Ok(SwapSide::Swap),
+            _ => Err(anyhow::anyhow!("unknown swap side: {}", value)),
+        }
+    }
+}
+
+const PROGRAMS: &[(&str, Dex)] = &[
+    ("675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8", Dex::Raydium), // amm v4
+    ("CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C", Dex::Raydium), // cpmm
+    ("CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK", Dex::Raydium), // clmm
+    ("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc", Dex::Orca),     // whirlpool
+    ("9W959DqEETiGZocYWCQPaJ6sBmUzgfxXfqGeTEdp3aQP", Dex::Orca),    // token swap v2
+    ("LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo", Dex::Meteora),  // dlmm
+    ("Eo7WjKq67rjJQSZxS6z3YkapzY3eMj6Xy8X5EQVn5UaB", Dex::Meteora), // pools
+    ("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4", Dex::Jupiter),  // v6
+    ("JUP4Fb2cqiRUcaTHdrPC8h2gNsA2ETXiPDD33WcGuJB", Dex::Jupiter),  // v4
+    ("6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P", Dex::PumpFun),  // bonding curve
+    ("pAMMBay6oceH9fJKBRHGP5D4bD4sWpmSwMn52FMfXEA", Dex::PumpFun),  // pump swap amm
+];
+
+// the dex the transaction traded on and its program, jupiter wins because it routes
+// through the others
+pub fn find_dex(program_ids: &[String]) -> Option<(Dex, String)> {
+    let known: Vec<(Dex, &String)> = program_ids
+        .iter()
+        .filter_map(|id| {
+            PROGRAMS
+                .iter()
+                .find(|(program, _)| program == id)
+                .map(|(_, dex)| (*dex, id))
+        })
+        .collect();
+    known
+        .iter()
+        .find(|(dex, _)| *dex == Dex::Jupiter)
+        .or(known.first())
+        .map(|(dex, id)| (*dex, id.to_string()))
+}
+
+// static keys followed by the ones loaded from lookup tables, what compiled instructions index
+fn account_keys(transaction: &EncodedTransactionWithStatusMeta) -> Vec<String> {
+    let EncodedTransaction::Json(ui) = &transaction.transaction else {
+        return vec![];
+    };
+    match &ui.message {
+        UiMessage::Parsed(message) => message
+            .account_keys
+            .iter()
+            .map(|key| key.pubkey.clone())
+            .collect(),
+        UiMessage::Raw(message) => {
+            let mut keys = message.account_keys.clone();
+            if let Some(OptionSerializer::Some(loaded)) =
+                transaction.meta.as_ref().map(|meta| &meta.loaded_addresses)
+            {
+                keys.extend(loaded.writable.iter().cloned());
+                keys.extend(loaded.readonly.iter().cloned());
+            }
+            keys
+        }
+    }
+}
+
+// programs of the outer and inner instructions, in order
+fn program_ids(transaction: &EncodedTransactionWithStatusMeta, keys: &[String]) -> Vec<String> {
+    let program_id = |instruction: &UiInstruction| match instruction {
+        UiInstruction::Compiled(compiled) => keys.get(compiled.program_id_index as usize).cloned(),
+        UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed)) => {
+            Some(parsed.program_id.clone())
+        }
+        UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(decoded)) => {
+            Some(decoded.program_id.clone())
+        }
+    };
+
+    let mut ids = vec![];
+    if let EncodedTransaction::Json(ui) = &transaction.transaction {
+        match &ui.message {
+            UiMessage::Parsed(message) => {
+                ids.extend(message.instructions.iter().filter_map(program_id))
+            }
+            UiMessage::Raw(message) => ids.extend(
+                message
+                    .instructions
+                    .iter()
+                    .filter_map(|compiled| keys.get(compiled.program_id_index as usize).cloned()),
+            ),
+        }
+    }
+    if let Some(OptionSerializer::Some(inner)) = transaction
+        .meta
+        .as_ref()
+        .map(|meta| &meta.inner_instructions)
+    {
+        for instructions in inner {
+            ids.extend(instructions.instructions.iter().filter_map(program_id));
+        }
+    }
+    ids
+}
+
+// lamports of token accounts the account opened minus the ones it closed, rent that is not
+// part of the price. wrapped sol accounts also hold the wrapped amount, which is counted
+// with the token balances
+fn token_account_rent(account: &str, meta: &UiTransactionStatusMeta) -> i128 {
+    let owned = |balances: &OptionSerializer<Vec<UiTransactionTokenBalance>>| {
+        let OptionSerializer::Some(balances) = balances else {
+            return HashMap::new();
+        };
+        balances
+            .iter()
+            .filter(|b| matches!(&b.owner, OptionSerializer::Some(owner) if owner == account))
+            .map(|b| (b.account_index as usize, b.clone()))
+            .collect::<HashMap<usize, UiTransactionTokenBalance>>()
+    };
+    let rent = |lamports: Option<&u64>, balance: &UiTransactionTokenBalance| {
+        let wrapped = if balance.mint == WSOL_MINT {
+            balance.ui_token_amount.amount.parse::<u64>().unwrap_or(0)
+        } else {
+            0
+        };
+        lamports.map_or(0, |lamports| *lamports as i128 - wrapped as i128)
+    };
+
+    let pre = owned(&meta.pre_token_balances);
+    let post = owned(&meta.post_token_balances);
+    let opened: i128 = post
+        .iter()
+        .filter(|(index, _)| !pre.contains_key(index))
+        .map(|(index, balance)| rent(meta.post_balances.get(*index), balance))
+        .sum();
+    let closed: i128 = pre
+        .iter()
+        .filter(|(index, _)| !post.contains_key(index))
+        .map(|(index, balance)| rent(meta.pre_balances.get(*index), balance))
+        .sum();
+    opened - closed
+}
+
+// one side of a swap, the amount is what left or reached the account
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwapLeg {
+    pub mint: String,
+    pub amount: u64,
+    pub decimals: u8,
+}
+
+impl SwapLeg {
+    pub fn ui_amount(&self) -> f64 {
+        self.amount as f64 / 10f64.powi(self.decimals as i32)
+    }
+}
+
+// priced in the quote mint when exactly one side is one, otherwise in the input
+pub fn swap_from_legs(dex: Dex, program: &str, input: SwapLeg, output: SwapLeg) -> NewSwap {
+    let is_quote = |leg: &SwapLeg| QUOTE_MINTS.contains(&leg.mint.as_str());
+    let (side, base, quote) = match (is_quote(&input), is_quote(&output)) {
+        (false, true) => (SwapSide::Sell, &input, &output),
+        (true, false) => (SwapSide::Buy, &output, &input),
+        _ => (SwapSide::Swap, &output, &input),
+    };
+    NewSwap {
+        dex,
+        program: program.to_string(),
+        side,
+        base_mint: base.mint.clone(),
+        quote_mint: quote.mint.clone(),
+        price: quote.ui_amount() / base.ui_amount(),
+        input_mint: input.mint,
+        input_amount: input.amount,
+        input_decimals: input.decimals,
+        output_mint: output.mint,
+        output_amount: output.amount,
+        output_decimals: output.decimals,
+    }
+}
+
+// the swap the account made in a transaction that called a known dex, from what it paid and
+// what it got. sol is the native balance change without the fee and token account rent plus
+// any wrapped sol change. a multi hop route is one swap from the first input to the last output
+pub fn decode_swap(
+    account: &str,
+    transaction: &EncodedTransactionWithStatusMeta,
+    deltas: &[BalanceDelta],
+) -> Option<NewSwap> {
+    let meta = transaction.meta.as_ref()?;
+    if meta.err.is_some() {
+        return None;
+    }
+    let keys = account_keys(transaction);
+    let (dex, program) = find_dex(&program_ids(transaction, &keys))?;
+
+    let mut sol = keys
+        .iter()
+        .position(|key| key == account)
+        .and_then(|index| {
+            let native =
+                *meta.post_balances.get(index)? as i128 - *meta.pre_balances.get(index)? as i128;
+            // the first key pays the fee
+            Some(if index == 0 {
+                native + meta.fee as i128
+            } else {
+                native
+            })
+        })
+        .unwrap_or(0)
+        + token_account_rent(account, meta);
+    let mut tokens = vec![];
+    for delta in deltas {
+        if delta.mint == WSOL_MINT {
+            sol += delta.delta();
+        } else {
+            let leg = SwapLeg {
+                mint: delta.mint.clone(),
+                amount: delta.delta().unsigned_abs() as u64,
+                decimals: delta.decimals,
+            };
+            tokens.push((leg, delta.delta() > 0));
+        }
+    }
+
+    // the largest token sent and received when several changed, dust from fees is ignored
+    let largest = |received: bool| {
+        tokens
+            .iter()
+            .filter(|(_, positive)| *positive == received)
+            .map(|(leg, _)| leg)
+            .max_by(|a, b| a.ui_amount().total_cmp(&b.ui_amount()))
+            .cloned()
+    };
+    let sol_leg = || SwapLeg {
+        mint: WSOL_MINT.to_string(),
+        amount: sol.unsigned_abs() as u64,
+        decimals: 9,
+    };
+    let (input, output) = match (largest(false), largest(true)) {
+        (Some(sent), Some(received)) => (sent, received),
+        (None, Some(received)) if sol < 0 => (sol_leg(), received),
+        (Some(sent), None) if sol > 0 => (sent, sol_leg()),
+        _ => return None,
+    };
+    Some(swap_from_legs(dex, &program, input, output))
+}
+
+// the average price an account paid for a token, per quote mint
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EntryPrice {
+    pub account: String,
+    pub token: String,
+    pub quote_mint: String,
+    pub buys: usize,
+    pub bought: f64, // ui amount of the token bought
+    pub spent: f64,  // ui amount of the quote paid for it
+    pub entry_price: f64,
+    pub sells: usize,
+    pub sold: f64,
+    pub received: f64,
+    pub first_buy_at: Option<i64>, // block time of the oldest buy
+}
+
+// buys and sells of the token grouped by account and quote mint, largest spender first
+pub fn entry_prices(token: &str, swaps: &[Swap]) -> Vec<EntryPrice> {
+    let mut prices: Vec<EntryPrice> = vec![];
+    for swap in swaps.iter().filter(|s| s.base_mint == token) {
+        let index = match prices
+            .iter()
+            .position(|p| p.account == swap.account && p.quote_mint == swap.quote_mint)
+        {
+            Some(index) => index,
+            None => {
+                prices.push(EntryPrice {
+                    account: swap.account.clone(),
+                    token: token.to_string(),
+                    quote_mint: swap.quote_mint.clone(),
+                    buys: 0,
+                    bought: 0.0,
+                    spent: 0.0,
+                    entry_price: 0.0,
+                    sells: 0,
+                    sold: 0.0,
+                    received: 0.0,
+                    first_buy_at: None,
+                });
+                prices.len() - 1
+            }
+        };
+        let price = &mut prices[index];
+        let (input, output) = (swap.input_ui_amount(), swap.output_ui_amount());
+        match swap.side {
+            SwapSide::Buy => {
+                price.buys += 1;
+                price.bought += output;
+                price.spent += input;
+                if let Some(time) = swap.block_time {
+                    price.first_buy_at = Some(price.first_buy_at.map_or(time, |t| t.min(time)));
+                }
+            }
+            SwapSide::Sell => {
+                price.sells += 1;
+                price.sold += input;
+                price.received += output;
+            }
+            SwapSide::Swap => {}
+        }
+    }
+    for price in &mut prices {
+        if price.bought > 0.0 {
+            price.entry_price = price.spent / price.bought;
+        }
+    }
+    prices.sort_by(|a, b| b.spent.total_cmp(&a.spent));
+    prices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use solana_sdk::pubkey::Pubkey;
+    use std::str::FromStr;
+
+    const ACCOUNT: &str = "9xHxgDbeQDX51Vof7ruAaYjSYgR87BXRp3ZC62jrmJV1";
+    const TOKEN: &str = "Evi1CoinMint11111111111111111111111111111111";
+    const PUMP: &str = "6EF8rrecthR5Dkzon8Nwu78hRvfCKubJ14M5uBEwF6P";
+    const JUPITER: &str = "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4";
+    const RAYDIUM: &str = "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8";
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+    fn token_balance(index: u8, mint: &str, owner: &str, amount: u64, decimals: u8) -> Value {
+        json!({
+            "accountIndex": index,
+            "mint": mint,
+            "uiTokenAmount": {
+                "uiAmount": null,
+                "decimals": decimals,
+                "amount": amount.to_string(),
+                "uiAmountString": ""
+            },
+            "owner": owner,
+            "programId": spl_token::id().to_string()
+        })
+    }
+
+    // keys are the account, its token accounts and the pool vaults, then the programs
+    fn transaction(
+        keys: &[&str],
+        outer: &str,
+        inner: &[&str],
+        balances: (Vec<u64>, Vec<u64>),
+        token_balances: (Vec<Value>, Vec<Value>),
+    ) -> EncodedTransactionWithStatusMeta {
+        let index = |program: &str| keys.iter().position(|k| *k == program).unwrap();
+        let account_keys: Vec<Value> = keys
+            .iter()
+            .map(|key| json!({"pubkey": key, "writable": true, "signer": false, "source": "transaction"}))
+            .collect();
+        let inner: Vec<Value> = inner
+            .iter()
+            .map(|program| json!({"programIdIndex": index(program), "accounts": [], "data": "", "stackHeight": 2}))
+            .collect();
+        serde_json::from_value(json!({
+            "transaction": {
+                "signatures": ["sig"],
+                "message": {
+                    "accountKeys": account_keys,
+                    "recentBlockhash": "11111111111111111111111111111111",
+                    "instructions": [
+                        {"programId": outer, "accounts": [], "data": "", "stackHeight": null}
+                    ]
+                }
+            },
+            "meta": {
+                "err": null,
+                "status": {"Ok": null},
+                "fee": 5000,
+                "preBalances": balances.0,
+                "postBalances": balances.1,
+                "innerInstructions": [{"index": 0, "instructions": inner}],
+                "preTokenBalances": token_balances.0,
+                "postTokenBalances": token_balances.1
+            },
+            "version": 0
+        }))
+        .unwrap()
+    }
+
+    fn deltas(account: &str, transaction: &EncodedTransactionWithStatusMeta) -> Vec<BalanceDelta> {
+        crate::history::token_deltas(account, transaction.meta.as_ref().unwrap())
+    }
+
+    #[test]
+    fn test_program_addresses_are_pubkeys() {
+        for (address, _) in PROGRAMS {
+            assert!(Pubkey::from_str(address).is_ok(), "{}", address);
+        }
+    }
+
+    #[test]
+    fn test_pump_fun_buy() {
+        // 0.5 sol for 1000 tokens, the token account is opened in the same transaction
+        let rent = 2_039_280;
+        let transaction = transaction(
+            &[ACCOUNT, "ata", "curve-vault", PUMP],
+            PUMP,
+            &[],
+            (
+                vec![10_000_000_000, 0, 0, 1],
+                vec![10_000_000_000 - 500_000_000 - 5000 - rent, rent, 0, 1],
+            ),
+            (
+                vec![token_balance(2, TOKEN, "curve", 5_000_000_000, 6)],
+                vec![
+                    token_balance(1, TOKEN, ACCOUNT, 1_000_000_000, 6),
+                    token_balance(2, TOKEN, "curve", 4_000_000_000, 6),
+                ],
+            ),
+        );
+        let swap = decode_swap(ACCOUNT, &transaction, &deltas(ACCOUNT, &transaction)).unwrap();
+        assert_eq!(swap.dex, Dex::PumpFun);
+        assert_eq!(swap.program, PUMP);
+        assert_eq!(swap.side, SwapSide::Buy);
+        assert_eq!(
+            (swap.input_mint.as_str(), swap.input_amount),
+            (WSOL_MINT, 500_000_000)
+        );
+        assert_eq!(
+            (swap.output_mint.as_str(), swap.output_amount),
+            (TOKEN, 1_000_000_000)
+        );
+        assert_eq!(swap.base_mint, TOKEN);
+        assert_eq!(swap.price, 0.0005);
+
+        // the pool side of the same transaction is not a swap of anyone else
+        assert!(decode_swap("curve", &transaction, &deltas("curve", &transaction)).is_none());
+    }
+
+    #[test]
+    fn test_jupiter_route_sell() {
+        // 1000 tokens to usdc through a raydium pool, hopping through the wrapped sol account
+        // of the account, which ends where it started
+        let transaction = transaction(
+            &[ACCOUNT, "ata", "usdc-ata", "wsol-ata", RAYDIUM, JUPITER],
+            JUPITER,
+            &[RAYDIUM],
+            (
+                vec![1_000_000_000, 1, 1, 2_039_280 + 3_000_000, 1, 1],
+                vec![1_000_000_000 - 5000, 1, 1, 2_039_280 + 3_000_000, 1, 1],
+            ),
+            (
+                vec![
+                    token_balance(1, TOKEN, ACCOUNT, 1_000_000_000, 6),
+                    token_balance(2, USDC, ACCOUNT, 10_000_000, 6),
+                    token_balance(3, WSOL_MINT, ACCOUNT, 3_000_000, 9),
+                ],
+                vec![
+                    token_balance(1, TOKEN, ACCOUNT, 0, 6),
+                    token_balance(2, USDC, ACCOUNT, 35_000_000, 6),
+                    token_balance(3, WSOL_MINT, ACCOUNT, 3_000_000, 9),
+                ],
+            ),
+        );
+        let swap = decode_swap(ACCOUNT, &transaction, &deltas(ACCOUNT, &transaction)).unwrap();
+        // routed swaps are stored as jupiter, not as the pool it went through
+        assert_eq!(swap.dex, Dex::Jupiter);
+        assert_eq!(swap.side, SwapSide::Sell);
+        assert_eq!(
+            (swap.input_mint.as_str(), swap.input_amount),
+            (TOKEN, 1_000_000_000)
+        );
+        assert_eq!(
+            (swap.output_mint.as_str(), swap.output_amount),
+            (USDC, 25_000_000)
+        );
+        assert_eq!(swap.quote_mint, USDC);
+        assert_eq!(swap.price, 0.025);
+    }
+
+    #[test]
+    fn test_not_a_swap() {
+        // a plain transfer that only pays the fee
+        let transfer = transaction(
+            &[
+                ACCOUNT,
+                "ata",
+                "other-ata",
+                "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+            ],
+            "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+            &[],
+            (vec![1_000_000, 1, 1, 1], vec![1_000_000 - 5000, 1, 1, 1]),
+            (
+                vec![token_balance(1, TOKEN, ACCOUNT, 100, 6)],
+                vec![token_balance(1, TOKEN, ACCOUNT, 0, 6)],
+            ),
+        );
+        assert!(decode_swap(ACCOUNT, &transfer, &deltas(ACCOUNT, &transfer)).is_none());
+
+        // a dex was called but the account only paid, e.g. a failed slippage check refund
+        let paid = transaction(
+            &[ACCOUNT, RAYDIUM],
+            RAYDIUM,
+            &[],
+            (vec![1_000_000, 1], vec![1_000_000 - 5000, 1]),
+            (vec![], vec![]),
+        );
+        assert!(decode_swap(ACCOUNT, &paid, &[]).is_none());
+    }
+
+    fn swap(account: &str, side: SwapSide, input: u64, output: u64, time: i64) -> Swap {
+        let (input_mint, output_mint) = match side {
+            SwapSide::Buy => (WSOL_MINT, TOKEN),
+            _ => (TOKEN, WSOL_MINT),
+        };
+        let (input_decimals, output_decimals) = match side {
+            SwapSide::Buy => (9, 6),
+            _ => (6, 9),
+        };
+        Swap {
+            account: account.to_string(),
+            side,
+            input_mint: input_mint.to_string(),
+            input_amount: input.to_string(),
+            input_decimals,
+            output_mint: output_mint.to_string(),
+            output_amount: output.to_string(),
+            output_decimals,
+            base_mint: TOKEN.to_string(),
+            quote_mint: WSOL_MINT.to_string(),
+            price: 0.0,
+            id: 0,
+            signature: "sig".to_string(),
+            slot: 1,
+            block_time: Some(time),
+            dex: Dex::PumpFun,
+            program: PUMP.to_string(),
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_entry_prices() {
+        let swaps = vec![
+            swap("evil", SwapSide::Sell, 500_000_000, 1_000_000_000, 30),
+            swap("evil", SwapSide::Buy, 3_000_000_000, 1_000_000_000, 20),
+            swap("evil", SwapSide::Buy, 1_000_000_000, 1_000_000_000, 10),
+            swap("small", SwapSide::Buy, 100_000_000, 100_000_000, 15),
+        ];
+        let prices = entry_prices(TOKEN, &swaps);
+        assert_eq!(prices.len(), 2);
+        let evil = &prices[0];
+        assert_eq!(evil.account, "evil");
+        assert_eq!((evil.buys, evil.bought, evil.spent), (2, 2000.0, 4.0));
+        assert_eq!(evil.entry_price, 0.002);
+        assert_eq!((evil.sells, evil.sold, evil.received), (1, 500.0, 1.0));
+        assert_eq!(evil.first_buy_at, Some(10));
+        assert_eq!(prices[1].entry_price, 0.001);
+
+        assert!(entry_prices("other", &swaps).is_empty());
+    }
+}
@@ -0,0 +1,432 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use tokio::sync::{Mutex, OnceCell};
+use tracing::{error, info};
+
+use crate::{
+    concentration::ConcentrationReport,
+    config::{get_global_config, TelegramConfig},
+    models::{PositionEvent, PositionEventType},
+};
+
+#[derive(Debug, Serialize)]
+struct SendMessage<'a> {
+    chat_id: &'a str,
+    text: &'a str,
+    disable_web_page_preview: bool,
+}
+
+// replace every {key} in the template
+pub fn render(template: &str, values: &[(&str, String)]) -> String {
+    let mut text = template.to_string();
+    for (key, value) in values {
+        text = text.replace(&format!("{{{}}}", key), value);
+    }
+    text
+}
+
+// the rules that fired, one per line
+fn reasons(report: &ConcentrationReport) -> String {
+    report
+        .reasons
+        .iter()
+        .map(|reason| {
+            format!(
+                "{} {} > {}",
+                reason.metric.as_str(),
+                reason.value,
+                reason.max
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// a chat and the message to send to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub chat_id: String,
+    pub text: String,
+}
+
+#[derive(Clone)]
+pub struct TelegramNotifier {
+    client: reqwest::Client,
+    config: Option<Arc<TelegramConfig>>,
+    suspicious_sent: Arc<Mutex<HashMap<String, i64>>>, // token -> last sent at
+}
+
+pub static GLOBAL_TELEGRAM: OnceCell<Arc<TelegramNotifier>> = OnceCell::const_new();
+
+pub async fn get_global_telegram() -> &'static Arc<TelegramNotifier> {
+    GLOBAL_TELEGRAM
+        .get_or_init(|| async {
+            let config = get_global_config().await;
+            Arc::new(TelegramNotifier::new(config.telegram.clone()))
+        })
+        .await
+}
+
+impl TelegramNotifier {
+    // without a config every notify is a no-op
+    pub fn new(config: Option<TelegramConfig>) -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .expect("Failed to build telegram client"),
+            config: config.map(Arc::new),
+            suspicious_sent: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    // a tracked wallet buying is opening or adding to a position
+    pub fn buy_messages(&self, events: &[PositionEvent]) -> Vec<Message> {
+        let Some(config) = &self.config else {
+            return vec![];
+        };
+
+        let mut messages = vec![];
+        for event in events {
+            if !matches!(
+                event.event_type,
+                PositionEventType::NewPosition | PositionEventType::Increased
+            ) {
+                continue;
+            }
+            let scale = 10f64.powi(event.decimals as i32);
+            let amount = event.amount.parse::<f64>().unwrap_or_default() / scale;
+            let values = |group: &str| {
+                [
+                    ("account", event.account.clone()),
+                    ("token", event.token.clone()),
+                    ("event_type", event.event_type.as_str().to_string()),
+                    ("amount", amount.to_string()),
+                    ("delta", event.ui_delta.to_string()),
+                    ("slot", event.slot.to_string()),
+                    ("group", group.to_string()),
+                ]
+            };
+
+            let groups: Vec<_> = config
+                .groups
+                .iter()
+                .filter(|group| group.accounts.contains(&event.account))
+                .collect();
+            if groups.is_empty() {
+                let text = render(&config.buy_template, &values("default"));
+                for chat_id in &config.default_chat_ids {
+                    messages.push(Message {
+                        chat_id: chat_id.clone(),
+                        text: text.clone(),
+                    });
+                }
+                continue;
+            }
+            for group in groups {
+                let template = group.buy_template.as_ref().unwrap_or(&config.buy_template);
+                let text = render(template, &values(&group.name));
+                for chat_id in &group.chat_ids {
+                    messages.push(Message {
+                        chat_id: chat_id.clone(),
+                        text: text.clone(),
+                    });
+                }
+            }
+        }
+        messages
+    }
+
+    // empty while the same token is still in its cooldown. the cooldown starts here so two
+    // checks at once send it once, send_suspicious ends it again when sending fails
+    pub async fn suspicious_messages(
+        &self,
+        report: &ConcentrationReport,
+        threshold: f64,
+    ) -> Vec<Message> {
+        let token = report.token.as_str();
+        let Some(config) = &self.config else {
+            return vec![];
+        };
+
+        let now = chrono::Local::now().timestamp();
+        let mut sent = self.suspicious_sent.lock().await;
+        if let Some(last) = sent.get(token) {
+            if now - last < config.suspicious_cooldown {
+                return vec![];
+            }
+        }
+        sent.insert(token.to_string(), now);
+
+        let text = render(
+            &config.suspicious_template,
+            &[
+                ("token", token.to_string()),
+                ("score", report.score.to_string()),
+                ("count", report.large_holders.to_string()),
+                ("threshold", threshold.to_string()),
+                ("reasons", reasons(report)),
+            ],
+        );
+        let chat_ids = if config.suspicious_chat_ids.is_empty() {
+            &config.default_chat_ids
+        } else {
+            &config.suspicious_chat_ids
+        };
+        chat_ids
+            .iter()
+            .map(|chat_id| Message {
+                chat_id: chat_id.clone(),
+                text: text.clone(),
+            })
+            .collect()
+    }
+
+    // https://core.telegram.org/bots/api#sendmessage
+    pub async fn send(&self, message: &Message) -> Result<()> {
+        let Some(config) = &self.config else {
+            return Ok(());
+        };
+
+        let url = format!(
+            "{}/bot{}/sendMessage",
+            config.api_url.trim_end_matches('/'),
+            config.bot_token
+        );
+        let response = self
+            .client
+            .post(url)
+            .json(&SendMessage {
+                chat_id: &message.chat_id,
+                text: &message.text,
+                disable_web_page_preview: true,
+            })
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow!("telegram status {}: {}", status, body));
+        }
+
+        info!("telegram message sent, chat: {}", message.chat_id);
+        Ok(())
+    }
+
+    // false when any of them was not sent
+    async fn send_all(&self, messages: &[Message]) -> bool {
+        let mut sent = true;
+        for message in messages {
+            if let Err(e) = self.send(message).await {
+                error!("telegram send error: {:?}, chat: {}", e, message.chat_id);
+                sent = false;
+            }
+        }
+        sent
+    }
+
+    // send in the background, the daemon and web handlers do not wait for telegram
+    pub fn spawn_send(&self, messages: Vec<Message>) {
+        if messages.is_empty() {
+            return;
+        }
+        let notifier = self.clone();
+        tokio::spawn(async move { notifier.send_all(&messages).await });
+    }
+
+    pub fn notify_buys(&self, events: &[PositionEvent]) {
+        self.spawn_send(self.buy_messages(events));
+    }
+
+    // a failed send does not hold back the next check, chats that got it may see it again
+    async fn send_suspicious(&self, report: &ConcentrationReport, threshold: f64) {
+        let messages = self.suspicious_messages(report, threshold).await;
+        if messages.is_empty() || self.send_all(&messages).await {
+            return;
+        }
+        self.suspicious_sent.lock().await.remove(&report.token);
+    }
+
+    pub async fn notify_suspicious(&self, report: &ConcentrationReport, threshold: f64) {
+        let notifier = self.clone();
+        let report = report.clone();
+        tokio::spawn(async move { notifier.send_suspicious(&report, threshold).await });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        concentration::LargestHolder,
+        config::{ConcentrationConfig, TelegramGroup},
+    };
+    use axum::{
+        extract::{Path, State},
+        routing::post,
+        Json, Router,
+    };
+    use std::sync::Mutex as StdMutex;
+    use tokio::net::TcpListener;
+
+    fn config(api_url: &str) -> TelegramConfig {
+        TelegramConfig {
+            bot_token: "123:abc".to_string(),
+            api_url: api_url.to_string(),
+            default_chat_ids: vec!["default".to_string()],
+            suspicious_chat_ids: vec![],
+            buy_template: "{group} {account} {event_type} {token} {amount} {delta}".to_string(),
+            suspicious_template: "{token} {score} {count} {threshold} {reasons}".to_string(),
+            suspicious_cooldown: 3600,
+            groups: vec![
+                TelegramGroup {
+                    name: "whales".to_string(),
+                    accounts: vec!["whale".to_string()],
+                    chat_ids: vec!["w1".to_string(), "w2".to_string()],
+                    buy_template: None,
+                },
+                TelegramGroup {
+                    name: "snipers".to_string(),
+                    accounts: vec!["sniper".to_string()],
+                    chat_ids: vec!["s1".to_string()],
+                    buy_template: Some("snipe {token}".to_string()),
+                },
+            ],
+        }
+    }
+
+    fn event(account: &str, event_type: PositionEventType) -> PositionEvent {
+        PositionEvent {
+            id: 1,
+            account: account.to_string(),
+            token: "mint".to_string(),
+            event_type,
+            previous_amount: "0".to_string(),
+            amount: "2500".to_string(),
+            delta: "2500".to_string(),
+            decimals: 2,
+            ui_delta: 25.0,
+            slot: 1,
+            created_at: 1,
+        }
+    }
+
+    #[test]
+    fn test_buy_messages_routing() {
+        let notifier = TelegramNotifier::new(Some(config("http://localhost")));
+        let messages = notifier.buy_messages(&[
+            event("whale", PositionEventType::NewPosition),
+            event("sniper", PositionEventType::Increased),
+            event("nobody", PositionEventType::NewPosition),
+            event("whale", PositionEventType::Exited),
+            event("whale", PositionEventType::Decreased),
+        ]);
+
+        let chats: Vec<&str> = messages.iter().map(|m| m.chat_id.as_str()).collect();
+        assert_eq!(chats, vec!["w1", "w2", "s1", "default"]);
+        assert_eq!(messages[0].text, "whales whale new_position mint 25 25");
+        assert_eq!(messages[2].text, "snipe mint");
+        assert_eq!(messages[3].text, "default nobody new_position mint 25 25");
+
+        assert!(TelegramNotifier::new(None)
+            .buy_messages(&[event("whale", PositionEventType::NewPosition)])
+            .is_empty());
+    }
+
+    // one holder with 60% of the supply
+    fn report(token: &str) -> ConcentrationReport {
+        let holder = LargestHolder {
+            address: "ata".to_string(),
+            amount: 600,
+            ..Default::default()
+        };
+        ConcentrationReport::new(
+            token,
+            1_000,
+            0,
+            vec![holder],
+            100.0,
+            &ConcentrationConfig::default(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_suspicious_cooldown() {
+        let notifier = TelegramNotifier::new(Some(config("http://localhost")));
+        let messages = notifier.suspicious_messages(&report("mint"), 100.0).await;
+        assert_eq!(
+            messages,
+            vec![Message {
+                chat_id: "default".to_string(),
+                text: "mint 75 1 100 large_holders 1 > 0\ntop10_share 0.6 > 0.5".to_string(),
+            }]
+        );
+        assert!(notifier
+            .suspicious_messages(&report("mint"), 100.0)
+            .await
+            .is_empty());
+        assert_eq!(
+            notifier
+                .suspicious_messages(&report("other"), 100.0)
+                .await
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_suspicious_cooldown_after_failed_send() {
+        // nothing listens there anymore
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let api_url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let notifier = TelegramNotifier::new(Some(config(&api_url)));
+        notifier.send_suspicious(&report("mint"), 100.0).await;
+        assert_eq!(
+            notifier
+                .suspicious_messages(&report("mint"), 100.0)
+                .await
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_to_mock_bot_api() {
+        type Received = Arc<StdMutex<Vec<(String, serde_json::Value)>>>;
+        async fn send_message(
+            State(received): State<Received>,
+            Path(bot): Path<String>,
+            Json(body): Json<serde_json::Value>,
+        ) -> Json<serde_json::Value> {
+            received.lock().unwrap().push((bot, body));
+            Json(serde_json::json!({"ok": true}))
+        }
+
+        let received: Received = Default::default();
+        let app = Router::new()
+            .route("/:bot/sendMessage", post(send_message))
+            .with_state(received.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let api_url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let notifier = TelegramNotifier::new(Some(config(&api_url)));
+        notifier
+            .send(&Message {
+                chat_id: "-100".to_string(),
+                text: "hi".to_string(),
+            })
+            .await
+            .unwrap();
+
+        let received = received.lock().unwrap().clone();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].0, "bot123:abc");
+        assert_eq!(received[0].1["chat_id"], "-100");
+        assert_eq!(received[0].1["text"], "hi");
+    }
+}
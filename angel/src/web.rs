@@ -1,17 +1,25 @@
 use crate::{
-    config::get_global_config, models::get_global_manager, solana_rpc::get_token_largest_accounts,
+    concentration::{ConcentrationReport, LargestHolder},
+    config::get_global_config,
+    holders::{HolderKind, HolderRegistry},
+    models::{get_global_manager, AccountUpdate, Coin, Token},
+    repository::SharedRepository,
+    solana_rpc::get_global_solana_rpc,
+    swaps::entry_prices,
+    telegram::get_global_telegram,
 };
 use anyhow::Result;
 use axum::{
     error_handling::HandleErrorLayer,
-    extract::{Json, Query},
+    extract::{Json, Path, Query, State},
     response::IntoResponse,
-    routing::get,
+    routing::{get, patch, post},
     Router,
 };
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use solana_sdk::pubkey::Pubkey;
+use std::{collections::HashMap, str::FromStr, time::Duration};
 use tokio::{net::TcpListener, signal};
 use tower::{BoxError, ServiceBuilder};
 use tower_http::trace::TraceLayer;
@@ -37,46 +45,74 @@ impl<T> CustomResponse<T> {
     fn err(msg: String) -> Self {
         CustomResponse { msg, data: None }
     }
-    fn to_json(self) -> Json<CustomResponse<T>> {
+    fn into_json(self) -> Json<CustomResponse<T>> {
         Json(self)
     }
 }
 
-pub async fn start_server() -> Result<()> {
-    let c = get_global_config().await;
-    let app = Router::new()
+// all api routes, storage comes in as state so tests can hand in a MemoryRepository
+pub fn router(manager: SharedRepository) -> Router {
+    Router::new()
         .route("/api/v1/add_account", get(add_account))
+        .route("/api/v1/accounts", get(get_accounts).post(create_account))
+        .route(
+            "/api/v1/accounts/:address",
+            patch(update_account).delete(delete_account),
+        )
+        .route("/api/v1/accounts/:address/restore", post(restore_account))
         .route("/api/v1/get_coin", get(get_coin))
+        .route("/api/v1/token_holders", get(get_token_holders))
         .route("/api/v1/get_account", get(get_account))
         .route("/api/v1/get_accounts", get(get_accounts))
         .route(
             "/api/v1/check_token_largest_accounts",
             get(check_token_largest_accounts),
         )
-        .layer(
-            ServiceBuilder::new()
-                .layer(HandleErrorLayer::new(|error: BoxError| async move {
-                    if error.is::<tower::timeout::error::Elapsed>() {
-                        Ok(StatusCode::REQUEST_TIMEOUT)
-                    } else {
-                        Err((
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            format!("Unhandled internal error: {}", error),
-                        ))
-                    }
-                }))
-                .timeout(Duration::from_secs(30))
-                .layer(TraceLayer::new_for_http())
-                .into_inner(),
-        );
+        .route("/api/v1/token_safety", get(get_token_safety))
+        .route("/api/v1/events", get(get_events))
+        .route("/api/v1/balance_changes", get(get_balance_changes))
+        .route("/api/v1/swaps", get(get_swaps))
+        .route("/api/v1/entry_prices", get(get_entry_prices))
+        .route("/api/v1/rpc_status", get(get_rpc_status))
+        .with_state(manager)
+}
+
+pub async fn start_server() -> Result<()> {
+    let c = get_global_config().await;
+    let manager: SharedRepository = get_global_manager().await.clone();
+    let app = router(manager).layer(
+        ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(|error: BoxError| async move {
+                if error.is::<tower::timeout::error::Elapsed>() {
+                    Ok(StatusCode::REQUEST_TIMEOUT)
+                } else {
+                    Err((
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        format!("Unhandled internal error: {}", error),
+                    ))
+                }
+            }))
+            .timeout(Duration::from_secs(30))
+            .layer(TraceLayer::new_for_http())
+            .into_inner(),
+    );
 
     let addr = TcpListener::bind(&c.host_uri).await.unwrap();
     info!("Starting web server at {}", addr.local_addr()?);
     info!("add account: /api/v1/add_account?address=xxx");
     info!("get coin: /api/v1/get_coin?token=xxx");
+    info!("token holders: /api/v1/token_holders?token=xxx");
     info!("get account: /api/v1/get_account?address=xxx");
     info!("get accounts: /api/v1/get_accounts");
+    info!("manage accounts: POST /api/v1/accounts, PATCH|DELETE /api/v1/accounts/xxx");
+    info!("restore account: POST /api/v1/accounts/xxx/restore");
     info!("check token largest accounts: /api/v1/check_token_largest_accounts?token=xxx");
+    info!("token safety: /api/v1/token_safety?token=xxx");
+    info!("get events: /api/v1/events?account=xxx&token=xxx&since=timestamp");
+    info!("balance changes: /api/v1/balance_changes?account=xxx&token=xxx");
+    info!("swaps: /api/v1/swaps?account=xxx&token=xxx");
+    info!("entry prices: /api/v1/entry_prices?token=xxx");
+    info!("rpc status: /api/v1/rpc_status");
     axum::serve(addr, app)
         .with_graceful_shutdown(shoutdown_signal())
         .await
@@ -95,97 +131,473 @@ struct TokenQuery {
     token: String,
 }
 
-async fn add_account(input: Query<AccountAddress>) -> impl IntoResponse {
-    let manager = get_global_manager().await;
-    if let Err(e) = manager.add_new_account(input.address.clone()).await {
-        return CustomResponse::err(e.to_string()).to_json();
+// any base58 pubkey is accepted, off-curve addresses such as PDAs can own token accounts too
+fn check_address(address: &str) -> Result<(), String> {
+    match Pubkey::from_str(address) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(format!("invalid address: {}", address)),
+    }
+}
+
+async fn add_account(
+    State(manager): State<SharedRepository>,
+    input: Query<AccountAddress>,
+) -> impl IntoResponse {
+    if let Err(e) = check_address(&input.address) {
+        return CustomResponse::err(e).into_json();
+    }
+    if let Err(e) = manager.add_new_account(&input.address).await {
+        return CustomResponse::err(e.to_string()).into_json();
+    }
+
+    CustomResponse::<i32>::ok(None).into_json()
+}
+
+#[derive(Deserialize)]
+struct NewAccount {
+    address: String,
+    #[serde(flatten)]
+    update: AccountUpdate,
+}
+
+// tracks the address with its label, note and tags. a deleted account is restored
+async fn create_account(
+    State(manager): State<SharedRepository>,
+    Json(input): Json<NewAccount>,
+) -> impl IntoResponse {
+    if let Err(e) = check_address(&input.address) {
+        return CustomResponse::err(e).into_json();
+    }
+    if let Err(e) = input.update.check() {
+        return CustomResponse::err(e.to_string()).into_json();
+    }
+    if let Err(e) = manager.add_new_account(&input.address).await {
+        return CustomResponse::err(e.to_string()).into_json();
+    }
+    match manager.update_account(&input.address, &input.update).await {
+        Ok(account) => CustomResponse::ok(account).into_json(),
+        Err(e) => CustomResponse::err(e.to_string()).into_json(),
+    }
+}
+
+async fn update_account(
+    State(manager): State<SharedRepository>,
+    Path(address): Path<String>,
+    Json(update): Json<AccountUpdate>,
+) -> impl IntoResponse {
+    if let Err(e) = update.check() {
+        return CustomResponse::err(e.to_string()).into_json();
+    }
+    match manager.update_account(&address, &update).await {
+        Ok(Some(account)) => CustomResponse::ok(Some(account)).into_json(),
+        Ok(None) => CustomResponse::err("account not found".to_string()).into_json(),
+        Err(e) => CustomResponse::err(e.to_string()).into_json(),
+    }
+}
+
+// the daemon and the streams drop the account on their next look at the tracked accounts
+async fn delete_account(
+    State(manager): State<SharedRepository>,
+    Path(address): Path<String>,
+) -> impl IntoResponse {
+    match manager.delete_account(&address).await {
+        Ok(true) => CustomResponse::<i32>::ok(None).into_json(),
+        Ok(false) => CustomResponse::err("account not found".to_string()).into_json(),
+        Err(e) => CustomResponse::err(e.to_string()).into_json(),
     }
+}
+
+async fn restore_account(
+    State(manager): State<SharedRepository>,
+    Path(address): Path<String>,
+) -> impl IntoResponse {
+    match manager.restore_account(&address).await {
+        Ok(true) => match manager.get_account_with_mint(&address).await {
+            Ok(account) => CustomResponse::ok(account).into_json(),
+            Err(e) => CustomResponse::err(e.to_string()).into_json(),
+        },
+        Ok(false) => CustomResponse::err("no deleted account".to_string()).into_json(),
+        Err(e) => CustomResponse::err(e.to_string()).into_json(),
+    }
+}
+
+async fn get_coin(
+    State(manager): State<SharedRepository>,
+    Query(query): Query<TokenQuery>,
+) -> impl IntoResponse {
+    match manager.get_coin_with_token(&query.token).await {
+        Ok(coin) => {
+            let coin = with_tokens(&manager, coin.into_iter().collect(), |c| &c.token)
+                .await
+                .pop();
+            CustomResponse::ok(Some(coin)).into_json()
+        }
+        Err(e) => CustomResponse::err(e.to_string()).into_json(),
+    }
+}
+
+// an item with the name and symbol of its mint from the tokens table, null until the daemon
+// has resolved them
+#[derive(Debug, Serialize)]
+struct WithToken<T> {
+    #[serde(flatten)]
+    item: T,
+    name: Option<String>,
+    symbol: Option<String>,
+}
 
-    CustomResponse::<i32>::ok(None).to_json()
+async fn with_tokens<T>(
+    manager: &SharedRepository,
+    items: Vec<T>,
+    mint: impl Fn(&T) -> &str,
+) -> Vec<WithToken<T>> {
+    let mut mints: Vec<String> = items.iter().map(|item| mint(item).to_string()).collect();
+    mints.sort();
+    mints.dedup();
+    let tokens: HashMap<String, Token> = match manager.get_tokens(&mints).await {
+        Ok(tokens) => tokens.into_iter().map(|t| (t.mint.clone(), t)).collect(),
+        Err(e) => {
+            warn!("get tokens error: {:?}", e);
+            HashMap::new()
+        }
+    };
+    items
+        .into_iter()
+        .map(|item| {
+            let token = tokens.get(mint(&item));
+            WithToken {
+                name: token.and_then(|t| t.name.clone()),
+                symbol: token.and_then(|t| t.symbol.clone()),
+                item,
+            }
+        })
+        .collect()
 }
 
-async fn get_coin(Query(query): Query<TokenQuery>) -> impl IntoResponse {
-    let manager = get_global_manager().await.clone();
+#[derive(Debug, Serialize)]
+struct TokenHolder {
+    account: String,
+    amount: String, // raw u64 amount
+    ui_amount: f64,
+    decimals: i64,
+    pubkey: Option<String>,
+    first_seen: i64, // when the coin was first stored for the account
+}
 
-    match manager.get_coin_with_token(query.token).await {
-        Ok(coin) => CustomResponse::ok(Some(coin)).to_json(),
-        Err(e) => CustomResponse::err(e.to_string()).to_json(),
+#[derive(Debug, Serialize)]
+struct TokenHoldersResponse {
+    token: String,
+    holders: Vec<TokenHolder>, // largest first
+    tracked_amount: String,    // raw amount over all tracked holders
+    tracked_ui_amount: f64,
+    supply: Option<String>,     // raw supply, none when the rpc lookup failed
+    tracked_share: Option<f64>, // tracked_amount / supply, 0..=1
+}
+
+impl TokenHoldersResponse {
+    fn new(token: &str, coins: Vec<Coin>, supply: Option<u64>) -> Self {
+        let mut holders: Vec<(u64, TokenHolder)> = coins
+            .into_iter()
+            .map(|c| {
+                let amount = c.amount.parse::<u64>().unwrap_or_default();
+                let holder = TokenHolder {
+                    ui_amount: amount as f64 / 10f64.powi(c.decimals as i32),
+                    account: c.account,
+                    amount: c.amount,
+                    decimals: c.decimals,
+                    pubkey: c.pubkey,
+                    first_seen: c.created_at,
+                };
+                (amount, holder)
+            })
+            .collect();
+        holders.sort_by_key(|(amount, _)| std::cmp::Reverse(*amount));
+
+        let tracked: u128 = holders.iter().map(|(amount, _)| *amount as u128).sum();
+        let decimals = holders.first().map_or(0, |(_, h)| h.decimals);
+        let tracked_share = match supply {
+            _ if tracked == 0 => Some(0.0),
+            Some(supply) if supply > 0 => Some(tracked as f64 / supply as f64),
+            _ => None,
+        };
+        Self {
+            token: token.to_string(),
+            holders: holders.into_iter().map(|(_, h)| h).collect(),
+            tracked_amount: tracked.to_string(),
+            tracked_ui_amount: tracked as f64 / 10f64.powi(decimals as i32),
+            supply: supply.map(|s| s.to_string()),
+            tracked_share,
+        }
     }
 }
 
-async fn get_account(Query(query): Query<AccountAddress>) -> impl IntoResponse {
-    let manager = get_global_manager().await.clone();
+// every tracked account holding the token, and how much of the supply they hold together
+async fn get_token_holders(
+    State(manager): State<SharedRepository>,
+    Query(query): Query<TokenQuery>,
+) -> impl IntoResponse {
+    let coins = match manager.get_coins_with_token(&query.token).await {
+        Ok(coins) => coins,
+        Err(e) => return CustomResponse::err(e.to_string()).into_json(),
+    };
 
-    match manager.get_account_with_mint(query.address).await {
-        Ok(account) => CustomResponse::ok(Some(account)).to_json(),
-        Err(_) => CustomResponse::err("account not found".to_string()).to_json(),
+    let mut supply = None;
+    if !coins.is_empty() {
+        match get_global_solana_rpc()
+            .await
+            .get_token_supply(&query.token)
+            .await
+        {
+            Ok(amount) => supply = amount.amount.parse::<u64>().ok(),
+            Err(e) => warn!("get_token_supply error: {:?}, token: {}", e, query.token),
+        }
+    }
+
+    let response = TokenHoldersResponse::new(&query.token, coins, supply);
+    let response = with_tokens(&manager, vec![response], |r| &r.token)
+        .await
+        .pop();
+    CustomResponse::ok(response).into_json()
+}
+
+async fn get_account(
+    State(manager): State<SharedRepository>,
+    Query(query): Query<AccountAddress>,
+) -> impl IntoResponse {
+    match manager.get_account_with_mint(&query.address).await {
+        Ok(account) => CustomResponse::ok(Some(account)).into_json(),
+        Err(_) => CustomResponse::err("account not found".to_string()).into_json(),
     }
 }
 
-async fn get_accounts() -> impl IntoResponse {
-    let manager = get_global_manager().await.clone();
+async fn get_accounts(State(manager): State<SharedRepository>) -> impl IntoResponse {
     match manager.get_all_accounts().await {
-        Ok(accounts) => CustomResponse::ok(Some(accounts)).to_json(),
-        Err(e) => CustomResponse::err(e.to_string()).to_json(),
+        Ok(accounts) => CustomResponse::ok(Some(accounts)).into_json(),
+        Err(e) => CustomResponse::err(e.to_string()).into_json(),
+    }
+}
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    account: Option<String>,
+    token: Option<String>,
+    since: Option<i64>, // unix timestamp in seconds
+    limit: Option<i64>,
+}
+
+async fn get_events(
+    State(manager): State<SharedRepository>,
+    Query(query): Query<EventsQuery>,
+) -> impl IntoResponse {
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    match manager
+        .get_position_events(
+            query.account.as_deref(),
+            query.token.as_deref(),
+            query.since,
+            limit,
+        )
+        .await
+    {
+        Ok(events) => {
+            let events = with_tokens(&manager, events, |e| &e.token).await;
+            CustomResponse::ok(Some(events)).into_json()
+        }
+        Err(e) => CustomResponse::err(e.to_string()).into_json(),
     }
 }
 
-#[derive(Serialize)]
-struct CheckLargestAccountsResponse {
-    is_suspicion: bool,
+#[derive(Deserialize)]
+struct BalanceChangesQuery {
+    account: Option<String>,
+    token: Option<String>,
+    limit: Option<i64>,
+}
+
+// token balance changes per transaction from the history ingestion, newest first
+async fn get_balance_changes(
+    State(manager): State<SharedRepository>,
+    Query(query): Query<BalanceChangesQuery>,
+) -> impl IntoResponse {
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    match manager
+        .get_token_balance_changes(query.account.as_deref(), query.token.as_deref(), limit)
+        .await
+    {
+        Ok(changes) => {
+            let changes = with_tokens(&manager, changes, |c| &c.mint).await;
+            CustomResponse::ok(Some(changes)).into_json()
+        }
+        Err(e) => CustomResponse::err(e.to_string()).into_json(),
+    }
+}
+
+#[derive(Deserialize)]
+struct SwapsQuery {
+    account: Option<String>,
+    token: Option<String>, // input or output mint
+    limit: Option<i64>,
+}
+
+// swaps decoded by the history ingestion, newest first, named after the traded token
+async fn get_swaps(
+    State(manager): State<SharedRepository>,
+    Query(query): Query<SwapsQuery>,
+) -> impl IntoResponse {
+    let limit = query.limit.unwrap_or(100).clamp(1, 1000);
+    match manager
+        .get_swaps(query.account.as_deref(), query.token.as_deref(), limit)
+        .await
+    {
+        Ok(swaps) => {
+            let swaps = with_tokens(&manager, swaps, |s| &s.base_mint).await;
+            CustomResponse::ok(Some(swaps)).into_json()
+        }
+        Err(e) => CustomResponse::err(e.to_string()).into_json(),
+    }
 }
 
-async fn check_token_largest_accounts(Query(query): Query<TokenQuery>) -> impl IntoResponse {
+#[derive(Deserialize)]
+struct EntryPricesQuery {
+    token: String,
+    account: Option<String>,
+}
+
+// what every tracked account paid on average for the token, from its stored swaps
+async fn get_entry_prices(
+    State(manager): State<SharedRepository>,
+    Query(query): Query<EntryPricesQuery>,
+) -> impl IntoResponse {
+    match manager
+        .get_token_swaps(query.account.as_deref(), &query.token)
+        .await
+    {
+        Ok(swaps) => {
+            let prices = entry_prices(&query.token, &swaps);
+            let prices = with_tokens(&manager, prices, |p| &p.token).await;
+            CustomResponse::ok(Some(prices)).into_json()
+        }
+        Err(e) => CustomResponse::err(e.to_string()).into_json(),
+    }
+}
+
+// health of every rpc endpoint as this process sees it
+async fn get_rpc_status() -> impl IntoResponse {
+    let status = get_global_solana_rpc().await.pool().status();
+    CustomResponse::ok(Some(status)).into_json()
+}
+
+// mint and freeze authorities and risky extensions of the mint, safe is true when there are none
+async fn get_token_safety(Query(query): Query<TokenQuery>) -> impl IntoResponse {
+    match get_global_solana_rpc()
+        .await
+        .get_token_safety(&query.token)
+        .await
+    {
+        Ok(safety) => CustomResponse::ok(Some(safety)).into_json(),
+        Err(e) => {
+            warn!("get_token_safety error: {:?}, token: {}", e, query.token);
+            CustomResponse::err(format!("get token safety err: {}", e)).into_json()
+        }
+    }
+}
+
+// concentration of the largest holders, scored with the rules in [concentration]
+async fn check_token_largest_accounts(
+    State(manager): State<SharedRepository>,
+    Query(query): Query<TokenQuery>,
+) -> impl IntoResponse {
     let c = get_global_config().await;
-    let check_amount = c.check_largest_account_hold_coin;
-    let mut count = 0;
+    let rpc = get_global_solana_rpc().await;
     debug!("check_token_largest_accounts: token: {}", query.token);
-    match get_token_largest_accounts(&query.token, &c.get_random_solana_rpc_url()).await {
-        Ok(accounts) => {
-            for account in accounts {
-                debug!(
-                    "check_token_largest_accounts: token: {}, amount: {}",
-                    query.token, account.amount.ui_amount_string
-                );
-                match account.amount.ui_amount_string.parse::<f64>() {
-                    Ok(amount) => {
-                        if amount > check_amount {
-                            count += 1;
-                            info!(
-                                "check_token_largest_accounts: token: {}, amount: {}, count: {}",
-                                query.token, amount, count
-                            );
-                        }
-                    }
-                    Err(e) => {
-                        warn!(
-                            "check_token_largest_accounts parse amount error: {:?}, token: {}",
-                            e, query.token
-                        );
-                    }
-                }
-            }
-        }
+
+    let (accounts, supply) = match tokio::try_join!(
+        rpc.get_token_largest_accounts(&query.token),
+        rpc.get_token_supply(&query.token)
+    ) {
+        Ok(result) => result,
         Err(e) => {
             warn!(
                 "check_token_largest_accounts error: {:?}, token: {}",
                 e, query.token
             );
-            return CustomResponse::err(format!(
-                "get token largest accounts err: {}",
-                e.to_string()
-            ))
-            .to_json();
+            return CustomResponse::err(format!("get token largest accounts err: {}", e))
+                .into_json();
         }
+    };
+
+    let addresses: Vec<String> = accounts.iter().map(|a| a.address.clone()).collect();
+    let owners = match rpc.get_token_account_owners(&addresses).await {
+        Ok(owners) => owners,
+        Err(e) => {
+            warn!(
+                "check_token_largest_accounts owners error: {:?}, token: {}",
+                e, query.token
+            );
+            return CustomResponse::err(format!("get token account owners err: {}", e)).into_json();
+        }
+    };
+    let coins = match manager.get_coins_with_token(&query.token).await {
+        Ok(coins) => coins,
+        Err(e) => return CustomResponse::err(e.to_string()).into_json(),
+    };
+
+    let registry = HolderRegistry::new(&c.concentration.labels);
+    let mut holders = vec![];
+    for account in accounts {
+        let amount = match account.amount.amount.parse::<u64>() {
+            Ok(amount) => amount,
+            Err(e) => {
+                warn!(
+                    "check_token_largest_accounts parse amount error: {:?}, token: {}",
+                    e, query.token
+                );
+                continue;
+            }
+        };
+        let owner = owners.get(&account.address);
+        let (kind, label) = owner
+            .and_then(|o| registry.classify(&o.owner, o.owner_program.as_deref()))
+            .map_or((HolderKind::Wallet, None), |(kind, name)| {
+                (kind, Some(name.to_string()))
+            });
+        // a tracked wallet is found by the owner, or by the token account stored on the coin
+        let tracked_account = coins
+            .iter()
+            .find(|coin| {
+                owner.is_some_and(|o| o.owner == coin.account)
+                    || coin.pubkey.as_deref() == Some(account.address.as_str())
+            })
+            .map(|coin| coin.account.clone());
+        holders.push(LargestHolder {
+            address: account.address,
+            amount,
+            owner: owner.map(|o| o.owner.clone()),
+            kind,
+            label,
+            tracked_account,
+        });
     }
-    if count > 1 {
-        CustomResponse::ok(Some(CheckLargestAccountsResponse { is_suspicion: true })).to_json()
-    } else {
-        CustomResponse::ok(Some(CheckLargestAccountsResponse {
-            is_suspicion: false,
-        }))
-        .to_json()
+
+    let report = ConcentrationReport::new(
+        &query.token,
+        supply.amount.parse().unwrap_or_default(),
+        supply.decimals,
+        holders,
+        c.check_largest_account_hold_coin,
+        &c.concentration,
+    );
+    info!(
+        "check_token_largest_accounts: token: {}, score: {}, reasons: {:?}",
+        query.token, report.score, report.reasons
+    );
+    if report.is_suspicion {
+        get_global_telegram()
+            .await
+            .notify_suspicious(&report, c.check_largest_account_hold_coin)
+            .await;
     }
+    CustomResponse::ok(Some(report)).into_json()
 }
 
 async fn shoutdown_signal() {
@@ -210,3 +622,260 @@ async fn shoutdown_signal() {
 
     info!("signal received, shutting down");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::{IngestedTransaction, NewCoin},
+        repository::{MemoryRepository, Repository},
+        swaps::{swap_from_legs, Dex, SwapLeg, WSOL_MINT},
+    };
+    use axum::body::{to_bytes, Body};
+    use axum::http::Request;
+    use std::sync::Arc;
+    use tower::ServiceExt;
+
+    const WALLET: &str = "9xHxgDbeQDX51Vof7ruAaYjSYgR87BXRp3ZC62jrmJV1";
+
+    async fn call(app: &Router, uri: &str) -> serde_json::Value {
+        send(app, "GET", uri, None).await
+    }
+
+    async fn send(
+        app: &Router,
+        method: &str,
+        uri: &str,
+        body: Option<serde_json::Value>,
+    ) -> serde_json::Value {
+        let request = Request::builder().method(method).uri(uri);
+        let request = match body {
+            Some(body) => request
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        };
+        let response = app.clone().oneshot(request.unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_account_handlers() {
+        let app = router(Arc::new(MemoryRepository::new()));
+
+        let added = call(&app, &format!("/api/v1/add_account?address={}", WALLET)).await;
+        assert_eq!(added["msg"], "ok");
+        let invalid = call(
+            &app,
+            "/api/v1/add_account?address=x%27%20OR%20%271%27%3D%271",
+        )
+        .await;
+        assert_eq!(invalid["msg"], "invalid address: x' OR '1'='1");
+
+        let account = call(&app, &format!("/api/v1/get_account?address={}", WALLET)).await;
+        assert_eq!(account["data"]["account"], WALLET);
+        let missing = call(&app, "/api/v1/get_account?address=nobody").await;
+        assert!(missing["data"].is_null());
+
+        let accounts = call(&app, "/api/v1/get_accounts").await;
+        assert_eq!(accounts["data"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_account_management() {
+        use serde_json::json;
+
+        let app = router(Arc::new(MemoryRepository::new()));
+
+        let invalid = send(
+            &app,
+            "POST",
+            "/api/v1/accounts",
+            Some(json!({"address": "evil"})),
+        )
+        .await;
+        assert_eq!(invalid["msg"], "invalid address: evil");
+        let created = send(
+            &app,
+            "POST",
+            "/api/v1/accounts",
+            Some(json!({"address": WALLET, "label": "whale", "tags": ["kol", "kol"]})),
+        )
+        .await;
+        assert_eq!(created["data"]["label"], "whale");
+        assert_eq!(created["data"]["tags"], json!(["kol"]));
+
+        let uri = format!("/api/v1/accounts/{}", WALLET);
+        let updated = send(
+            &app,
+            "PATCH",
+            &uri,
+            Some(json!({"note": "early", "tags": []})),
+        )
+        .await;
+        assert_eq!(updated["data"]["label"], "whale");
+        assert_eq!(updated["data"]["note"], "early");
+        assert_eq!(updated["data"]["tags"], json!([]));
+        let too_many = send(&app, "PATCH", &uri, Some(json!({"tags": vec!["t"; 17]}))).await;
+        assert_eq!(too_many["msg"], "more than 16 tags");
+        let comma = send(&app, "PATCH", &uri, Some(json!({"tags": ["a,b"]}))).await;
+        assert_eq!(
+            comma["msg"],
+            "tag must be at most 32 characters without commas: a,b"
+        );
+
+        assert_eq!(send(&app, "DELETE", &uri, None).await["msg"], "ok");
+        assert_eq!(
+            send(&app, "DELETE", &uri, None).await["msg"],
+            "account not found"
+        );
+        let accounts = call(&app, "/api/v1/accounts").await;
+        assert_eq!(accounts["data"], json!([]));
+        let missing = send(&app, "PATCH", &uri, Some(json!({"label": "x"}))).await;
+        assert_eq!(missing["msg"], "account not found");
+
+        let restore = format!("{}/restore", uri);
+        let restored = send(&app, "POST", &restore, None).await;
+        assert_eq!(restored["data"]["note"], "early");
+        assert_eq!(restored["data"]["deleted"], 0);
+        assert_eq!(
+            send(&app, "POST", &restore, None).await["msg"],
+            "no deleted account"
+        );
+        let accounts = call(&app, "/api/v1/accounts").await;
+        assert_eq!(accounts["data"][0]["account"], WALLET);
+    }
+
+    #[tokio::test]
+    async fn test_coin_and_event_handlers() {
+        let manager = Arc::new(MemoryRepository::new());
+        manager
+            .add_new_coin("evil", &NewCoin::new("mint"))
+            .await
+            .unwrap();
+        manager
+            .add_token(&Token {
+                mint: "mint".to_string(),
+                name: Some("Evil Coin".to_string()),
+                symbol: Some("EVIL".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let app = router(manager);
+
+        let coin = call(&app, "/api/v1/get_coin?token=mint").await;
+        assert_eq!(coin["data"]["account"], "evil");
+        assert_eq!(
+            coin["data"]["program"],
+            "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"
+        );
+        assert_eq!(coin["data"]["amount"], "0");
+        assert_eq!(coin["data"]["decimals"], 0);
+        assert_eq!(coin["data"]["name"], "Evil Coin");
+        assert_eq!(coin["data"]["symbol"], "EVIL");
+        let events = call(&app, "/api/v1/events?account=evil&since=0").await;
+        assert_eq!(events["data"], serde_json::json!([]));
+        let changes = call(&app, "/api/v1/balance_changes?account=evil").await;
+        assert_eq!(changes["data"], serde_json::json!([]));
+        let swaps = call(&app, "/api/v1/swaps?account=evil&token=mint").await;
+        assert_eq!(swaps["data"], serde_json::json!([]));
+
+        // nobody tracked holds it, so the supply is never looked up
+        let holders = call(&app, "/api/v1/token_holders?token=other").await;
+        assert_eq!(holders["data"]["holders"], serde_json::json!([]));
+        assert_eq!(holders["data"]["tracked_share"], 0.0);
+        assert!(holders["data"]["name"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_swap_handlers() {
+        let manager = Arc::new(MemoryRepository::new());
+        for (signature, slot, sol) in [("sig1", 1, 1_000_000_000), ("sig2", 2, 3_000_000_000)] {
+            let transaction = IngestedTransaction {
+                signature: signature.to_string(),
+                slot,
+                swap: Some(swap_from_legs(
+                    Dex::Raydium,
+                    "675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8",
+                    SwapLeg {
+                        mint: WSOL_MINT.to_string(),
+                        amount: sol,
+                        decimals: 9,
+                    },
+                    SwapLeg {
+                        mint: "mint".to_string(),
+                        amount: 1_000_000,
+                        decimals: 6,
+                    },
+                )),
+                ..Default::default()
+            };
+            manager
+                .add_ingested_transaction("evil", &transaction)
+                .await
+                .unwrap();
+        }
+        manager
+            .add_token(&Token {
+                mint: "mint".to_string(),
+                symbol: Some("EVIL".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let app = router(manager);
+
+        let swaps = call(&app, "/api/v1/swaps?token=mint&limit=1").await;
+        assert_eq!(swaps["data"].as_array().unwrap().len(), 1);
+        assert_eq!(swaps["data"][0]["signature"], "sig2");
+        assert_eq!(swaps["data"][0]["side"], "buy");
+        assert_eq!(swaps["data"][0]["dex"], "raydium");
+        assert_eq!(swaps["data"][0]["price"], 3.0);
+        assert_eq!(swaps["data"][0]["symbol"], "EVIL");
+
+        let prices = call(&app, "/api/v1/entry_prices?token=mint").await;
+        assert_eq!(prices["data"][0]["account"], "evil");
+        assert_eq!(prices["data"][0]["buys"], 2);
+        assert_eq!(prices["data"][0]["entry_price"], 2.0);
+        assert_eq!(prices["data"][0]["quote_mint"], WSOL_MINT);
+        assert_eq!(prices["data"][0]["symbol"], "EVIL");
+        let prices = call(&app, "/api/v1/entry_prices?token=mint&account=nobody").await;
+        assert_eq!(prices["data"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_token_holders_response() {
+        let manager = MemoryRepository::new();
+        for (account, amount) in [("small", 250_000), ("big", 1_500_000)] {
+            let coin = NewCoin {
+                amount,
+                decimals: 6,
+                pubkey: format!("{}-ata", account),
+                ..NewCoin::new("mint")
+            };
+            manager.add_new_coin(account, &coin).await.unwrap();
+        }
+        let coins = manager.get_coins_with_token("mint").await.unwrap();
+
+        let response = TokenHoldersResponse::new("mint", coins, Some(10_000_000));
+        let accounts: Vec<&str> = response
+            .holders
+            .iter()
+            .map(|h| h.account.as_str())
+            .collect();
+        assert_eq!(accounts, vec!["big", "small"]);
+        assert_eq!(response.holders[0].ui_amount, 1.5);
+        assert_eq!(response.holders[0].pubkey.as_deref(), Some("big-ata"));
+        assert_eq!(response.tracked_amount, "1750000");
+        assert_eq!(response.tracked_ui_amount, 1.75);
+        assert_eq!(response.supply.as_deref(), Some("10000000"));
+        assert_eq!(response.tracked_share, Some(0.175));
+
+        let coins = manager.get_coins_with_token("mint").await.unwrap();
+        let response = TokenHoldersResponse::new("mint", coins, None);
+        assert_eq!(response.tracked_share, None);
+    }
+}
@@ -0,0 +1,311 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Result};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+
+use crate::{
+    config::WebhookConfig,
+    models::{PositionEvent, PositionEventType},
+    repository::{Repository, SharedRepository},
+};
+
+pub const SIGNATURE_HEADER: &str = "X-Angel-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Angel-Timestamp";
+
+#[derive(Debug, Serialize)]
+struct WebhookPayload<'a> {
+    #[serde(rename = "type")]
+    payload_type: &'static str,
+    data: &'a PositionEvent,
+}
+
+// hex(hmac_sha256(secret, "{timestamp}.{body}")), receivers recompute it to verify the sender
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts any key length");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[derive(Clone)]
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    webhooks: Arc<Vec<WebhookConfig>>,
+}
+
+impl WebhookNotifier {
+    pub fn new(webhooks: Vec<WebhookConfig>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            webhooks: Arc::new(webhooks),
+        }
+    }
+
+    // only opening and exiting a position is pushed, increases and decreases stay in the events api
+    fn should_notify(event: &PositionEvent) -> bool {
+        matches!(
+            event.event_type,
+            PositionEventType::NewPosition | PositionEventType::Exited
+        )
+    }
+
+    // deliver in the background so retries never hold up the daemon loop
+    pub fn notify(&self, manager: SharedRepository, events: &[PositionEvent]) {
+        if self.webhooks.is_empty() {
+            return;
+        }
+        let events: Vec<PositionEvent> = events
+            .iter()
+            .filter(|event| Self::should_notify(event))
+            .cloned()
+            .collect();
+        if events.is_empty() {
+            return;
+        }
+
+        let notifier = self.clone();
+        tokio::spawn(async move {
+            for event in &events {
+                for webhook in notifier.webhooks.iter() {
+                    if let Err(e) = notifier.deliver(manager.as_ref(), webhook, event).await {
+                        error!(
+                            "webhook delivery error: {:?}, url: {}, event: {}",
+                            e, webhook.url, event.id
+                        );
+                    }
+                }
+            }
+        });
+    }
+
+    // post one event to one webhook, retrying with exponential backoff. every attempt is
+    // written to webhook_deliveries
+    pub async fn deliver(
+        &self,
+        manager: &dyn Repository,
+        webhook: &WebhookConfig,
+        event: &PositionEvent,
+    ) -> Result<()> {
+        let body = serde_json::to_string(&WebhookPayload {
+            payload_type: "position_event",
+            data: event,
+        })?;
+        let delivery_id = manager
+            .add_webhook_delivery(&webhook.url, event.id, &body)
+            .await?;
+
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let timestamp = chrono::Local::now().timestamp();
+            let result = self
+                .client
+                .post(&webhook.url)
+                .timeout(Duration::from_secs(webhook.timeout))
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(TIMESTAMP_HEADER, timestamp.to_string())
+                .header(SIGNATURE_HEADER, sign(&webhook.secret, timestamp, &body))
+                .body(body.clone())
+                .send()
+                .await;
+
+            let (status_code, error, retryable) = match result {
+                Ok(response) if response.status().is_success() => {
+                    manager
+                        .update_webhook_delivery(
+                            delivery_id,
+                            attempts,
+                            Some(response.status().as_u16() as i64),
+                            None,
+                            true,
+                        )
+                        .await?;
+                    info!(
+                        "webhook delivered, url: {}, event: {}, attempts: {}",
+                        webhook.url, event.id, attempts
+                    );
+                    return Ok(());
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    // a 4xx other than 429 will not get better by sending it again
+                    let retryable = status.is_server_error()
+                        || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+                    (
+                        Some(status.as_u16() as i64),
+                        format!("http status {}", status),
+                        retryable,
+                    )
+                }
+                Err(e) => (None, e.to_string(), true),
+            };
+
+            manager
+                .update_webhook_delivery(delivery_id, attempts, status_code, Some(&error), false)
+                .await?;
+            if !retryable || attempts > webhook.max_retries as i64 {
+                return Err(anyhow!(
+                    "webhook failed after {} attempts: {}",
+                    attempts,
+                    error
+                ));
+            }
+
+            let backoff = webhook
+                .backoff_ms
+                .saturating_mul(1 << (attempts - 1).min(16));
+            warn!(
+                "webhook attempt {} failed: {}, url: {}, retry in {}ms",
+                attempts, error, webhook.url, backoff
+            );
+            sleep(Duration::from_millis(backoff)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::MemoryRepository;
+    use axum::{extract::State, http::HeaderMap, routing::post, Router};
+    use reqwest::StatusCode;
+    use std::sync::Mutex;
+    use tokio::net::TcpListener;
+
+    // answers with the given statuses in order, then 200, and keeps what it received
+    #[derive(Clone, Default)]
+    struct StandIn {
+        statuses: Arc<Mutex<Vec<StatusCode>>>,
+        received: Arc<Mutex<Vec<(HeaderMap, String)>>>,
+    }
+
+    async fn hook(State(stand_in): State<StandIn>, headers: HeaderMap, body: String) -> StatusCode {
+        stand_in.received.lock().unwrap().push((headers, body));
+        let mut statuses = stand_in.statuses.lock().unwrap();
+        if statuses.is_empty() {
+            StatusCode::OK
+        } else {
+            statuses.remove(0)
+        }
+    }
+
+    async fn start_stand_in(statuses: Vec<StatusCode>) -> (String, StandIn) {
+        let stand_in = StandIn {
+            statuses: Arc::new(Mutex::new(statuses)),
+            ..Default::default()
+        };
+        let app = Router::new()
+            .route("/hook", post(hook))
+            .with_state(stand_in.clone());
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, stand_in)
+    }
+
+    fn webhook(url: &str, max_retries: u32) -> WebhookConfig {
+        WebhookConfig {
+            url: url.to_string(),
+            secret: "secret".to_string(),
+            max_retries,
+            backoff_ms: 1,
+            timeout: 5,
+        }
+    }
+
+    async fn saved_event(manager: &dyn Repository) -> PositionEvent {
+        let event = PositionEvent {
+            id: 0,
+            account: "evil".to_string(),
+            token: "mint".to_string(),
+            event_type: PositionEventType::NewPosition,
+            previous_amount: "0".to_string(),
+            amount: "10".to_string(),
+            delta: "10".to_string(),
+            decimals: 0,
+            ui_delta: 10.0,
+            slot: 1,
+            created_at: 1,
+        };
+        manager
+            .add_position_events(&[event])
+            .await
+            .unwrap()
+            .remove(0)
+    }
+
+    #[tokio::test]
+    async fn test_deliver_retries_and_signs() {
+        let manager = MemoryRepository::new();
+        let event = saved_event(&manager).await;
+        let (url, stand_in) = start_stand_in(vec![
+            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::TOO_MANY_REQUESTS,
+        ])
+        .await;
+
+        let notifier = WebhookNotifier::new(vec![]);
+        notifier
+            .deliver(&manager, &webhook(&url, 3), &event)
+            .await
+            .unwrap();
+
+        let received = stand_in.received.lock().unwrap().clone();
+        assert_eq!(received.len(), 3);
+        let (headers, body) = received.last().unwrap();
+        let timestamp: i64 = headers[TIMESTAMP_HEADER].to_str().unwrap().parse().unwrap();
+        assert_eq!(
+            headers[SIGNATURE_HEADER].to_str().unwrap(),
+            sign("secret", timestamp, body)
+        );
+        let payload: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(payload["type"], "position_event");
+        assert_eq!(payload["data"]["event_type"], "new_position");
+
+        let deliveries = manager
+            .get_webhook_deliveries_with_event(event.id)
+            .await
+            .unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].attempts, 3);
+        assert_eq!(deliveries[0].status_code, Some(200));
+        assert_eq!(deliveries[0].delivered, 1);
+    }
+
+    #[tokio::test]
+    async fn test_deliver_gives_up() {
+        let manager = MemoryRepository::new();
+        let event = saved_event(&manager).await;
+
+        // retries run out
+        let (url, stand_in) = start_stand_in(vec![StatusCode::BAD_GATEWAY; 5]).await;
+        let notifier = WebhookNotifier::new(vec![]);
+        assert!(notifier
+            .deliver(&manager, &webhook(&url, 1), &event)
+            .await
+            .is_err());
+        assert_eq!(stand_in.received.lock().unwrap().len(), 2);
+
+        // a client error is not retried
+        let (url, stand_in) = start_stand_in(vec![StatusCode::BAD_REQUEST]).await;
+        assert!(notifier
+            .deliver(&manager, &webhook(&url, 3), &event)
+            .await
+            .is_err());
+        assert_eq!(stand_in.received.lock().unwrap().len(), 1);
+
+        let deliveries = manager
+            .get_webhook_deliveries_with_event(event.id)
+            .await
+            .unwrap();
+        assert_eq!(deliveries.len(), 2);
+        assert_eq!(deliveries[0].attempts, 2);
+        assert_eq!(deliveries[0].status_code, Some(502));
+        assert_eq!(deliveries[1].attempts, 1);
+        assert!(deliveries.iter().all(|d| d.delivered == 0));
+    }
+}
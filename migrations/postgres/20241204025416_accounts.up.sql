@@ -0,0 +1,8 @@
+-- Add up migration script here
+
+CREATE TABLE accounts (
+    id BIGSERIAL PRIMARY KEY,
+    account VARCHAR(255) NOT NULL UNIQUE, -- account address
+    created_at BIGINT NOT NULL, -- created at
+    deleted BIGINT DEFAULT 0 -- deleted flag , 1 is deleted
+);
@@ -0,0 +1,9 @@
+-- Add up migration script here
+
+CREATE TABLE coins (
+    id BIGSERIAL PRIMARY KEY,
+    account VARCHAR(255) NOT NULL, -- account address
+    token VARCHAR(255) NOT NULL, -- token address
+    created_at BIGINT NOT NULL, -- created at
+    deleted BIGINT DEFAULT 0 -- deleted flag , 1 is deleted
+);
@@ -0,0 +1,15 @@
+-- Add up migration script here
+
+-- one row per (account, mint) observed by a daemon pass
+CREATE TABLE holding_snapshots (
+    id BIGSERIAL PRIMARY KEY,
+    account VARCHAR(255) NOT NULL, -- account address
+    mint VARCHAR(255) NOT NULL, -- token address
+    amount VARCHAR(32) NOT NULL, -- raw amount, u64 as text, "0" means the position was exited
+    decimals BIGINT NOT NULL, -- token decimals
+    ui_amount DOUBLE PRECISION NOT NULL, -- amount / 10^decimals
+    slot BIGINT NOT NULL, -- rpc context slot
+    observed_at BIGINT NOT NULL -- observed at
+);
+
+CREATE INDEX idx_holding_snapshots_account_mint ON holding_snapshots (account, mint, observed_at);
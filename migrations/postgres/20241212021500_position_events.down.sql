@@ -0,0 +1,3 @@
+-- Add down migration script here
+
+DROP TABLE position_events;
\ No newline at end of file
@@ -0,0 +1,17 @@
+-- Add up migration script here
+
+-- one row per (webhook, event), updated after every attempt
+CREATE TABLE webhook_deliveries (
+    id BIGSERIAL PRIMARY KEY,
+    url VARCHAR(1024) NOT NULL, -- webhook url
+    event_id BIGINT NOT NULL, -- position_events.id
+    payload TEXT NOT NULL, -- json body that was signed and posted
+    attempts BIGINT NOT NULL, -- attempts made so far
+    status_code BIGINT, -- http status of the last attempt
+    error TEXT, -- error of the last attempt
+    delivered BIGINT NOT NULL DEFAULT 0, -- 1 is delivered
+    created_at BIGINT NOT NULL, -- created at
+    updated_at BIGINT NOT NULL -- updated at
+);
+
+CREATE INDEX idx_webhook_deliveries_event ON webhook_deliveries (event_id);
@@ -0,0 +1,8 @@
+-- Add up migration script here
+
+-- the token program of the mint and the token-2022 extensions that matter before buying
+ALTER TABLE coins ADD COLUMN program VARCHAR(255) NOT NULL DEFAULT 'TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA'; -- spl token or token-2022 program id
+ALTER TABLE coins ADD COLUMN transfer_fee_bps BIGINT; -- transfer fee in basis points
+ALTER TABLE coins ADD COLUMN transfer_fee_max VARCHAR(32); -- raw u64 cap of the transfer fee
+ALTER TABLE coins ADD COLUMN permanent_delegate VARCHAR(255); -- can move or burn any holder's tokens
+ALTER TABLE coins ADD COLUMN non_transferable BIGINT NOT NULL DEFAULT 0; -- 1 is non-transferable
@@ -0,0 +1,6 @@
+-- Add up migration script here
+
+-- how much of the token the account holds, exact raw amount and decimals instead of a float
+ALTER TABLE coins ADD COLUMN amount VARCHAR(32) NOT NULL DEFAULT '0'; -- raw u64 amount over all token accounts of the mint
+ALTER TABLE coins ADD COLUMN decimals BIGINT NOT NULL DEFAULT 0; -- decimals of the mint
+ALTER TABLE coins ADD COLUMN pubkey VARCHAR(255); -- the token account holding the most
@@ -0,0 +1,13 @@
+-- Add up migration script here
+
+-- metadata of the mints, resolved once from metaplex token metadata or the token-2022 metadata extension
+CREATE TABLE tokens (
+    mint VARCHAR(255) PRIMARY KEY, -- token mint
+    name VARCHAR(255), -- null when the mint has no metadata
+    symbol VARCHAR(64),
+    uri VARCHAR(1024),
+    decimals BIGINT NOT NULL, -- decimals of the mint
+    program VARCHAR(255) NOT NULL, -- spl token or token-2022 program id
+    source VARCHAR(32) NOT NULL, -- metaplex, token_2022 or none
+    updated_at BIGINT NOT NULL -- updated at
+);
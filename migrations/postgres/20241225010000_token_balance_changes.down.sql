@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP TABLE signature_cursors;
+DROP TABLE token_balance_changes;
@@ -0,0 +1,27 @@
+-- Add up migration script here
+
+-- token balance changes of a tracked account per transaction, from the pre/post token balances
+CREATE TABLE token_balance_changes (
+    id BIGSERIAL PRIMARY KEY,
+    account VARCHAR(255) NOT NULL, -- account address
+    signature VARCHAR(128) NOT NULL, -- transaction signature
+    slot BIGINT NOT NULL, -- slot of the transaction
+    block_time BIGINT, -- unix time of the block, null when the node does not know it
+    mint VARCHAR(255) NOT NULL, -- token address
+    pre_amount VARCHAR(32) NOT NULL, -- raw amount before the transaction
+    post_amount VARCHAR(32) NOT NULL, -- raw amount after the transaction
+    delta VARCHAR(32) NOT NULL, -- signed raw amount change
+    decimals BIGINT NOT NULL, -- token decimals
+    created_at BIGINT NOT NULL -- created at
+);
+
+CREATE UNIQUE INDEX idx_token_balance_changes_signature ON token_balance_changes (account, signature, mint);
+CREATE INDEX idx_token_balance_changes_mint ON token_balance_changes (mint, slot);
+
+-- the newest signature of an account that was ingested, ingestion resumes after it
+CREATE TABLE signature_cursors (
+    account VARCHAR(255) PRIMARY KEY, -- account address
+    signature VARCHAR(128) NOT NULL, -- newest processed signature
+    slot BIGINT NOT NULL, -- slot of that signature
+    updated_at BIGINT NOT NULL -- updated at
+);
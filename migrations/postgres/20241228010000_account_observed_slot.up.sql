@@ -0,0 +1,5 @@
+-- Add up migration script here
+
+-- set on every daemon pass, also when the account holds nothing, so the first pass of an
+-- empty wallet is its baseline and its first buy is a new position
+ALTER TABLE accounts ADD COLUMN last_observed_slot BIGINT; -- null until the daemon observed the account
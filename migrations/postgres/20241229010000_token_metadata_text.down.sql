@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+ALTER TABLE tokens ALTER COLUMN uri TYPE VARCHAR(1024) USING LEFT(uri, 1024);
+ALTER TABLE tokens ALTER COLUMN symbol TYPE VARCHAR(64) USING LEFT(symbol, 64);
+ALTER TABLE tokens ALTER COLUMN name TYPE VARCHAR(255) USING LEFT(name, 255);
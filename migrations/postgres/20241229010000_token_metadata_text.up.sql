@@ -0,0 +1,6 @@
+-- Add up migration script here
+
+-- token-2022 metadata strings have no length limit, a long one would fail the whole insert
+ALTER TABLE tokens ALTER COLUMN name TYPE TEXT;
+ALTER TABLE tokens ALTER COLUMN symbol TYPE TEXT;
+ALTER TABLE tokens ALTER COLUMN uri TYPE TEXT;
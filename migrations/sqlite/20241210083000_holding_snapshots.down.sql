@@ -0,0 +1,3 @@
+-- Add down migration script here
+
+DROP TABLE holding_snapshots;
\ No newline at end of file
@@ -0,0 +1,15 @@
+-- Add up migration script here
+
+-- one row per (account, mint) observed by a daemon pass
+CREATE TABLE holding_snapshots (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account VARCHAR(255) NOT NULL, -- account address
+    mint VARCHAR(255) NOT NULL, -- token address
+    amount VARCHAR(32) NOT NULL, -- raw amount, u64 as text, "0" means the position was exited
+    decimals INTEGER NOT NULL, -- token decimals
+    ui_amount REAL NOT NULL, -- amount / 10^decimals
+    slot INTEGER NOT NULL, -- rpc context slot
+    observed_at INTEGER NOT NULL -- observed at
+);
+
+CREATE INDEX idx_holding_snapshots_account_mint ON holding_snapshots (account, mint, observed_at);
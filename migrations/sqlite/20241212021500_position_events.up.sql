@@ -0,0 +1,19 @@
+-- Add up migration script here
+
+-- position changes found by diffing two daemon passes of an account
+CREATE TABLE position_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account VARCHAR(255) NOT NULL, -- account address
+    token VARCHAR(255) NOT NULL, -- token address
+    event_type VARCHAR(32) NOT NULL, -- new_position, increased, decreased, exited
+    previous_amount VARCHAR(32) NOT NULL, -- raw amount before the change
+    amount VARCHAR(32) NOT NULL, -- raw amount after the change
+    delta VARCHAR(32) NOT NULL, -- signed raw amount change
+    decimals INTEGER NOT NULL, -- token decimals
+    ui_delta REAL NOT NULL, -- signed ui amount change
+    slot INTEGER NOT NULL, -- rpc context slot
+    created_at INTEGER NOT NULL -- created at
+);
+
+CREATE INDEX idx_position_events_account ON position_events (account, created_at);
+CREATE INDEX idx_position_events_token ON position_events (token, created_at);
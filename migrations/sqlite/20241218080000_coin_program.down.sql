@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+ALTER TABLE coins DROP COLUMN non_transferable;
+ALTER TABLE coins DROP COLUMN permanent_delegate;
+ALTER TABLE coins DROP COLUMN transfer_fee_max;
+ALTER TABLE coins DROP COLUMN transfer_fee_bps;
+ALTER TABLE coins DROP COLUMN program;
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+ALTER TABLE coins DROP COLUMN pubkey;
+ALTER TABLE coins DROP COLUMN decimals;
+ALTER TABLE coins DROP COLUMN amount;
@@ -0,0 +1,27 @@
+-- Add up migration script here
+
+-- swaps of a tracked account decoded from its transactions, one per transaction
+CREATE TABLE swaps (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account VARCHAR(255) NOT NULL, -- account address
+    signature VARCHAR(128) NOT NULL, -- transaction signature
+    slot INTEGER NOT NULL, -- slot of the transaction
+    block_time INTEGER, -- unix time of the block, null when the node does not know it
+    dex VARCHAR(32) NOT NULL, -- raydium, orca, meteora, jupiter or pump_fun
+    program VARCHAR(255) NOT NULL, -- dex program id
+    side VARCHAR(16) NOT NULL, -- buy, sell or swap
+    input_mint VARCHAR(255) NOT NULL, -- token paid, wrapped sol for sol
+    input_amount VARCHAR(32) NOT NULL, -- raw amount paid
+    input_decimals INTEGER NOT NULL, -- decimals of the input token
+    output_mint VARCHAR(255) NOT NULL, -- token received
+    output_amount VARCHAR(32) NOT NULL, -- raw amount received
+    output_decimals INTEGER NOT NULL, -- decimals of the output token
+    base_mint VARCHAR(255) NOT NULL, -- token that was bought or sold
+    quote_mint VARCHAR(255) NOT NULL, -- token it was priced in
+    price REAL NOT NULL, -- quote per base in ui amounts
+    created_at INTEGER NOT NULL -- created at
+);
+
+CREATE UNIQUE INDEX idx_swaps_signature ON swaps (account, signature);
+CREATE INDEX idx_swaps_input_mint ON swaps (input_mint, slot);
+CREATE INDEX idx_swaps_output_mint ON swaps (output_mint, slot);
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+ALTER TABLE accounts DROP COLUMN tags;
+ALTER TABLE accounts DROP COLUMN note;
+ALTER TABLE accounts DROP COLUMN label;
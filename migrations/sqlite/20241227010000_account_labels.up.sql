@@ -0,0 +1,6 @@
+-- Add up migration script here
+
+-- what the user knows about a tracked wallet
+ALTER TABLE accounts ADD COLUMN label VARCHAR(255); -- short name shown next to the address
+ALTER TABLE accounts ADD COLUMN note TEXT; -- free text
+ALTER TABLE accounts ADD COLUMN tags TEXT NOT NULL DEFAULT ''; -- comma separated
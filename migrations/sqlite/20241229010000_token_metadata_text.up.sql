@@ -0,0 +1,4 @@
+-- Add up migration script here
+
+-- sqlite does not enforce varchar lengths, only postgres needs its metadata columns widened
+SELECT 1;